@@ -4,6 +4,7 @@
 //! - Update a PR's base branch
 //! - Merge a PR using squash strategy
 //! - Close a PR with a comment
+//! - Check branch protection and delete branches after landing
 
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -38,7 +39,6 @@ struct AddCommentRequest<'a> {
 
 /// Response from merge endpoint
 #[derive(Deserialize, Debug)]
-#[allow(dead_code)]
 struct MergeResponse {
     sha: String,
     merged: bool,
@@ -51,6 +51,21 @@ struct PrResponse {
     html_url: String,
 }
 
+/// A successfully merged PR
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergedPr {
+    /// SHA of the squash commit on the target branch
+    pub sha: String,
+    /// HTML URL of the merged PR
+    pub html_url: String,
+}
+
+/// Response from the get-branch endpoint
+#[derive(Deserialize, Debug)]
+struct BranchResponse {
+    protected: bool,
+}
+
 fn build_request(client: &Client, credentials: &Credentials, url: &str) -> reqwest::RequestBuilder {
     client
         .patch(url)
@@ -86,6 +101,32 @@ fn build_post_request(
         .header("Accept", "application/vnd.github.v3+json")
 }
 
+fn build_get_request(
+    client: &Client,
+    credentials: &Credentials,
+    url: &str,
+) -> reqwest::RequestBuilder {
+    client
+        .get(url)
+        .timeout(Duration::from_secs(10))
+        .header("Authorization", format!("token {}", credentials.token))
+        .header("User-Agent", "luqven/gh-stack")
+        .header("Accept", "application/vnd.github.v3+json")
+}
+
+fn build_delete_request(
+    client: &Client,
+    credentials: &Credentials,
+    url: &str,
+) -> reqwest::RequestBuilder {
+    client
+        .delete(url)
+        .timeout(Duration::from_secs(30))
+        .header("Authorization", format!("token {}", credentials.token))
+        .header("User-Agent", "luqven/gh-stack")
+        .header("Accept", "application/vnd.github.v3+json")
+}
+
 /// Update a PR's base branch
 ///
 /// # Arguments
@@ -130,12 +171,12 @@ pub async fn update_pr_base(
 /// * `credentials` - GitHub credentials
 ///
 /// # Returns
-/// The HTML URL of the merged PR
+/// The merge commit SHA and HTML URL of the merged PR
 pub async fn merge_pr(
     pr_number: usize,
     repository: &str,
     credentials: &Credentials,
-) -> Result<String, Box<dyn Error>> {
+) -> Result<MergedPr, Box<dyn Error>> {
     let client = Client::new();
     let url = format!(
         "{}/repos/{}/pulls/{}/merge",
@@ -179,7 +220,10 @@ pub async fn merge_pr(
         .await?;
 
     let pr_data: PrResponse = pr_response.json().await?;
-    Ok(pr_data.html_url)
+    Ok(MergedPr {
+        sha: merge_response.sha,
+        html_url: pr_data.html_url,
+    })
 }
 
 /// Close a PR with a comment
@@ -238,6 +282,83 @@ pub async fn close_pr_with_comment(
     Ok(())
 }
 
+/// Check whether a branch has branch protection enabled
+///
+/// Returns `false` if the branch doesn't exist.
+///
+/// # Arguments
+/// * `branch` - The branch name
+/// * `repository` - Repository in "owner/repo" format
+/// * `credentials` - GitHub credentials
+pub async fn is_branch_protected(
+    branch: &str,
+    repository: &str,
+    credentials: &Credentials,
+) -> Result<bool, Box<dyn Error>> {
+    let client = Client::new();
+    let url = format!(
+        "{}/repos/{}/branches/{}",
+        super::github_api_base(),
+        repository,
+        super::encode_branch(branch)
+    );
+
+    let response = build_get_request(&client, credentials, &url).send().await?;
+
+    if response.status() == 404 {
+        return Ok(false);
+    }
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        return Err(format!("Failed to fetch branch ({}): {}", status, text).into());
+    }
+
+    let branch_response: BranchResponse = response.json().await?;
+    Ok(branch_response.protected)
+}
+
+/// Delete a branch on the remote
+///
+/// # Arguments
+/// * `branch` - The branch name
+/// * `repository` - Repository in "owner/repo" format
+/// * `credentials` - GitHub credentials
+///
+/// # Returns
+/// `true` if the branch was deleted, `false` if it no longer existed
+pub async fn delete_branch(
+    branch: &str,
+    repository: &str,
+    credentials: &Credentials,
+) -> Result<bool, Box<dyn Error>> {
+    let client = Client::new();
+    let url = format!(
+        "{}/repos/{}/git/refs/heads/{}",
+        super::github_api_base(),
+        repository,
+        super::encode_branch(branch)
+    );
+
+    let response = build_delete_request(&client, credentials, &url)
+        .send()
+        .await?;
+
+    // GitHub answers 422 "Reference does not exist" for already-deleted branches
+    if response.status() == 404 || response.status() == 422 {
+        return Ok(false);
+    }
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        return Err(format!("Failed to delete branch ({}): {}", status, text).into());
+    }
+
+    Ok(true)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -294,7 +415,13 @@ mod tests {
         let result = merge_pr(123, "owner/repo", &creds).await;
 
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "https://github.com/owner/repo/pull/123");
+        assert_eq!(
+            result.unwrap(),
+            MergedPr {
+                sha: "abc123".to_string(),
+                html_url: "https://github.com/owner/repo/pull/123".to_string(),
+            }
+        );
         merge_mock.assert_async().await;
         pr_mock.assert_async().await;
     }
@@ -333,4 +460,79 @@ mod tests {
         comment_mock.assert_async().await;
         close_mock.assert_async().await;
     }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_is_branch_protected() {
+        let mut server = Server::new_async().await;
+
+        let protected_mock = server
+            .mock("GET", "/repos/owner/repo/branches/release")
+            .with_status(200)
+            .with_body(r#"{"name": "release", "protected": true}"#)
+            .create_async()
+            .await;
+
+        let missing_mock = server
+            .mock("GET", "/repos/owner/repo/branches/gone")
+            .with_status(404)
+            .with_body(r#"{"message": "Branch not found"}"#)
+            .create_async()
+            .await;
+
+        std::env::set_var("GITHUB_API_BASE", server.url());
+
+        let creds = Credentials::new("test-token");
+        assert!(is_branch_protected("release", "owner/repo", &creds)
+            .await
+            .unwrap());
+        assert!(!is_branch_protected("gone", "owner/repo", &creds)
+            .await
+            .unwrap());
+
+        protected_mock.assert_async().await;
+        missing_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_delete_branch() {
+        let mut server = Server::new_async().await;
+
+        let deleted_mock = server
+            .mock("DELETE", "/repos/owner/repo/git/refs/heads/feature-1")
+            .with_status(204)
+            .create_async()
+            .await;
+
+        let missing_mock = server
+            .mock("DELETE", "/repos/owner/repo/git/refs/heads/feature-2")
+            .with_status(422)
+            .with_body(r#"{"message": "Reference does not exist"}"#)
+            .create_async()
+            .await;
+
+        let encoded_mock = server
+            .mock("DELETE", "/repos/owner/repo/git/refs/heads/fix/%2312")
+            .with_status(204)
+            .create_async()
+            .await;
+
+        std::env::set_var("GITHUB_API_BASE", server.url());
+
+        let creds = Credentials::new("test-token");
+        assert!(delete_branch("feature-1", "owner/repo", &creds)
+            .await
+            .unwrap());
+        assert!(!delete_branch("feature-2", "owner/repo", &creds)
+            .await
+            .unwrap());
+        assert!(delete_branch("fix/#12", "owner/repo", &creds)
+            .await
+            .unwrap());
+
+        deleted_mock.assert_async().await;
+        missing_mock.assert_async().await;
+        encoded_mock.assert_async().await;
+    }
 }
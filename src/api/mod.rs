@@ -1,5 +1,6 @@
 use crate::Credentials;
 use chrono::{DateTime, Utc};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
 use reqwest::{Client, RequestBuilder, Response};
 use std::error::Error;
 use std::fmt;
@@ -31,6 +32,25 @@ pub fn github_api_base() -> String {
     GITHUB_API_BASE.to_string()
 }
 
+/// Everything but the unreserved characters gets escaped in a path segment
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// A branch name for use in a URL path
+///
+/// Each `/`-separated part is percent-encoded, so names containing `#`, `%`
+/// or `?` reach the API intact.
+pub fn encode_branch(branch: &str) -> String {
+    branch
+        .split('/')
+        .map(|part| utf8_percent_encode(part, PATH_SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
 /// Maximum number of retry attempts for rate-limited requests
 const MAX_RETRIES: u32 = 3;
 
@@ -204,6 +224,12 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_encode_branch() {
+        assert_eq!(encode_branch("feat/part-1"), "feat/part-1");
+        assert_eq!(encode_branch("fix/#12 100%?"), "fix/%2312%20100%25%3F");
+    }
+
     #[tokio::test]
     async fn test_mock_github_api_search() {
         let mut server = Server::new_async().await;
@@ -77,6 +77,7 @@ pub struct PullRequest {
 impl PullRequest {
     /// Create a new PullRequest for testing purposes
     #[cfg(test)]
+    #[allow(clippy::too_many_arguments)]
     pub fn new_for_test(
         number: usize,
         head: &str,
@@ -111,8 +112,26 @@ impl PullRequest {
         }
     }
 
+    /// Move a test PullRequest into another repository
+    #[cfg(test)]
+    pub fn in_repository(mut self, repository: &str) -> Self {
+        self.url = format!(
+            "https://api.github.com/repos/{}/pulls/{}",
+            repository, self.number
+        );
+        self
+    }
+
+    /// Point a test PullRequest's head at a specific commit
+    #[cfg(test)]
+    pub fn with_head_sha(mut self, sha: &str) -> Self {
+        self.head.sha = sha.to_string();
+        self
+    }
+
     /// Create a new PullRequest for testing purposes with updated_at field
     #[cfg(test)]
+    #[allow(clippy::too_many_arguments)]
     pub fn new_for_test_with_updated_at(
         number: usize,
         head: &str,
@@ -148,6 +167,42 @@ impl PullRequest {
         }
     }
 
+    /// A stand-in for a branch that has no PR yet, so a stack found from
+    /// local branches alone can be drawn and restacked
+    ///
+    /// `head_sha` is where the remote has the branch (empty if it was never
+    /// pushed), as GitHub would report it for a real PR. The id is 0, which
+    /// GitHub never hands out.
+    pub fn unopened(number: usize, head: &str, base: &str, head_sha: &str, title: &str) -> Self {
+        PullRequest {
+            id: 0,
+            number,
+            head: PullRequestRef {
+                label: head.to_string(),
+                gitref: head.to_string(),
+                sha: head_sha.to_string(),
+            },
+            base: PullRequestRef {
+                label: base.to_string(),
+                gitref: base.to_string(),
+                sha: String::new(),
+            },
+            title: title.to_string(),
+            url: String::new(),
+            body: None,
+            state: PullRequestStatus::Open,
+            merged_at: None,
+            updated_at: None,
+            draft: false,
+            reviews: vec![],
+        }
+    }
+
+    /// Whether this stands in for a branch without a PR (see `unopened`)
+    pub fn is_unopened(&self) -> bool {
+        self.id == 0
+    }
+
     pub fn head(&self) -> &str {
         &self.head.gitref
     }
@@ -164,6 +219,16 @@ impl PullRequest {
         self.number
     }
 
+    /// Repository the PR belongs to, in "owner/repo" format
+    ///
+    /// Taken from the API URL (`.../repos/owner/repo/pulls/123`).
+    pub fn repository(&self) -> String {
+        self.url
+            .split_once("/repos/")
+            .map(|(_, path)| path.splitn(3, '/').take(2).collect::<Vec<_>>().join("/"))
+            .unwrap_or_default()
+    }
+
     pub fn title(&self) -> String {
         let title = self.title.trim();
         let title = match &self.draft {
@@ -215,6 +280,15 @@ impl PullRequest {
         &self.head.sha
     }
 
+    /// Copy of this PR pointing at a different base branch
+    ///
+    /// Used to reflect a base change made through the API without refetching.
+    pub fn with_base(&self, base: &str) -> PullRequest {
+        let mut pr = self.clone();
+        pr.base.gitref = base.to_string();
+        pr
+    }
+
     /// Get the raw title without markdown formatting
     pub fn raw_title(&self) -> &str {
         self.title.trim()
@@ -291,6 +365,63 @@ mod tests {
         assert_eq!(pr.head_sha(), "abc123");
     }
 
+    #[test]
+    fn test_with_base() {
+        let pr = PullRequest::new_for_test(
+            7,
+            "feature-2",
+            "feature-1",
+            "Test PR",
+            PullRequestStatus::Open,
+            false,
+            None,
+            vec![],
+        );
+        let retargeted = pr.with_base("main");
+
+        assert_eq!(retargeted.base(), "main");
+        assert_eq!(retargeted.head(), "feature-2");
+        assert_eq!(retargeted.number(), 7);
+        assert_eq!(pr.base(), "feature-1");
+    }
+
+    #[test]
+    fn test_unopened() {
+        let pr = PullRequest::unopened(2, "feat/part-2", "feat/part-1", "", "Add widget");
+        assert!(pr.is_unopened());
+        assert_eq!(pr.head(), "feat/part-2");
+        assert_eq!(pr.base(), "feat/part-1");
+        assert_eq!(pr.head_sha(), "");
+        assert_eq!(*pr.state(), PullRequestStatus::Open);
+
+        let opened = PullRequest::new_for_test(
+            2,
+            "feat/part-2",
+            "feat/part-1",
+            "Add widget",
+            PullRequestStatus::Open,
+            false,
+            None,
+            vec![],
+        );
+        assert!(!opened.is_unopened());
+    }
+
+    #[test]
+    fn test_repository_from_url() {
+        let pr = PullRequest::new_for_test(
+            123,
+            "feature",
+            "main",
+            "Test",
+            PullRequestStatus::Open,
+            false,
+            None,
+            vec![],
+        );
+        assert_eq!(pr.repository(), "test/repo");
+    }
+
     #[test]
     fn test_html_url_conversion() {
         let pr = PullRequest::new_for_test(
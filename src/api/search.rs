@@ -1,8 +1,11 @@
 use futures::future::join_all;
 use serde::Deserialize;
 use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
 
 use crate::api::{PullRequest, PullRequestReview};
+use crate::store::{self, TrackedBranch};
 use crate::{api, Credentials};
 
 #[derive(Deserialize, Debug, Clone)]
@@ -17,6 +20,165 @@ struct SearchResponse {
     items: Vec<SearchItem>,
 }
 
+/// How the PRs that make up a stack are found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Discovery {
+    /// PRs whose title contains the identifier
+    #[default]
+    Title,
+    /// PRs chained by base/head starting from the identifier's branch
+    Chain,
+    /// PRs whose body contains the `<!-- gh-stack:[id] -->` marker
+    Marker,
+    /// PRs carrying a label named after the identifier
+    Label,
+    /// PRs for the branches recorded in the local stack store
+    Local,
+    /// Local branches stacked on each other, found from commit ancestry
+    /// without asking GitHub
+    Git,
+}
+
+impl Discovery {
+    /// Names accepted on the command line
+    pub const NAMES: &'static [&'static str] =
+        &["title", "chain", "marker", "label", "local", "git"];
+}
+
+impl FromStr for Discovery {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "title" => Ok(Discovery::Title),
+            "chain" => Ok(Discovery::Chain),
+            "marker" => Ok(Discovery::Marker),
+            "label" => Ok(Discovery::Label),
+            "local" => Ok(Discovery::Local),
+            "git" => Ok(Discovery::Git),
+            other => Err(format!(
+                "Unknown discovery strategy '{}' (expected one of: {})",
+                other,
+                Discovery::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Discovery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Discovery::Title => "title",
+            Discovery::Chain => "chain",
+            Discovery::Marker => "marker",
+            Discovery::Label => "label",
+            Discovery::Local => "local",
+            Discovery::Git => "git",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The hidden marker `create` writes into a PR body to tie it to a stack
+pub fn stack_marker(identifier: &str) -> String {
+    format!("<!-- gh-stack:[{}] -->", identifier)
+}
+
+/// Search query for a discovery strategy, optionally scoped to a repository
+///
+/// Chain and local discovery don't search, so they have no query.
+fn search_query(
+    discovery: Discovery,
+    identifier: &str,
+    repository: Option<&str>,
+) -> Option<String> {
+    let query = match discovery {
+        Discovery::Title => format!("{} in:title", identifier),
+        // Search matches words, not the exact comment; results are filtered
+        // on the full marker afterwards
+        Discovery::Marker => format!("\"gh-stack:[{}]\" in:body is:pr", identifier),
+        Discovery::Label => format!("label:\"{}\" is:pr", identifier),
+        Discovery::Chain | Discovery::Local | Discovery::Git => return None,
+    };
+
+    Some(match repository {
+        Some(repository) => format!("{} repo:{}", query, repository),
+        None => query,
+    })
+}
+
+/// Find the PRs of a stack using the given discovery strategy
+///
+/// `trunk` and `include_closed` are only used by chain discovery, where
+/// `identifier` is the head branch of any PR in the stack.
+pub async fn discover_pull_requests(
+    discovery: Discovery,
+    identifier: &str,
+    repository: &str,
+    trunk: &str,
+    include_closed: bool,
+    credentials: &Credentials,
+) -> Result<Vec<PullRequest>, Box<dyn Error>> {
+    if matches!(discovery, Discovery::Local | Discovery::Git) {
+        return Err("Local discovery reads the stack from a git repository".into());
+    }
+
+    let query = match search_query(discovery, identifier, Some(repository)) {
+        Some(query) => query,
+        None => {
+            return match api::stack::fetch_pr_by_head(repository, identifier, credentials).await? {
+                Some(pr) => {
+                    let prs = api::stack::discover_stack(
+                        repository,
+                        pr,
+                        trunk,
+                        include_closed,
+                        credentials,
+                    )
+                    .await?;
+                    Ok(with_reviews(prs, credentials).await)
+                }
+                None => Ok(vec![]),
+            };
+        }
+    };
+
+    let prs = search_pull_requests(&query, credentials).await?;
+
+    Ok(match discovery {
+        Discovery::Marker => {
+            let marker = stack_marker(identifier);
+            prs.into_iter()
+                .filter(|pr| pr.body().contains(&marker))
+                .collect()
+        }
+        _ => prs,
+    })
+}
+
+/// Find the open PRs for branches in the local stack store
+///
+/// Each PR is pointed at the branch's recorded parent rather than its base on
+/// GitHub. Branches without an open PR are left out.
+pub async fn fetch_pull_requests_for_branches(
+    repository: &str,
+    branches: &[TrackedBranch],
+    credentials: &Credentials,
+) -> Result<Vec<PullRequest>, Box<dyn Error>> {
+    if branches.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let prs: Vec<PullRequest> = api::stack::fetch_all_open_prs(repository, credentials)
+        .await?
+        .into_iter()
+        .filter(|pr| branches.iter().any(|b| b.name == pr.head()))
+        .collect();
+
+    let prs = store::apply_parents(prs, branches);
+    Ok(with_reviews(prs, credentials).await)
+}
+
 pub async fn fetch_reviews_for_pull_request(
     pr: &PullRequest,
     credentials: &Credentials,
@@ -37,11 +199,29 @@ pub async fn fetch_reviews_for_pull_request(
 pub async fn fetch_pull_requests_matching(
     pattern: &str,
     credentials: &Credentials,
+) -> Result<Vec<PullRequest>, Box<dyn Error>> {
+    let query = search_query(Discovery::Title, pattern, None).unwrap();
+    search_pull_requests(&query, credentials).await
+}
+
+pub async fn fetch_matching_pull_requests_from_repository(
+    pattern: &str,
+    repository: &str,
+    credentials: &Credentials,
+) -> Result<Vec<PullRequest>, Box<dyn Error>> {
+    let query = search_query(Discovery::Title, pattern, Some(repository)).unwrap();
+    search_pull_requests(&query, credentials).await
+}
+
+/// Run an issue search and fetch the full PR (with reviews) for every hit
+async fn search_pull_requests(
+    query: &str,
+    credentials: &Credentials,
 ) -> Result<Vec<PullRequest>, Box<dyn Error>> {
     let client = reqwest::Client::new();
 
-    let request = api::base_request(&client, credentials, "https://api.github.com/search/issues")
-        .query(&[("q", format!("{} in:title", pattern))]);
+    let url = format!("{}/search/issues", api::github_api_base());
+    let request = api::base_request(&client, credentials, &url).query(&[("q", query)]);
 
     let items = request.send().await?.json::<SearchResponse>().await?.items;
 
@@ -56,50 +236,273 @@ pub async fn fetch_pull_requests_matching(
         .into_iter()
         .map(|item| item.unwrap());
 
-    let responses: Vec<_> = join_all(items.map(|item| item.json::<PullRequest>()))
+    let prs = join_all(items.map(|item| item.json::<PullRequest>()))
         .await
         .into_iter()
-        .map(|item| async {
-            let pr = item.unwrap();
-            let pr = pr.fetch_reviews(credentials).await.unwrap();
-            pr
-        })
+        .map(|item| item.unwrap())
         .collect();
 
-    Ok(join_all(responses).await)
+    Ok(with_reviews(prs, credentials).await)
 }
-pub async fn fetch_matching_pull_requests_from_repository(
-    pattern: &str,
-    repository: &str,
-    credentials: &Credentials,
-) -> Result<Vec<PullRequest>, Box<dyn Error>> {
-    let client = reqwest::Client::new();
 
-    let request = api::base_request(&client, credentials, "https://api.github.com/search/issues")
-        .query(&[("q", format!("{} in:title repo:{}", pattern, repository))]);
+/// Attach reviews to PRs fetched without them
+async fn with_reviews(prs: Vec<PullRequest>, credentials: &Credentials) -> Vec<PullRequest> {
+    let responses: Vec<_> = prs
+        .into_iter()
+        .map(|pr| async { pr.fetch_reviews(credentials).await.unwrap() })
+        .collect();
 
-    let items = request.send().await?.json::<SearchResponse>().await?.items;
+    join_all(responses).await
+}
 
-    let item_futures = items.into_iter().map(|item| {
-        api::base_request(&client, credentials, &item.url.replace("issues", "pulls")).send()
-    });
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{Matcher, Server};
+    use serial_test::serial;
 
-    // The `unwrap`s are required here because both `reqwest::send` and `reqwest::json` return a `Result` which has
-    // to be unwrapped after the future has been `await`ed on.
-    let items = join_all(item_futures)
+    fn make_pr_json(base_url: &str, number: usize, head: &str, base: &str, body: &str) -> String {
+        format!(
+            r#"{{
+                "id": {number},
+                "number": {number},
+                "head": {{"label": "user:{head}", "ref": "{head}", "sha": "abc{number}"}},
+                "base": {{"label": "user:{base}", "ref": "{base}", "sha": "def{number}"}},
+                "title": "PR {number}",
+                "url": "{base_url}/repos/owner/repo/pulls/{number}",
+                "body": "{body}",
+                "state": "open",
+                "merged_at": null,
+                "updated_at": null,
+                "draft": false
+            }}"#
+        )
+    }
+
+    /// Mock the PR and review endpoints that follow a search hit
+    async fn mock_pr(server: &mut Server, number: usize, head: &str, base: &str, body: &str) {
+        let url = server.url();
+        server
+            .mock(
+                "GET",
+                format!("/repos/owner/repo/pulls/{}", number).as_str(),
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(make_pr_json(&url, number, head, base, body))
+            .create_async()
+            .await;
+        server
+            .mock(
+                "GET",
+                format!("/repos/owner/repo/pulls/{}/reviews", number).as_str(),
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body("[]")
+            .create_async()
+            .await;
+    }
+
+    fn search_body(base_url: &str, numbers: &[usize]) -> String {
+        let items: Vec<String> = numbers
+            .iter()
+            .map(|n| {
+                format!(
+                    r#"{{"url": "{}/repos/owner/repo/issues/{}", "title": "PR {}"}}"#,
+                    base_url, n, n
+                )
+            })
+            .collect();
+        format!(r#"{{"items": [{}]}}"#, items.join(","))
+    }
+
+    #[test]
+    fn test_discovery_round_trips_through_names() {
+        for name in Discovery::NAMES {
+            let discovery: Discovery = name.parse().unwrap();
+            assert_eq!(discovery.to_string(), *name);
+        }
+        assert!("branch".parse::<Discovery>().is_err());
+        assert_eq!(Discovery::default(), Discovery::Title);
+    }
+
+    #[test]
+    fn test_search_query_per_strategy() {
+        assert_eq!(
+            search_query(Discovery::Title, "STACK-1", Some("owner/repo")).unwrap(),
+            "STACK-1 in:title repo:owner/repo"
+        );
+        assert_eq!(
+            search_query(Discovery::Title, "STACK-1", None).unwrap(),
+            "STACK-1 in:title"
+        );
+        assert_eq!(
+            search_query(Discovery::Marker, "STACK-1", Some("owner/repo")).unwrap(),
+            "\"gh-stack:[STACK-1]\" in:body is:pr repo:owner/repo"
+        );
+        assert_eq!(
+            search_query(Discovery::Label, "STACK-1", Some("owner/repo")).unwrap(),
+            "label:\"STACK-1\" is:pr repo:owner/repo"
+        );
+        assert!(search_query(Discovery::Chain, "feature-1", Some("owner/repo")).is_none());
+    }
+
+    #[test]
+    fn test_stack_marker() {
+        assert_eq!(stack_marker("STACK-1"), "<!-- gh-stack:[STACK-1] -->");
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_marker_discovery_filters_on_full_marker() {
+        let mut server = Server::new_async().await;
+        let url = server.url();
+
+        let search = server
+            .mock("GET", "/search/issues")
+            .match_query(Matcher::UrlEncoded(
+                "q".into(),
+                "\"gh-stack:[STACK-1]\" in:body is:pr repo:owner/repo".into(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(search_body(&url, &[1, 2]))
+            .create_async()
+            .await;
+        mock_pr(
+            &mut server,
+            1,
+            "feature-1",
+            "main",
+            "<!-- gh-stack:[STACK-1] -->",
+        )
+        .await;
+        // Mentions the identifier, but isn't part of the stack
+        mock_pr(
+            &mut server,
+            2,
+            "other",
+            "main",
+            "Follow-up to gh-stack:[STACK-1]",
+        )
+        .await;
+
+        std::env::set_var("GITHUB_API_BASE", &url);
+
+        let creds = Credentials::new("test-token");
+        let prs = discover_pull_requests(
+            Discovery::Marker,
+            "STACK-1",
+            "owner/repo",
+            "main",
+            false,
+            &creds,
+        )
         .await
-        .into_iter()
-        .map(|item| item.unwrap());
+        .unwrap();
+
+        search.assert_async().await;
+        let numbers: Vec<usize> = prs.iter().map(|pr| pr.number()).collect();
+        assert_eq!(numbers, vec![1]);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_label_discovery_searches_by_label() {
+        let mut server = Server::new_async().await;
+        let url = server.url();
 
-    let responses: Vec<_> = join_all(items.map(|item| item.json::<PullRequest>()))
+        let search = server
+            .mock("GET", "/search/issues")
+            .match_query(Matcher::UrlEncoded(
+                "q".into(),
+                "label:\"STACK-1\" is:pr repo:owner/repo".into(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(search_body(&url, &[1, 2]))
+            .create_async()
+            .await;
+        mock_pr(&mut server, 1, "feature-1", "main", "").await;
+        mock_pr(&mut server, 2, "feature-2", "feature-1", "").await;
+
+        std::env::set_var("GITHUB_API_BASE", &url);
+
+        let creds = Credentials::new("test-token");
+        let prs = discover_pull_requests(
+            Discovery::Label,
+            "STACK-1",
+            "owner/repo",
+            "main",
+            false,
+            &creds,
+        )
         .await
-        .into_iter()
-        .map(|item| async {
-            let pr = item.unwrap();
-            let pr = pr.fetch_reviews(credentials).await.unwrap();
-            pr
-        })
-        .collect();
+        .unwrap();
+
+        search.assert_async().await;
+        assert_eq!(prs.len(), 2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_chain_discovery_walks_from_branch() {
+        let mut server = Server::new_async().await;
+        let url = server.url();
+
+        server
+            .mock("GET", "/repos/owner/repo/pulls")
+            .match_query(Matcher::UrlEncoded("head".into(), "owner:feature-2".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(format!(
+                "[{}]",
+                make_pr_json(&url, 2, "feature-2", "feature-1", "")
+            ))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/owner/repo/pulls")
+            .match_query(Matcher::UrlEncoded("per_page".into(), "100".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(format!(
+                "[{},{},{}]",
+                make_pr_json(&url, 1, "feature-1", "main", ""),
+                make_pr_json(&url, 2, "feature-2", "feature-1", ""),
+                make_pr_json(&url, 3, "unrelated", "main", "")
+            ))
+            .create_async()
+            .await;
+        for n in 1..=2 {
+            server
+                .mock(
+                    "GET",
+                    format!("/repos/owner/repo/pulls/{}/reviews", n).as_str(),
+                )
+                .with_status(200)
+                .with_header("content-type", "application/json")
+                .with_body("[]")
+                .create_async()
+                .await;
+        }
+
+        std::env::set_var("GITHUB_API_BASE", &url);
+
+        let creds = Credentials::new("test-token");
+        let prs = discover_pull_requests(
+            Discovery::Chain,
+            "feature-2",
+            "owner/repo",
+            "main",
+            false,
+            &creds,
+        )
+        .await
+        .unwrap();
 
-    Ok(join_all(responses).await)
+        let numbers: Vec<usize> = prs.iter().map(|pr| pr.number()).collect();
+        assert_eq!(numbers, vec![1, 2]);
+    }
 }
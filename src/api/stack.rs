@@ -10,6 +10,8 @@
 //! This reduces API calls from O(N) to O(1) for most repositories.
 
 use crate::api::{github_api_base, PullRequest};
+use crate::graph;
+use crate::identifier::Trunks;
 use crate::Credentials;
 use reqwest::Client;
 use std::collections::{HashMap, HashSet};
@@ -193,15 +195,65 @@ pub async fn fetch_all_open_prs(
     Ok(all_prs)
 }
 
+/// Fetch closed (including merged) PRs matching a `head` or `base` filter.
+///
+/// Returns the most recent PRs first.
+///
+/// # Arguments
+/// * `repo` - Repository in "owner/repo" format
+/// * `filter` - Query parameter to filter on, `"head"` or `"base"`
+/// * `branch` - Branch name to filter by
+/// * `creds` - GitHub credentials
+pub async fn fetch_closed_prs(
+    repo: &str,
+    filter: &str,
+    branch: &str,
+    creds: &Credentials,
+) -> Result<Vec<PullRequest>, Box<dyn Error>> {
+    let client = Client::new();
+
+    let value = if filter == "head" {
+        let owner = repo.split('/').next().unwrap_or(repo);
+        format!("{}:{}", owner, branch)
+    } else {
+        branch.to_string()
+    };
+
+    let url = format!("{}/repos/{}/pulls", github_api_base(), repo);
+
+    let response = build_request(&client, creds, &url)
+        .query(&[("state", "closed"), ("per_page", "100"), (filter, &value)])
+        .send()
+        .await?;
+
+    if response.status() == 429 {
+        return Err("GitHub API rate limit exceeded".into());
+    }
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        return Err(format!("Failed to fetch closed PRs ({}): {}", status, text).into());
+    }
+
+    let prs: Vec<PullRequest> = response.json().await?;
+    Ok(prs)
+}
+
 /// Discover the full stack by walking PR chain from a starting PR.
 ///
 /// Uses batch-fetch strategy: fetches all open PRs in one paginated call,
 /// then walks the chain in-memory. This reduces API calls from O(N) to O(1).
 ///
+/// With `include_closed`, closed and merged PRs are also fetched for every
+/// branch the walk reaches, so a stack whose lower PRs have landed is still
+/// found in full. This costs extra API calls per branch.
+///
 /// # Arguments
 /// * `repo` - Repository in "owner/repo" format
 /// * `starting_pr` - The PR to start discovery from
 /// * `trunk` - The trunk branch name (e.g., "main", "master")
+/// * `include_closed` - Also follow closed and merged PRs
 /// * `creds` - GitHub credentials
 ///
 /// # Returns
@@ -210,16 +262,55 @@ pub async fn discover_stack(
     repo: &str,
     starting_pr: PullRequest,
     trunk: &str,
+    include_closed: bool,
     creds: &Credentials,
 ) -> Result<Vec<PullRequest>, Box<dyn Error>> {
     // Batch fetch all open PRs (1 paginated API call)
-    let all_prs = fetch_all_open_prs(repo, creds).await?;
+    let open_prs = fetch_all_open_prs(repo, creds).await?;
 
-    // Build in-memory index
-    let index = PrIndex::from_prs(all_prs);
+    if !include_closed {
+        // Walk chain in memory (no more API calls)
+        let index = PrIndex::from_prs(open_prs);
+        return Ok(discover_stack_from_index(&index, starting_pr, trunk));
+    }
+
+    let mut closed_prs: Vec<PullRequest> = Vec::new();
+    let mut fetched: HashSet<(&str, String)> = HashSet::new();
+
+    loop {
+        // Closed PRs go first so an open PR for the same head wins the index
+        let mut prs = closed_prs.clone();
+        prs.extend(open_prs.iter().cloned());
+        let index = PrIndex::from_prs(prs);
+        let stack = discover_stack_from_index(&index, starting_pr.clone(), trunk);
+
+        let mut found = Vec::new();
+        for pr in &stack {
+            // A base without a PR in the stack may belong to a landed PR
+            let base = pr.base();
+            if base != trunk
+                && !stack.iter().any(|p| p.head() == base)
+                && fetched.insert(("head", base.to_string()))
+            {
+                let latest = fetch_closed_prs(repo, "head", base, creds).await?;
+                found.extend(latest.into_iter().next());
+            }
 
-    // Walk chain in memory (no more API calls)
-    Ok(discover_stack_from_index(&index, starting_pr, trunk))
+            // Closed PRs stacked on this one
+            if fetched.insert(("base", pr.head().to_string())) {
+                found.extend(fetch_closed_prs(repo, "base", pr.head(), creds).await?);
+            }
+        }
+
+        found.retain(|pr| {
+            !closed_prs.iter().any(|c| c.number() == pr.number())
+                && !open_prs.iter().any(|o| o.number() == pr.number())
+        });
+        if found.is_empty() {
+            return Ok(stack);
+        }
+        closed_prs.extend(found);
+    }
 }
 
 /// Walk stack using pre-fetched PR index (pure in-memory operation).
@@ -274,67 +365,7 @@ fn discover_stack_from_index(
     }
 
     // Sort PRs by their position in the stack (bottom to top)
-    sort_stack(visited.into_values().collect(), trunk)
-}
-
-/// Sort PRs by their position in the stack (bottom to top).
-/// Bottom = PR whose base is trunk, Top = PR with no children.
-fn sort_stack(prs: Vec<PullRequest>, trunk: &str) -> Vec<PullRequest> {
-    if prs.is_empty() {
-        return prs;
-    }
-
-    // Build a map from base -> PR for sorting
-    let head_to_pr: HashMap<&str, &PullRequest> = prs.iter().map(|pr| (pr.head(), pr)).collect();
-
-    let mut sorted = Vec::with_capacity(prs.len());
-    let mut remaining: HashSet<&str> = prs.iter().map(|pr| pr.head()).collect();
-
-    // Find the root(s) - PRs whose base is trunk or not in our set
-    let mut current_base = trunk;
-
-    while !remaining.is_empty() {
-        // Find a PR whose base matches current_base
-        let next_pr = prs
-            .iter()
-            .find(|pr| remaining.contains(pr.head()) && pr.base() == current_base);
-
-        match next_pr {
-            Some(pr) => {
-                remaining.remove(pr.head());
-                current_base = pr.head();
-                sorted.push(pr.clone());
-            }
-            None => {
-                // No more PRs with expected base, try to find any remaining PR
-                // whose base is already in sorted list or is trunk
-                let sorted_heads: HashSet<&str> = sorted.iter().map(|pr| pr.head()).collect();
-                let fallback = prs.iter().find(|pr| {
-                    remaining.contains(pr.head())
-                        && (pr.base() == trunk || sorted_heads.contains(pr.base()))
-                });
-
-                match fallback {
-                    Some(pr) => {
-                        remaining.remove(pr.head());
-                        current_base = pr.head();
-                        sorted.push(pr.clone());
-                    }
-                    None => {
-                        // Add any remaining PRs (shouldn't happen in well-formed stacks)
-                        for head in remaining.iter() {
-                            if let Some(pr) = head_to_pr.get(head) {
-                                sorted.push((*pr).clone());
-                            }
-                        }
-                        break;
-                    }
-                }
-            }
-        }
-    }
-
-    sorted
+    graph::sort_prs(visited.into_values().collect())
 }
 
 /// Discover all stacks in a repository.
@@ -342,27 +373,55 @@ fn sort_stack(prs: Vec<PullRequest>, trunk: &str) -> Vec<PullRequest> {
 /// Groups PRs by their root (PR whose base is trunk) and returns
 /// each group as a separate stack. Uses batch-fetch for efficiency.
 ///
+/// With `include_closed`, a PR whose base has no open PR is traced back to
+/// trunk through closed and merged PRs.
+///
 /// # Arguments
 /// * `repo` - Repository in "owner/repo" format
-/// * `trunk` - The trunk branch name (e.g., "main", "master")
+/// * `trunks` - Branches stacks may be rooted on
+/// * `include_closed` - Also follow closed and merged PRs
 /// * `creds` - GitHub credentials
 ///
 /// # Returns
 /// Vector of stacks, where each stack is a vector of PRs sorted bottom to top
 pub async fn discover_all_stacks(
     repo: &str,
-    trunk: &str,
+    trunks: &Trunks,
+    include_closed: bool,
     creds: &Credentials,
 ) -> Result<Vec<Vec<PullRequest>>, Box<dyn Error>> {
-    let all_prs = fetch_all_open_prs(repo, creds).await?;
-    Ok(group_into_stacks(all_prs, trunk))
+    let mut all_prs = fetch_all_open_prs(repo, creds).await?;
+
+    if include_closed {
+        // Follow bases without an open PR down to trunk through landed PRs,
+        // so stacks whose bottom PRs merged still have a root
+        let mut fetched: HashSet<String> = HashSet::new();
+        loop {
+            let missing: Vec<String> = all_prs
+                .iter()
+                .map(|pr| pr.base().to_string())
+                .filter(|base| !trunks.matches(base) && !all_prs.iter().any(|pr| pr.head() == base))
+                .filter(|base| fetched.insert(base.clone()))
+                .collect();
+            if missing.is_empty() {
+                break;
+            }
+
+            for base in missing {
+                let latest = fetch_closed_prs(repo, "head", &base, creds).await?;
+                all_prs.extend(latest.into_iter().next());
+            }
+        }
+    }
+
+    Ok(group_into_stacks(all_prs, trunks))
 }
 
 /// Group PRs into stacks (pure in-memory operation).
 ///
-/// PRs are grouped by walking from each root (PR whose base is trunk)
+/// PRs are grouped by walking from each root (PR whose base is a trunk)
 /// down through child PRs.
-fn group_into_stacks(prs: Vec<PullRequest>, trunk: &str) -> Vec<Vec<PullRequest>> {
+fn group_into_stacks(prs: Vec<PullRequest>, trunks: &Trunks) -> Vec<Vec<PullRequest>> {
     if prs.is_empty() {
         return vec![];
     }
@@ -376,8 +435,13 @@ fn group_into_stacks(prs: Vec<PullRequest>, trunk: &str) -> Vec<Vec<PullRequest>
             .push(pr);
     }
 
-    // Find root PRs (those whose base is trunk)
-    let roots: Vec<&PullRequest> = prs.iter().filter(|pr| pr.base() == trunk).collect();
+    // Find root PRs (those whose base is a trunk), lowest number first so
+    // the result doesn't depend on fetch order
+    let is_root = |pr: &PullRequest| {
+        trunks.matches(pr.base()) || (trunks.any_base && !prs.iter().any(|p| p.head() == pr.base()))
+    };
+    let mut roots: Vec<&PullRequest> = prs.iter().filter(|pr| is_root(pr)).collect();
+    roots.sort_by_key(|pr| pr.number());
 
     // For each root, build its stack by walking down
     let mut stacks = Vec::new();
@@ -406,7 +470,7 @@ fn group_into_stacks(prs: Vec<PullRequest>, trunk: &str) -> Vec<Vec<PullRequest>
         }
 
         // Sort the stack
-        stack = sort_stack(stack, trunk);
+        stack = graph::sort_prs(stack);
         stacks.push(stack);
     }
 
@@ -555,7 +619,7 @@ mod tests {
         let pr1 = make_test_pr(1, "feature-1", "main");
         let pr2 = make_test_pr(2, "feature-2", "feature-1");
 
-        let stacks = group_into_stacks(vec![pr1, pr2], "main");
+        let stacks = group_into_stacks(vec![pr1, pr2], &Trunks::new("main"));
 
         assert_eq!(stacks.len(), 1);
         assert_eq!(stacks[0].len(), 2);
@@ -567,7 +631,7 @@ mod tests {
         let pr2 = make_test_pr(2, "feature-2", "feature-1");
         let pr3 = make_test_pr(3, "other-1", "main");
 
-        let stacks = group_into_stacks(vec![pr1, pr2, pr3], "main");
+        let stacks = group_into_stacks(vec![pr1, pr2, pr3], &Trunks::new("main"));
 
         assert_eq!(stacks.len(), 2);
         // Larger stack first
@@ -576,42 +640,43 @@ mod tests {
     }
 
     #[test]
-    fn test_group_into_stacks_empty() {
-        let stacks = group_into_stacks(vec![], "main");
-        assert!(stacks.is_empty());
+    fn test_group_into_stacks_trunk_patterns() {
+        let pr1 = make_test_pr(1, "feature-1", "main");
+        let pr2 = make_test_pr(2, "fix-1", "release/1.2");
+        let pr3 = make_test_pr(3, "fix-2", "fix-1");
+        let pr4 = make_test_pr(4, "experiment", "spike");
+
+        let trunks = Trunks {
+            patterns: vec!["main".to_string(), "release/*".to_string()],
+            any_base: false,
+        };
+        let stacks = group_into_stacks(vec![pr1, pr2, pr3, pr4], &trunks);
+
+        let roots: Vec<(&str, usize)> = stacks.iter().map(|s| (s[0].base(), s.len())).collect();
+        assert_eq!(roots, vec![("release/1.2", 2), ("main", 1)]);
     }
 
-    // === sort_stack tests ===
-
     #[test]
-    fn test_sort_stack_linear() {
+    fn test_group_into_stacks_any_base() {
         let pr1 = make_test_pr(1, "feature-1", "main");
         let pr2 = make_test_pr(2, "feature-2", "feature-1");
-        let pr3 = make_test_pr(3, "feature-3", "feature-2");
+        let pr3 = make_test_pr(3, "experiment", "spike");
 
-        // Give them in wrong order
-        let prs = vec![pr3, pr1, pr2];
-        let sorted = sort_stack(prs, "main");
+        let trunks = Trunks {
+            patterns: vec!["main".to_string()],
+            any_base: true,
+        };
+        let stacks = group_into_stacks(vec![pr1, pr2, pr3], &trunks);
 
-        assert_eq!(sorted.len(), 3);
-        assert_eq!(sorted[0].number(), 1); // base: main
-        assert_eq!(sorted[1].number(), 2); // base: feature-1
-        assert_eq!(sorted[2].number(), 3); // base: feature-2
+        let roots: Vec<(&str, usize)> = stacks.iter().map(|s| (s[0].base(), s.len())).collect();
+        // feature-1 has a PR, so #2 isn't a root of its own
+        assert_eq!(roots, vec![("main", 2), ("spike", 1)]);
     }
 
     #[test]
-    fn test_sort_stack_single() {
-        let pr = make_test_pr(1, "feature", "main");
-
-        let sorted = sort_stack(vec![pr], "main");
-        assert_eq!(sorted.len(), 1);
-        assert_eq!(sorted[0].number(), 1);
-    }
-
-    #[test]
-    fn test_sort_stack_empty() {
-        let sorted = sort_stack(vec![], "main");
-        assert!(sorted.is_empty());
+    fn test_group_into_stacks_empty() {
+        let stacks = group_into_stacks(vec![], &Trunks::new("main"));
+        assert!(stacks.is_empty());
     }
 
     // === API tests with mocks ===
@@ -732,6 +797,34 @@ mod tests {
         mock.assert_async().await;
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn test_fetch_closed_prs_encodes_branch() {
+        let mut server = Server::new_async().await;
+
+        let mock = server
+            .mock("GET", "/repos/owner/repo/pulls")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("state".into(), "closed".into()),
+                mockito::Matcher::UrlEncoded("base".into(), "fix/#1&x".into()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body("[]")
+            .create_async()
+            .await;
+
+        std::env::set_var("GITHUB_API_BASE", server.url());
+
+        let creds = Credentials::new("test-token");
+        let prs = fetch_closed_prs("owner/repo", "base", "fix/#1&x", &creds)
+            .await
+            .unwrap();
+        assert!(prs.is_empty());
+
+        mock.assert_async().await;
+    }
+
     #[tokio::test]
     #[serial]
     async fn test_fetch_prs_by_base_empty() {
@@ -885,7 +978,7 @@ mod tests {
             vec![],
         );
 
-        let result = discover_stack("owner/repo", starting_pr, "main", &creds).await;
+        let result = discover_stack("owner/repo", starting_pr, "main", false, &creds).await;
 
         assert!(result.is_ok());
         let stack = result.unwrap();
@@ -896,4 +989,116 @@ mod tests {
 
         mock.assert_async().await;
     }
+
+    fn make_merged_pr_json(number: usize, head: &str, base: &str, title: &str) -> String {
+        make_pr_json(number, head, base, title)
+            .replace(r#""state": "open""#, r#""state": "closed""#)
+            .replace(
+                r#""merged_at": null"#,
+                r#""merged_at": "2024-01-01T00:00:00Z""#,
+            )
+    }
+
+    /// Mock the closed-PR lookup for one `head`/`base` filter value
+    async fn mock_closed(server: &mut Server, filter: &str, value: &str, body: String) {
+        server
+            .mock("GET", "/repos/owner/repo/pulls")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("state".into(), "closed".into()),
+                mockito::Matcher::UrlEncoded(filter.into(), value.into()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(body)
+            .create_async()
+            .await;
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_discover_stack_follows_merged_base() {
+        let mut server = Server::new_async().await;
+
+        // #1 has landed; #2 and #3 are still open on top of it
+        let pr2 = make_pr_json(2, "feature-2", "feature-1", "PR 2");
+        let pr3 = make_pr_json(3, "feature-3", "feature-2", "PR 3");
+        server
+            .mock("GET", "/repos/owner/repo/pulls")
+            .match_query(mockito::Matcher::UrlEncoded("state".into(), "open".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(format!("[{}, {}]", pr2, pr3))
+            .create_async()
+            .await;
+        mock_closed(
+            &mut server,
+            "head",
+            "owner:feature-1",
+            format!("[{}]", make_merged_pr_json(1, "feature-1", "main", "PR 1")),
+        )
+        .await;
+        for base in ["feature-1", "feature-2", "feature-3"] {
+            mock_closed(&mut server, "base", base, "[]".to_string()).await;
+        }
+
+        std::env::set_var("GITHUB_API_BASE", server.url());
+
+        let creds = Credentials::new("test-token");
+        let starting_pr = make_test_pr(3, "feature-3", "feature-2");
+
+        let without = discover_stack("owner/repo", starting_pr.clone(), "main", false, &creds)
+            .await
+            .unwrap();
+        let numbers: Vec<usize> = without.iter().map(|pr| pr.number()).collect();
+        assert_eq!(numbers, vec![2, 3]);
+
+        let with = discover_stack("owner/repo", starting_pr, "main", true, &creds)
+            .await
+            .unwrap();
+        let numbers: Vec<usize> = with.iter().map(|pr| pr.number()).collect();
+        assert_eq!(numbers, vec![1, 2, 3]);
+        assert!(with[0].is_merged());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_discover_all_stacks_roots_half_landed_stack() {
+        let mut server = Server::new_async().await;
+
+        let pr2 = make_pr_json(2, "feature-2", "feature-1", "PR 2");
+        let pr5 = make_pr_json(5, "other", "main", "PR 5");
+        server
+            .mock("GET", "/repos/owner/repo/pulls")
+            .match_query(mockito::Matcher::UrlEncoded("state".into(), "open".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(format!("[{}, {}]", pr2, pr5))
+            .create_async()
+            .await;
+        mock_closed(
+            &mut server,
+            "head",
+            "owner:feature-1",
+            format!("[{}]", make_merged_pr_json(1, "feature-1", "main", "PR 1")),
+        )
+        .await;
+
+        std::env::set_var("GITHUB_API_BASE", server.url());
+
+        let creds = Credentials::new("test-token");
+
+        let stacks = discover_all_stacks("owner/repo", &Trunks::new("main"), false, &creds)
+            .await
+            .unwrap();
+        assert_eq!(stacks.len(), 1);
+
+        let stacks = discover_all_stacks("owner/repo", &Trunks::new("main"), true, &creds)
+            .await
+            .unwrap();
+        let numbers: Vec<Vec<usize>> = stacks
+            .iter()
+            .map(|s| s.iter().map(|pr| pr.number()).collect())
+            .collect();
+        assert_eq!(numbers, vec![vec![1, 2], vec![5]]);
+    }
 }
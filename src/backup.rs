@@ -0,0 +1,313 @@
+//! Backups of the branches a command is about to move or delete
+//!
+//! Before autorebase, push or land rewrite branches, the SHAs those branches
+//! pointed at, locally and on the remote, are kept under
+//! `refs/gh-stack/backup/<op-id>/` so the commits can't be garbage-collected,
+//! and recorded in an operation log at `.git/gh-stack/oplog.json`.
+//! `gh-stack undo` puts the branches back.
+
+use chrono::{DateTime, Utc};
+use git2::{BranchType, Repository};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::PathBuf;
+
+use crate::git::{self, BranchPush};
+use crate::state;
+
+/// Namespace the backup refs live under
+const BACKUP_REFS: &str = "refs/gh-stack/backup";
+
+/// Where a branch pointed before an operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BranchBackup {
+    pub name: String,
+    /// The local branch's SHA, if it existed
+    pub local: Option<String>,
+    /// The remote-tracking branch's SHA, if it existed
+    pub remote: Option<String>,
+}
+
+/// A command that moved or deleted branches
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Operation {
+    /// Identifier for `gh-stack undo`, also naming the backup refs
+    pub id: String,
+    /// The command that ran, e.g. `autorebase`
+    pub command: String,
+    /// Remote the branches were pushed to or deleted from
+    pub remote: String,
+    pub created_at: DateTime<Utc>,
+    pub branches: Vec<BranchBackup>,
+}
+
+impl Operation {
+    /// Pushes putting the remote branches back where they were
+    ///
+    /// Each push is leased on where the remote-tracking branch points now, or
+    /// on the branch not existing if it has been deleted.
+    pub fn restore_pushes(&self, repo: &Repository) -> Vec<BranchPush> {
+        self.branches
+            .iter()
+            .filter_map(|branch| {
+                let sha = branch.remote.clone()?;
+                let current = remote_sha(repo, &self.remote, &branch.name);
+                (current.as_ref() != Some(&sha)).then(|| BranchPush {
+                    branch: branch.name.clone(),
+                    sha,
+                    expected: current.unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+}
+
+fn oplog_path(repo: &Repository) -> PathBuf {
+    state::state_dir(repo).join("oplog.json")
+}
+
+fn local_sha(repo: &Repository, branch: &str) -> Option<String> {
+    let target = repo
+        .find_branch(branch, BranchType::Local)
+        .ok()?
+        .get()
+        .target()?;
+    Some(target.to_string())
+}
+
+fn remote_sha(repo: &Repository, remote: &str, branch: &str) -> Option<String> {
+    let reference = repo
+        .find_reference(&format!("refs/remotes/{}/{}", remote, branch))
+        .ok()?;
+    Some(reference.target()?.to_string())
+}
+
+/// Every recorded operation, oldest first
+pub fn operations(repo: &Repository) -> Result<Vec<Operation>, Box<dyn Error>> {
+    Ok(state::read_json(&oplog_path(repo))?.unwrap_or_default())
+}
+
+/// Back up `branches` before `command` moves or deletes them
+///
+/// Branches that exist neither locally nor on the remote are left out.
+pub fn record(
+    repo: &Repository,
+    command: &str,
+    remote: &str,
+    branches: &[String],
+) -> Result<Operation, Box<dyn Error>> {
+    let mut log = operations(repo)?;
+
+    let created_at = Utc::now();
+    let stamp = created_at.format("%Y%m%dT%H%M%S").to_string();
+    let mut id = stamp.clone();
+    let mut n = 1;
+    while log.iter().any(|op| op.id == id) {
+        n += 1;
+        id = format!("{}-{}", stamp, n);
+    }
+
+    let mut backups = vec![];
+    for name in branches {
+        let backup = BranchBackup {
+            name: name.clone(),
+            local: local_sha(repo, name),
+            remote: remote_sha(repo, remote, name),
+        };
+        for (kind, sha) in [("local", &backup.local), ("remote", &backup.remote)] {
+            if let Some(sha) = sha {
+                repo.reference(
+                    &format!("{}/{}/{}/{}", BACKUP_REFS, id, kind, name),
+                    git2::Oid::from_str(sha)?,
+                    true,
+                    &format!("gh-stack backup before {}", command),
+                )?;
+            }
+        }
+        if backup.local.is_some() || backup.remote.is_some() {
+            backups.push(backup);
+        }
+    }
+
+    let op = Operation {
+        id,
+        command: command.to_string(),
+        remote: remote.to_string(),
+        created_at,
+        branches: backups,
+    };
+    log.push(op.clone());
+    state::write_json(&oplog_path(repo), &log)?;
+
+    Ok(op)
+}
+
+/// The operation with this id, or the latest one
+pub fn find(repo: &Repository, id: Option<&str>) -> Result<Operation, Box<dyn Error>> {
+    let log = operations(repo)?;
+    let op = match id {
+        Some(id) => log.into_iter().find(|op| op.id == id),
+        None => log.into_iter().last(),
+    };
+    op.ok_or_else(|| match id {
+        Some(id) => format!("No gh-stack operation {}", id).into(),
+        None => "No gh-stack operations to undo".into(),
+    })
+}
+
+/// Put the local branches of `op` back where they were
+///
+/// Returns the branches that moved. Branches that didn't exist locally are
+/// left alone.
+pub fn restore_local(repo: &Repository, op: &Operation) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut restored = vec![];
+    for branch in &op.branches {
+        let Some(sha) = &branch.local else {
+            continue;
+        };
+        if local_sha(repo, &branch.name).as_ref() == Some(sha) {
+            continue;
+        }
+        let commit = repo.find_commit(git2::Oid::from_str(sha)?)?;
+        git::move_branch(repo, &branch.name, &commit)?;
+        restored.push(branch.name.clone());
+    }
+    Ok(restored)
+}
+
+/// Plain-text listing of the operation log, newest first
+pub fn render_operations(log: &[Operation]) -> String {
+    let mut out = String::new();
+    for op in log.iter().rev() {
+        let names: Vec<&str> = op.branches.iter().map(|b| b.name.as_str()).collect();
+        out.push_str(&format!(
+            "{}  {:<11} {}\n",
+            op.id,
+            op.command,
+            names.join(", ")
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{commit, init_repo};
+
+    #[test]
+    fn test_record_then_restore_local() {
+        let (_dir, repo) = init_repo();
+        let old = commit(&repo, &[], &[], "old");
+        let new = commit(&repo, &[old], &[], "new");
+        repo.branch("feature", &repo.find_commit(old).unwrap(), true)
+            .unwrap();
+        repo.reference("refs/remotes/origin/feature", old, true, "test")
+            .unwrap();
+
+        let op = record(
+            &repo,
+            "autorebase",
+            "origin",
+            &["feature".to_string(), "missing".to_string()],
+        )
+        .unwrap();
+        assert_eq!(op.branches.len(), 1);
+        assert_eq!(
+            repo.refname_to_id(&format!("refs/gh-stack/backup/{}/local/feature", op.id))
+                .unwrap(),
+            old
+        );
+        assert_eq!(
+            repo.refname_to_id(&format!("refs/gh-stack/backup/{}/remote/feature", op.id))
+                .unwrap(),
+            old
+        );
+
+        // The command moves the branch on both sides...
+        repo.branch("feature", &repo.find_commit(new).unwrap(), true)
+            .unwrap();
+        repo.reference("refs/remotes/origin/feature", new, true, "test")
+            .unwrap();
+
+        // ...and undo puts it back
+        let op = find(&repo, None).unwrap();
+        assert_eq!(restore_local(&repo, &op).unwrap(), vec!["feature"]);
+        assert_eq!(local_sha(&repo, "feature"), Some(old.to_string()));
+        assert_eq!(
+            op.restore_pushes(&repo),
+            vec![BranchPush {
+                branch: "feature".to_string(),
+                sha: old.to_string(),
+                expected: new.to_string(),
+            }]
+        );
+
+        // Nothing left to restore
+        assert!(restore_local(&repo, &op).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_operation_ids_are_unique() {
+        let (_dir, repo) = init_repo();
+        let first = record(&repo, "push", "origin", &[]).unwrap();
+        let second = record(&repo, "push", "origin", &[]).unwrap();
+        assert_ne!(first.id, second.id);
+
+        assert_eq!(find(&repo, None).unwrap().id, second.id);
+        assert_eq!(find(&repo, Some(&first.id)).unwrap().id, first.id);
+        assert!(find(&repo, Some("nope")).is_err());
+        assert_eq!(operations(&repo).unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn test_restore_pushes_after_land_cleanup() {
+        use crate::land::{execute_cleanup, CleanupPlan, OutputFormat};
+
+        let (_dir, repo) = init_repo();
+        let old = commit(&repo, &[], &[], "old");
+        repo.reference("refs/remotes/origin/landed", old, true, "test")
+            .unwrap();
+        let op = record(&repo, "land", "origin", &["landed".to_string()]).unwrap();
+
+        let mut server = mockito::Server::new_async().await;
+        let protected_mock = server
+            .mock("GET", "/repos/owner/repo/branches/landed")
+            .with_status(200)
+            .with_body(r#"{"protected": false}"#)
+            .create_async()
+            .await;
+        let delete_mock = server
+            .mock("DELETE", "/repos/owner/repo/git/refs/heads/landed")
+            .with_status(204)
+            .create_async()
+            .await;
+        std::env::set_var("GITHUB_API_BASE", server.url());
+
+        let cleanup = CleanupPlan {
+            retarget: vec![],
+            branches_to_delete: vec!["landed".to_string()],
+            target_branch: "main".to_string(),
+            repository: "owner/repo".to_string(),
+        };
+        let creds = crate::Credentials::new("test-token");
+        let result = execute_cleanup(&cleanup, Some(&repo), "origin", OutputFormat::Text, &creds)
+            .await
+            .unwrap();
+        assert_eq!(result.deleted_remote, vec!["landed"]);
+        protected_mock.assert_async().await;
+        delete_mock.assert_async().await;
+
+        // The branch is gone from the remote, so restoring it recreates it
+        let pushes = op.restore_pushes(&repo);
+        assert_eq!(
+            pushes,
+            vec![BranchPush {
+                branch: "landed".to_string(),
+                sha: old.to_string(),
+                expected: String::new(),
+            }]
+        );
+    }
+}
@@ -1,215 +1,1073 @@
-use crate::api::PullRequestStatus;
+use crate::api::{PullRequest, PullRequestStatus};
+use crate::backup;
 use crate::graph::FlatDep;
+use crate::sign::Signer;
+use crate::state;
 use crate::util::loop_until_confirm;
 use git2::build::CheckoutBuilder;
-use git2::{CherrypickOptions, Commit, Index, Oid, Repository, Revwalk, Sort};
+use git2::{
+    BranchType, CherrypickOptions, Commit, FileFavor, Index, MergeOptions, ObjectType, Oid,
+    Repository, Signature, Sort, StatusOptions,
+};
+use serde::{Deserialize, Serialize};
 
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
 use tokio::process::Command;
 
 fn remote_ref(remote: &str, git_ref: &str) -> String {
     format!("{}/{}", remote, git_ref)
 }
 
-/// For all open pull requests in the graph, generate a series of commands
-/// (force-pushes) that will rebase the entire stack. The "PREBASE" variable
-/// is a base for the first branch in the stack (essentially a "stop cherry-picking
-/// here" marker), and is required because of our squash-merge workflow.
-/// TODO: Move this directly into Rust.
-pub fn generate_rebase_script(deps: FlatDep) -> String {
-    let deps = deps
-        .iter()
-        .filter(|(dep, _)| *dep.state() == PullRequestStatus::Open)
-        .collect::<Vec<_>>();
+/// Where a PR at the bottom of the stack (or of a fork off a merged PR) is
+/// rebuilt, when its parent isn't rebuilt along with it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptRoot {
+    /// Branch to rebuild the PR on
+    pub onto: String,
+    /// Where the PR's own commits start, if it could be worked out here
+    pub fork_point: Option<String>,
+}
+
+/// The open PR of the stack whose branch `pr` is based on
+fn open_parent<'a>(deps: &'a FlatDep, pr: &PullRequest) -> Option<&'a PullRequest> {
+    deps.iter()
+        .map(|(d, _)| d.as_ref())
+        .find(|d| d.head() == pr.base() && *d.state() == PullRequestStatus::Open)
+}
+
+/// Work out where each open PR without an open parent is rebuilt
+///
+/// A base that belongs to a merged PR is followed to wherever that PR was
+/// merged into. With a local repository, a base branch that no longer
+/// exists on `remote` falls back to `trunk`, and fork points are computed
+/// as autorebase computes them. Without one, a merged parent's head SHA is
+/// used as the fork point, if there is one.
+pub fn script_roots(
+    repo: Option<&Repository>,
+    remote: &str,
+    trunk: &str,
+    deps: &FlatDep,
+) -> HashMap<usize, ScriptRoot> {
+    let mut roots = HashMap::new();
+    for (pr, _) in deps.iter() {
+        if *pr.state() != PullRequestStatus::Open || open_parent(deps, pr).is_some() {
+            continue;
+        }
+
+        let mut onto = landed_base(deps, pr).to_string();
+        let fork_point = match repo {
+            Some(repo) => {
+                if repo.revparse_single(&remote_ref(remote, &onto)).is_err() {
+                    onto = trunk.to_string();
+                }
+                repo.revparse_single(&remote_ref(remote, &onto))
+                    .ok()
+                    .and_then(|base| fork_point(repo, remote, deps, pr, base.id()).ok())
+                    .map(|oid| oid.to_string())
+            }
+            None => deps
+                .iter()
+                .find(|(d, _)| d.head() == pr.base() && d.is_merged())
+                .map(|(d, _)| d.head_sha().to_string()),
+        };
+
+        roots.insert(pr.number(), ScriptRoot { onto, fork_point });
+    }
+    roots
+}
 
+/// A bash script that rebuilds every open PR of the stack and force-pushes
+/// it, for when you'd rather review and run the steps yourself
+///
+/// Each branch's own commits are rebased onto the rebuilt branch below it,
+/// starting from the SHA GitHub reports for the branch. Pushes are leased on
+/// that SHA, so a branch someone else pushed to since is rejected rather
+/// than overwritten. `roots` (see [`script_roots`]) says where PRs without
+/// an open parent go.
+pub fn generate_rebase_script(
+    deps: &FlatDep,
+    remote: &str,
+    roots: &HashMap<usize, ScriptRoot>,
+) -> String {
     let mut out = String::new();
 
     out.push_str("#!/usr/bin/env bash\n\n");
     out.push_str("set -euo pipefail\n");
     out.push_str("set -o xtrace\n\n");
 
-    out.push_str("# ------ THIS SCRIPT ASSUMES YOUR PR STACK IS A SINGLE CHAIN WITHOUT BRANCHING ----- #\n\n");
-    out.push_str("# It starts at the base of the stack, cherry-picking onto the new base and force-pushing as it goes.\n");
-    out.push_str("# We can't tell where the initial cherry-pick should stop (mainly because of our squash merge workflow),\n");
     out.push_str(
-        "# so that initial stopping point for the first PR needs to be specified manually.\n\n",
+        "# Rebuilds the stack one PR at a time, bottom first: each branch's own commits\n",
+    );
+    out.push_str(
+        "# are rebased onto the rebuilt branch below it, then force-pushed. Each push is\n",
     );
+    out.push_str("# leased on the SHA GitHub reported for the branch, so a branch someone else\n");
+    out.push_str("# has pushed to since is rejected rather than overwritten.\n\n");
 
-    out.push_str("export PREBASE=\"<enter a marker to stop the initial cherry-pick at>\"\n");
+    out.push_str(&format!("git fetch {}\n", remote));
 
-    for (from, to) in deps {
-        let to = if let Some(pr) = to {
-            pr.head().to_string()
-        } else {
-            String::from("<enter a ref to rebase the stack on; usually `develop`>")
-        };
+    for (pr, _) in deps.iter() {
+        if *pr.state() != PullRequestStatus::Open {
+            continue;
+        }
 
-        out.push_str("\n# -------------- #\n\n");
+        // A parent in the stack is pushed before its children, which updates
+        // its remote-tracking branch
+        let (onto, prebase) = match (open_parent(deps, pr), roots.get(&pr.number())) {
+            (Some(parent), _) => (
+                parent.head().to_string(),
+                Some(parent.head_sha().to_string()),
+            ),
+            (None, Some(root)) => (root.onto.clone(), root.fork_point.clone()),
+            (None, None) => (pr.base().to_string(), None),
+        };
+        let prebase = prebase.unwrap_or_else(|| "$(git merge-base \"$TO\" \"$FROM\")".to_string());
 
-        out.push_str(&format!("export TO=\"{}\"\n", remote_ref("heap", &to)));
+        out.push_str("\n# -------------- #\n");
         out.push_str(&format!(
-            "export FROM=\"{}\"\n\n",
-            remote_ref("heap", from.head())
+            "# #{}: {} ({})\n\n",
+            pr.number(),
+            pr.title(),
+            pr.head()
         ));
 
-        out.push_str("git checkout \"$TO\"\n");
-        out.push_str("git cherry-pick \"$PREBASE\"..\"$FROM\"\n");
-        out.push_str("export PREBASE=\"$(git rev-parse --verify $FROM)\"\n");
-        out.push_str("git push -f heap HEAD:refs/heads/\"$FROM\"\n");
+        out.push_str(&format!("export TO=\"{}\"\n", remote_ref(remote, &onto)));
+        out.push_str(&format!("export FROM=\"{}\"\n", pr.head_sha()));
+        out.push_str(&format!("export PREBASE=\"{}\"\n\n", prebase));
+
+        out.push_str("git rebase --onto \"$TO\" \"$PREBASE\" \"$FROM\"\n");
+        out.push_str(&format!(
+            "git push --force-with-lease={}:\"$FROM\" {} HEAD:refs/heads/{}\n",
+            pr.head(),
+            remote,
+            pr.head()
+        ));
     }
 
     out
 }
 
-fn oid_to_commit(repo: &Repository, oid: Oid) -> Commit<'_> {
-    repo.find_commit(oid).unwrap()
+fn checkout_commit(repo: &Repository, commit: &Commit, options: Option<&mut CheckoutBuilder>) {
+    repo.checkout_tree(commit.as_object(), options).unwrap();
+    repo.set_head_detached(commit.id()).unwrap();
 }
 
-fn head_commit(repo: &Repository) -> Commit<'_> {
-    repo.find_commit(repo.head().unwrap().target().unwrap())
-        .unwrap()
+fn rev_to_commit<'a>(repo: &'a Repository, rev: &str) -> Result<Commit<'a>, Box<dyn Error>> {
+    repo.revparse_single(rev)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|_| format!("{} isn't a commit", rev).into())
 }
 
-fn checkout_commit(repo: &Repository, commit: &Commit, options: Option<&mut CheckoutBuilder>) {
-    repo.checkout_tree(commit.as_object(), options).unwrap();
-    repo.set_head_detached(commit.id()).unwrap();
+/// The commit a PR's head branch points at locally
+fn local_head<'a>(repo: &'a Repository, branch: &str) -> Result<Commit<'a>, Box<dyn Error>> {
+    repo.find_branch(branch, BranchType::Local)
+        .and_then(|b| b.get().peel_to_commit())
+        .map_err(|_| format!("{} isn't a local branch", branch).into())
 }
 
-fn rev_to_commit<'a>(repo: &'a Repository, rev: &str) -> Commit<'a> {
-    let commit = repo.revparse_single(rev).unwrap();
-    let commit = commit.into_commit().unwrap();
-    commit
+/// The patch ID of a (non-merge) commit's changes, as `git patch-id` computes it
+fn patch_id(repo: &Repository, commit: &Commit) -> Result<Oid, Box<dyn Error>> {
+    let parent = commit.parent(0)?.tree()?;
+    let diff = repo.diff_tree_to_tree(Some(&parent), Some(&commit.tree()?), None)?;
+    Ok(diff.patchid(None)?)
 }
 
-/// Commit and checkout `index`
-fn create_commit<'a>(repo: &'a Repository, index: &mut Index, message: &str) -> Commit<'a> {
-    let tree = index.write_tree_to(repo).unwrap();
-    let tree = repo.find_tree(tree).unwrap();
+/// Patch IDs of the commits in `upstream` that aren't in `branch`
+///
+/// A commit on the branch with one of these IDs has already been applied
+/// upstream (by a cherry-pick, say) and doesn't need replaying.
+fn upstream_patch_ids(
+    repo: &Repository,
+    upstream: Oid,
+    branch: Oid,
+) -> Result<HashSet<Oid>, Box<dyn Error>> {
+    let mut walk = repo.revwalk()?;
+    walk.push(upstream)?;
+    if let Ok(base) = repo.merge_base(upstream, branch) {
+        walk.hide(base)?;
+    }
 
-    let signature = repo.signature().unwrap();
-    let commit = repo
-        .commit(
-            None,
-            &signature,
-            &signature,
-            message,
-            &tree,
-            &[&head_commit(repo)],
-        )
-        .unwrap();
+    let mut ids = HashSet::new();
+    for oid in walk {
+        let commit = repo.find_commit(oid?)?;
+        if commit.parent_count() == 1 {
+            ids.insert(patch_id(repo, &commit)?);
+        }
+    }
+    Ok(ids)
+}
+
+/// Commits reachable from `head` but not from `boundary`, oldest first
+fn commits_between(
+    repo: &Repository,
+    boundary: Oid,
+    head: Oid,
+) -> Result<Vec<Oid>, Box<dyn Error>> {
+    let mut walk = repo.revwalk()?;
+    walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+    walk.push(head)?;
+    walk.hide(boundary)?;
+    Ok(walk.collect::<Result<Vec<_>, _>>()?)
+}
+
+/// The newest commit on `head` whose changes since `merge_base` match a
+/// single commit on `upstream`, as a squash-merge of them would
+fn squash_point(
+    repo: &Repository,
+    upstream: Oid,
+    head: Oid,
+    merge_base: Oid,
+) -> Result<Option<Oid>, Box<dyn Error>> {
+    let squashed = upstream_patch_ids(repo, upstream, head)?;
+    if squashed.is_empty() {
+        return Ok(None);
+    }
+
+    let base_tree = repo.find_commit(merge_base)?.tree()?;
+    let mut point = None;
+    for oid in commits_between(repo, merge_base, head)? {
+        let commit = repo.find_commit(oid)?;
+        let diff = repo.diff_tree_to_tree(Some(&base_tree), Some(&commit.tree()?), None)?;
+        if squashed.contains(&diff.patchid(None)?) {
+            point = Some(oid);
+        }
+    }
+    Ok(point)
+}
+
+/// Where the commits of `pr`'s own branch start, for rebasing onto `onto`
+///
+/// The merge base with `onto` only works until the branch's parent is
+/// squash-merged: after that, trunk shares none of the parent's commits,
+/// and the merge base would drag all of them along. So the fork point is
+/// whichever of these sits highest on the branch:
+///
+/// - the merge base with `onto`
+/// - the recorded head SHA of each merged PR in `deps`
+/// - the local and remote-tracking refs of those PRs' branches and of
+///   the branch `pr` is based on
+/// - the newest commit whose changes so far match one commit on `onto`
+pub fn fork_point(
+    repo: &Repository,
+    remote: &str,
+    deps: &FlatDep,
+    pr: &PullRequest,
+    onto: Oid,
+) -> Result<Oid, Box<dyn Error>> {
+    let head = local_head(repo, pr.head())?.id();
+    let merge_base = repo.merge_base(onto, head)?;
+
+    let mut branches = vec![pr.base()];
+    let mut candidates = vec![merge_base];
+    for (dep, _) in deps.iter().filter(|(d, _)| d.is_merged()) {
+        branches.push(dep.head());
+        if let Ok(sha) = Oid::from_str(dep.head_sha()) {
+            candidates.push(sha);
+        }
+    }
+    for branch in branches {
+        for name in [branch.to_string(), remote_ref(remote, branch)] {
+            if let Ok(commit) = repo.revparse_single(&name).and_then(|o| o.peel_to_commit()) {
+                candidates.push(commit.id());
+            }
+        }
+    }
+    candidates.extend(squash_point(repo, onto, head, merge_base)?);
+
+    let mut best = (merge_base, 0);
+    for candidate in candidates {
+        if candidate == head
+            || repo.find_commit(candidate).is_err()
+            || !repo.graph_descendant_of(head, candidate)?
+        {
+            continue;
+        }
+        let (ahead, _) = repo.graph_ahead_behind(candidate, merge_base)?;
+        if ahead > best.1 {
+            best = (candidate, ahead);
+        }
+    }
+    Ok(best.0)
+}
 
-    let commit = oid_to_commit(repo, commit);
+/// The branch to rebuild `pr` on: its base, or if that belongs to a merged PR
+/// of the stack, wherever that PR was merged into
+pub fn landed_base<'a>(deps: &'a FlatDep, pr: &'a PullRequest) -> &'a str {
+    let mut base = pr.base();
+    let mut seen = HashSet::new();
+    while let Some((parent, _)) = deps.iter().find(|(d, _)| d.head() == base && d.is_merged()) {
+        if !seen.insert(parent.number()) {
+            break;
+        }
+        base = parent.base();
+    }
+    base
+}
 
+/// Cherry-pick `commit` onto `onto` in the working tree, leaving its
+/// conflicts there for the user to resolve
+fn cherry_pick_into_worktree(
+    repo: &Repository,
+    commit: Oid,
+    onto: Oid,
+) -> Result<(), Box<dyn Error>> {
     let mut cb = CheckoutBuilder::new();
     cb.force();
+    checkout_commit(repo, &repo.find_commit(onto)?, Some(&mut cb));
+
+    let mut cb = CheckoutBuilder::new();
+    cb.allow_conflicts(true);
+    let mut opts = CherrypickOptions::new();
+    opts.checkout_builder(cb);
+    repo.cherrypick(&repo.find_commit(commit)?, Some(&mut opts))?;
 
-    checkout_commit(repo, &commit, Some(&mut cb));
+    Ok(())
+}
 
-    // "Complete" the cherry-pick. There is likely a better way to do
-    // this that I haven't found so far.
-    repo.cleanup_state().unwrap();
+/// Linked worktree where autorebase leaves a conflict for the user to
+/// resolve, so their own checkout is never touched
+pub fn conflict_worktree_path(repo: &Repository) -> PathBuf {
+    state::state_dir(repo).join("worktree")
+}
 
-    commit
+/// The repository's working directory, for running `git` in
+fn workdir(repo: &Repository) -> Result<&Path, Box<dyn Error>> {
+    repo.workdir()
+        .ok_or_else(|| "gh-stack needs a repository with a working directory".into())
 }
 
-fn cherry_pick_range(repo: &Repository, walk: &mut Revwalk) {
-    for from in walk {
-        let from = oid_to_commit(repo, from.unwrap());
+/// Run `git` in the repository's working directory, failing if it does
+fn run_git(repo: &Repository, args: &[&str]) -> Result<(), Box<dyn Error>> {
+    let dir = workdir(repo)?;
+    let output = std::process::Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .output()?;
 
-        if from.parent_count() > 1 {
-            panic!("Exiting: I don't know how to deal with merge commits correctly.");
-        }
+    if !output.status.success() {
+        return Err(format!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Open the conflict worktree, adding it (detached at `at`) if it isn't there yet
+fn open_conflict_worktree(repo: &Repository, at: Oid) -> Result<Repository, Box<dyn Error>> {
+    let path = conflict_worktree_path(repo);
+    if !path.exists() {
+        let location = path.to_string_lossy();
+        run_git(
+            repo,
+            &["worktree", "add", "--detach", &location, &at.to_string()],
+        )?;
+    }
+    Ok(Repository::open(&path)?)
+}
+
+/// Remove the conflict worktree, if there is one
+fn remove_conflict_worktree(repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let path = conflict_worktree_path(repo);
+    if path.exists() {
+        run_git(
+            repo,
+            &["worktree", "remove", "--force", &path.to_string_lossy()],
+        )?;
+    }
+    Ok(())
+}
 
+/// Point a local branch at `target`
+///
+/// If the branch is checked out, the working tree is brought along first; a
+/// safe checkout refuses to overwrite uncommitted changes.
+pub fn move_branch(repo: &Repository, name: &str, target: &Commit) -> Result<(), Box<dyn Error>> {
+    let head = repo.head().ok();
+    let checked_out = head
+        .as_ref()
+        .is_some_and(|h| h.is_branch() && h.shorthand() == Some(name));
+
+    if checked_out {
         let mut cb = CheckoutBuilder::new();
-        cb.allow_conflicts(true);
-        let mut opts = CherrypickOptions::new();
-        opts.checkout_builder(cb);
+        cb.safe();
+        repo.checkout_tree(target.as_object(), Some(&mut cb))?;
+        repo.reference(
+            &format!("refs/heads/{}", name),
+            target.id(),
+            true,
+            "gh-stack: move branch",
+        )?;
+    } else {
+        repo.branch(name, target, true)?;
+    }
+    Ok(())
+}
 
-        println!("Cherry-picking: {:?}", from);
-        repo.cherrypick(&from, Some(&mut opts)).unwrap();
+/// What happened to a local branch when bringing it up to date with its
+/// remote-tracking branch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BranchUpdate {
+    UpToDate,
+    /// Moved from the first commit to the second
+    FastForwarded(String, String),
+    /// Has commits the remote branch doesn't; left alone
+    Diverged,
+    /// No local or no remote-tracking branch
+    Missing,
+}
 
-        let mut index = repo.index().unwrap();
+/// Fast-forward the local `branch` to `remote/branch`, if it can be
+pub fn fast_forward_branch(
+    repo: &Repository,
+    remote: &str,
+    branch: &str,
+) -> Result<BranchUpdate, Box<dyn Error>> {
+    let Ok(local) = repo.find_branch(branch, BranchType::Local) else {
+        return Ok(BranchUpdate::Missing);
+    };
+    let Ok(upstream) = repo.refname_to_id(&format!("refs/remotes/{}/{}", remote, branch)) else {
+        return Ok(BranchUpdate::Missing);
+    };
+    let local = local.get().peel_to_commit()?.id();
 
-        if index.has_conflicts() {
-            let prompt = "Conflicts! Resolve manually and `git add` each one (don't run any `git cherry-pick` commands, though).";
-            loop_until_confirm(prompt);
+    if local == upstream {
+        return Ok(BranchUpdate::UpToDate);
+    }
+    if !repo.graph_descendant_of(upstream, local)? {
+        return Ok(BranchUpdate::Diverged);
+    }
+
+    move_branch(repo, branch, &repo.find_commit(upstream)?)?;
+    Ok(BranchUpdate::FastForwarded(
+        local.to_string(),
+        upstream.to_string(),
+    ))
+}
+
+/// Whether tracked files in the working tree have changes that aren't committed
+pub fn has_uncommitted_changes(repo: &Repository) -> Result<bool, Box<dyn Error>> {
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(false).include_ignored(false);
+    Ok(!repo.statuses(Some(&mut opts))?.is_empty())
+}
+
+/// The branch checked out in `repo`, if it is one of the stack's branches
+/// that the rebase moves
+fn moved_checkout<'a>(repo: &Repository, state: &'a RebaseState) -> Option<&'a BranchRebase> {
+    let head = repo.head().ok()?;
+    if !head.is_branch() {
+        return None;
+    }
+    let name = head.shorthand()?;
+    state
+        .branches
+        .iter()
+        .find(|b| b.name == name && b.new_head.is_some() && b.moved())
+}
+
+/// `signature` as a commit header writes it
+fn signature_header(signature: &Signature) -> Vec<u8> {
+    let when = signature.when();
+    let offset = when.offset_minutes().abs();
+    let mut header = signature.name_bytes().to_vec();
+    header.extend_from_slice(b" <");
+    header.extend_from_slice(signature.email_bytes());
+    header.extend_from_slice(
+        format!(
+            "> {} {}{:02}{:02}",
+            when.seconds(),
+            when.sign(),
+            offset / 60,
+            offset % 60
+        )
+        .as_bytes(),
+    );
+    header
+}
+
+/// The raw contents of `commit` rewritten onto `parent` with `tree` and
+/// `committer`
+///
+/// The author, `encoding` header and message are copied byte for byte, as
+/// git2 only takes messages as UTF-8 and would lose a message in any other
+/// encoding.
+fn replayed_commit_buffer(
+    commit: &Commit,
+    tree: Oid,
+    parent: Oid,
+    committer: &Signature,
+) -> Vec<u8> {
+    let header_line = |name: &[u8]| {
+        commit
+            .raw_header_bytes()
+            .split(|b| *b == b'\n')
+            .find(|line| line.starts_with(name))
+            .map(<[u8]>::to_vec)
+    };
 
-            // Reload index from disk
-            index = repo.index().unwrap();
-            index.read(true).unwrap();
+    let mut buffer = format!("tree {}\nparent {}\n", tree, parent).into_bytes();
+    if let Some(author) = header_line(b"author ") {
+        buffer.extend_from_slice(&author);
+        buffer.push(b'\n');
+    }
+    buffer.extend_from_slice(b"committer ");
+    buffer.extend_from_slice(&signature_header(committer));
+    buffer.push(b'\n');
+    if let Some(encoding) = header_line(b"encoding ") {
+        buffer.extend_from_slice(&encoding);
+        buffer.push(b'\n');
+    }
+    buffer.push(b'\n');
+    buffer.extend_from_slice(commit.message_raw_bytes());
+    buffer
+}
+
+/// `buffer` with `signature` added as its `gpgsig` header, as `git commit -S`
+/// writes it
+fn with_signature(buffer: &[u8], signature: &str) -> Vec<u8> {
+    let end = buffer
+        .windows(2)
+        .position(|w| w == b"\n\n")
+        .map_or(buffer.len(), |i| i + 1);
+    let mut signed = buffer[..end].to_vec();
+    signed.extend_from_slice(b"gpgsig ");
+    signed.extend_from_slice(signature.trim_end().replace('\n', "\n ").as_bytes());
+    signed.push(b'\n');
+    signed.extend_from_slice(&buffer[end..]);
+    signed
+}
+
+/// Commit `tree` on top of `onto` as the replayed copy of `commit`
+///
+/// The copy keeps the original author, author date and message, whatever
+/// its encoding; the committer is the current user, and `signer` signs it if
+/// given. Returns `None` when `commit` no longer changes anything, unless it
+/// was empty to begin with.
+fn commit_onto(
+    repo: &Repository,
+    commit: &Commit,
+    tree: Oid,
+    onto: &Commit,
+    signer: Option<&Signer>,
+) -> Result<Option<Oid>, Box<dyn Error>> {
+    let was_empty = commit.tree_id() == commit.parent(0)?.tree_id();
+    if tree == onto.tree_id() && !was_empty {
+        return Ok(None);
+    }
+
+    let mut buffer = replayed_commit_buffer(commit, tree, onto.id(), &repo.signature()?);
+    if let Some(signer) = signer {
+        let signature = signer.sign(&buffer)?;
+        buffer = with_signature(&buffer, &signature);
+    }
+    Ok(Some(repo.odb()?.write(ObjectType::Commit, &buffer)?))
+}
+
+/// What became of a commit when it was replayed
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Replayed {
+    /// Copied onto the stack as this commit: the original, if nothing below
+    /// it changed, or else a new copy
+    Picked(Oid),
+    /// Left out, for this reason
+    Skipped(&'static str),
+    /// Doesn't apply cleanly; these paths conflict
+    Conflict(Vec<String>),
+}
+
+/// Paths with conflicts in a merged index
+fn conflicted_paths(index: &Index) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut paths = vec![];
+    for conflict in index.conflicts()? {
+        let conflict = conflict?;
+        if let Some(entry) = conflict.our.or(conflict.their).or(conflict.ancestor) {
+            paths.push(String::from_utf8_lossy(&entry.path).into_owned());
         }
+    }
+    Ok(paths)
+}
+
+/// Replay a single commit on top of `onto`, in memory
+///
+/// This is `git rebase` without a working tree. Like `git rebase`, merge
+/// commits are dropped (the commits they bring in are replayed on their
+/// own) and commits whose changes are already in `onto` are skipped.
+/// Commits that were empty to begin with are kept.
+fn replay_commit(
+    repo: &Repository,
+    commit: &Commit,
+    onto: &Commit,
+    applied: &HashSet<Oid>,
+    signer: Option<&Signer>,
+) -> Result<Replayed, Box<dyn Error>> {
+    // Nothing below this commit changed, so it can stay as it is
+    if commit.parent_count() == 1 && commit.parent_id(0)? == onto.id() {
+        return Ok(Replayed::Picked(commit.id()));
+    }
+
+    if commit.parent_count() > 1 {
+        return Ok(Replayed::Skipped("merge commit"));
+    }
+
+    if applied.contains(&patch_id(repo, commit)?) {
+        return Ok(Replayed::Skipped("already applied"));
+    }
 
-        create_commit(repo, &mut index, from.message().unwrap());
+    let mut index = repo.cherrypick_commit(commit, onto, 0, None)?;
+    if index.has_conflicts() {
+        return Ok(Replayed::Conflict(conflicted_paths(&index)?));
+    }
+
+    let tree = index.write_tree_to(repo)?;
+    match commit_onto(repo, commit, tree, onto, signer)? {
+        Some(new) => Ok(Replayed::Picked(new)),
+        None => Ok(Replayed::Skipped("no changes left")),
     }
 }
 
-pub async fn perform_rebase(
-    deps: FlatDep,
+/// A branch of the stack being rebuilt by autorebase
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BranchRebase {
+    /// Local branch name
+    pub name: String,
+    /// The local branch's SHA when the rebase started
+    pub head: String,
+    /// The commit `commits` were counted from; if the stack below still ends
+    /// here, the branch is left as it is
+    pub base: String,
+    /// Commits to replay, oldest first
+    pub commits: Vec<String>,
+    /// The remote branch's SHA when the rebase started, used as the push lease; empty if
+    /// the branch was never pushed
+    pub remote_sha: String,
+    /// The rebuilt branch head, once all its commits are replayed
+    pub new_head: Option<String>,
+}
+
+impl BranchRebase {
+    /// Whether the rebuilt branch differs from the remote branch
+    pub fn needs_push(&self) -> bool {
+        self.new_head.as_ref() != Some(&self.remote_sha)
+    }
+
+    /// Whether the rebuilt branch differs from the local branch
+    pub fn moved(&self) -> bool {
+        self.new_head.as_ref() != Some(&self.head)
+    }
+}
+
+/// Progress of an autorebase, saved after every commit so a conflict can be
+/// resolved and the rebase picked up again with `--continue`, `--skip` or
+/// `--abort`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RebaseState {
+    /// Remote the rebuilt branches are pushed to
+    pub remote: String,
+    /// Working directory autorebase was started from; its checkout follows
+    /// its branch when the rebase finishes
+    #[serde(default)]
+    pub checkout: Option<String>,
+    /// Patch IDs of the commits already in the new base
+    pub applied: Vec<String>,
+    /// Branches to rebuild, bottom of the stack first
+    pub branches: Vec<BranchRebase>,
+    /// Top of the rebuilt stack so far
+    pub tip: String,
+    /// Index of the branch being rebuilt
+    pub branch: usize,
+    /// Index of the next commit of that branch to replay
+    pub commit: usize,
+    /// Only update local branches; push later with `gh-stack push`
+    #[serde(default)]
+    pub no_push: bool,
+}
+
+impl RebaseState {
+    /// Location of the state file for a repository
+    pub fn path(repo: &Repository) -> PathBuf {
+        state::state_dir(repo).join("autorebase.json")
+    }
+
+    /// Load the state at `path`, if an autorebase is in progress
+    pub fn load(path: &Path) -> Result<Option<Self>, Box<dyn Error>> {
+        state::read_json(path)
+    }
+
+    /// Persist the state to `path`
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        state::write_json(path, self)
+    }
+
+    /// The commit the rebase is stopped at
+    fn current_commit(&self) -> Option<&str> {
+        self.branches
+            .get(self.branch)?
+            .commits
+            .get(self.commit)
+            .map(String::as_str)
+    }
+
+    fn applied(&self) -> Result<HashSet<Oid>, git2::Error> {
+        self.applied.iter().map(|id| Oid::from_str(id)).collect()
+    }
+}
+
+/// How an autorebase run ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RebaseOutcome {
+    /// Every branch was rebuilt and pushed
+    Done,
+    /// Every branch was rebuilt, but the remote refused some of the pushes
+    Rejected(Vec<RejectedPush>),
+    /// Stopped at a commit that doesn't apply cleanly; the conflict is in
+    /// the conflict worktree and the progress is saved
+    Conflict {
+        branch: String,
+        commit: String,
+        summary: String,
+        worktree: PathBuf,
+    },
+}
+
+/// Replay the remaining commits of every branch, saving progress to `path`
+///
+/// Returns the conflict that stopped the replay, if any.
+fn replay_branches(
     repo: &Repository,
-    remote: &str,
-    boundary: Option<&str>,
+    state: &mut RebaseState,
+    path: &Path,
+) -> Result<Option<RebaseOutcome>, Box<dyn Error>> {
+    let applied = state.applied()?;
+    let signer = Signer::from_repo(repo)?;
+
+    while state.branch < state.branches.len() {
+        let branch = &state.branches[state.branch];
+        if state.commit == 0 {
+            println!("\nWorking on PR: {:?}", branch.name);
+
+            if branch.base == state.tip {
+                println!("Nothing below {} changed; leaving it alone", branch.name);
+                state.tip = branch.head.clone();
+                state.commit = branch.commits.len();
+            }
+        }
+
+        while let Some(oid) = state.current_commit() {
+            let commit = repo.find_commit(Oid::from_str(oid)?)?;
+            let tip = repo.find_commit(Oid::from_str(&state.tip)?)?;
+
+            let summary = commit.summary().unwrap_or_default();
+            match replay_commit(repo, &commit, &tip, &applied, signer.as_ref())? {
+                Replayed::Picked(new) => {
+                    if new == commit.id() {
+                        println!("Unchanged: {} {}", commit.id(), summary);
+                    } else {
+                        println!("Cherry-picked: {} {}", commit.id(), summary);
+                    }
+                    state.tip = new.to_string();
+                }
+                Replayed::Skipped(reason) => {
+                    println!("Skipping ({}): {} {}", reason, commit.id(), summary);
+                }
+                Replayed::Conflict(_) => {
+                    let worktree = open_conflict_worktree(repo, tip.id())?;
+                    cherry_pick_into_worktree(&worktree, commit.id(), tip.id())?;
+                    state.save(path)?;
+                    return Ok(Some(RebaseOutcome::Conflict {
+                        branch: state.branches[state.branch].name.clone(),
+                        commit: commit.id().to_string(),
+                        summary: commit.summary().unwrap_or_default().to_string(),
+                        worktree: conflict_worktree_path(repo),
+                    }));
+                }
+            }
+
+            state.commit += 1;
+            state.save(path)?;
+        }
+
+        state.branches[state.branch].new_head = Some(state.tip.clone());
+        state.branch += 1;
+        state.commit = 0;
+        state.save(path)?;
+    }
+
+    Ok(None)
+}
+
+/// Replay what's left of an autorebase, then push and update local branches
+async fn finish_rebase(
+    repo: &Repository,
+    mut state: RebaseState,
     ci: bool,
-) -> Result<(), Box<dyn Error>> {
-    let deps = deps
+) -> Result<RebaseOutcome, Box<dyn Error>> {
+    let path = RebaseState::path(repo);
+    if let Some(conflict) = replay_branches(repo, &mut state, &path)? {
+        return Ok(conflict);
+    }
+    remove_conflict_worktree(repo)?;
+
+    // The checkout has to follow its branch, so it mustn't have changes of its own
+    if let Some(branch) = moved_checkout(repo, &state) {
+        if has_uncommitted_changes(repo)? {
+            state.save(&path)?;
+            return Err(format!(
+                "{} has uncommitted changes. Commit or stash them, then run `gh-stack autorebase --continue`.",
+                branch.name
+            )
+            .into());
+        }
+    }
+
+    let pushes = push_plan(&state);
+    let touched: Vec<String> = state
+        .branches
         .iter()
-        .filter(|(dep, _)| *dep.state() == PullRequestStatus::Open)
-        .collect::<Vec<_>>();
+        .filter(|b| b.moved() || (!state.no_push && b.needs_push()))
+        .map(|b| b.name.clone())
+        .collect();
+    if !touched.is_empty() {
+        let op = backup::record(repo, "autorebase", &state.remote, &touched)?;
+        println!(
+            "\nBacked up {} branch(es); undo with `gh-stack undo {}`",
+            touched.len(),
+            op.id
+        );
+    }
 
-    let (pr, _) = deps[0];
+    // A later `gh-stack push` leases on what the remote had when this
+    // restack started; an earlier unpushed restack's lease is older still
+    let mut leases = load_leases(repo)?;
+    let mut rejected = vec![];
+    if state.no_push {
+        println!("\nSkipping the push (--no-push).");
+        for branch in &state.branches {
+            leases
+                .entry(branch.name.clone())
+                .or_insert_with(|| branch.remote_sha.clone());
+        }
+    } else if pushes.is_empty() {
+        println!(
+            "\nEvery branch already matches {}; nothing to push.",
+            state.remote
+        );
+    } else {
+        rejected = push_branches(repo, &state.remote, &pushes, ci).await?;
+    }
+    if !state.no_push {
+        // Only the rejected branches are still waiting to be pushed
+        leases.retain(|name, _| {
+            let pushed = state.branches.iter().any(|b| &b.name == name);
+            !pushed || rejected.iter().any(|r| &r.branch == name)
+        });
+    }
+    save_leases(repo, &leases)?;
 
-    let base = rev_to_commit(repo, &remote_ref(remote, pr.base()));
-    let head = rev_to_commit(repo, pr.head());
+    println!("\nUpdating local branches so they point to the new stack.\n");
+    for branch in &state.branches {
+        let target = branch.new_head.as_deref().unwrap_or(&state.tip);
+        if !branch.moved() {
+            println!("  = Branch {} is unchanged", branch.name);
+            continue;
+        }
+        let target = repo.find_commit(Oid::from_str(target)?)?;
+        println!("  + Branch {} now points to {}", branch.name, target.id());
+        move_branch(repo, &branch.name, &target)?;
+    }
 
-    let mut stop_cherry_pick_at = match boundary {
-        Some(rev) => rev_to_commit(repo, rev).id(),
-        None => repo.merge_base(base.id(), head.id()).unwrap(),
-    };
-    let mut update_local_branches_to = vec![];
+    state::remove(&path)?;
+    if rejected.is_empty() {
+        Ok(RebaseOutcome::Done)
+    } else {
+        Ok(RebaseOutcome::Rejected(rejected))
+    }
+}
 
-    println!("Checking out {:?}", base);
-    checkout_commit(repo, &base, None);
+/// A branch update to push, guarded by the remote SHA it should replace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BranchPush {
+    pub branch: String,
+    /// Commit the remote branch should point to
+    pub sha: String,
+    /// Where gh-stack last saw the remote branch; empty if it shouldn't exist
+    pub expected: String,
+}
 
-    let mut push_refspecs = vec![];
+impl BranchPush {
+    fn refspec(&self) -> String {
+        format!("{}:refs/heads/{}", self.sha, self.branch)
+    }
 
-    for (pr, _) in deps {
-        println!("\nWorking on PR: {:?}", pr.head());
+    /// Only overwrite the remote branch if it still points where we saw it,
+    /// so a teammate's push in the meantime isn't lost
+    fn lease(&self) -> String {
+        format!("--force-with-lease={}:{}", self.branch, self.expected)
+    }
+}
 
-        let from = rev_to_commit(repo, pr.head());
+/// A branch the remote refused to update
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedPush {
+    pub branch: String,
+    /// Git's explanation, e.g. `stale info` when the lease failed
+    pub reason: String,
+}
 
-        let mut walk = repo.revwalk().unwrap();
-        walk.set_sorting(Sort::TOPOLOGICAL).unwrap();
-        walk.set_sorting(Sort::REVERSE).unwrap();
-        walk.push(from.id()).unwrap();
-        walk.hide(stop_cherry_pick_at).unwrap();
+/// Branches to push after a rebase
+///
+/// Branches that come out of the rebase exactly as the remote has them are
+/// left out, so their PRs keep their CI runs and approvals.
+fn push_plan(state: &RebaseState) -> Vec<BranchPush> {
+    state
+        .branches
+        .iter()
+        .filter(|b| b.needs_push())
+        .map(|b| BranchPush {
+            branch: b.name.clone(),
+            sha: b.new_head.clone().unwrap_or_else(|| state.tip.clone()),
+            expected: b.remote_sha.clone(),
+        })
+        .collect()
+}
 
-        // TODO: Simplify by using rebase instead of cherry-pick
-        // TODO: Skip if remote/<branch> is the same SHA as <branch> (only until the first cherry-pick)
-        cherry_pick_range(repo, &mut walk);
+/// Where a `--no-push` restack saw each remote branch, waiting for
+/// `gh-stack push`
+fn leases_path(repo: &Repository) -> PathBuf {
+    state::state_dir(repo).join("leases.json")
+}
 
-        // Record the commit (in the new stack) that the local branch should now point to.
-        // Actually perform the switch later on in a batch so we don't leave the repo in
-        // a troubled state if this process is interrupted.
-        update_local_branches_to.push((pr.head(), head_commit(repo)));
+fn load_leases(repo: &Repository) -> Result<BTreeMap<String, String>, Box<dyn Error>> {
+    Ok(state::read_json(&leases_path(repo))?.unwrap_or_default())
+}
 
-        // Use remote branch as boundary for the next cherry-pick
-        let from = rev_to_commit(repo, &remote_ref(remote, pr.head()));
-        stop_cherry_pick_at = from.id();
+fn save_leases(repo: &Repository, leases: &BTreeMap<String, String>) -> Result<(), Box<dyn Error>> {
+    match leases.is_empty() {
+        true => state::remove(&leases_path(repo)),
+        false => state::write_json(&leases_path(repo), leases),
+    }
+}
 
-        push_refspecs.push(format!(
-            "{}:refs/heads/{}",
-            head_commit(repo).id(),
-            pr.head()
-        ));
+/// Forget the recorded leases of branches that have been pushed
+pub fn clear_leases(repo: &Repository, branches: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut leases = load_leases(repo)?;
+    leases.retain(|branch, _| !branches.contains(branch));
+    save_leases(repo, &leases)
+}
+
+/// Branches of the stack whose local branch differs from the PR's head on
+/// GitHub, for `gh-stack push`, along with those it mustn't push
+///
+/// Each push is leased on where the `--no-push` restack saw the remote
+/// branch, or else on the remote-tracking branch, so a teammate's push that
+/// gh-stack hasn't seen is never overwritten. A local branch that is behind
+/// the remote one, by GitHub's account or the remote-tracking branch's, is
+/// refused rather than pushed. Merged and closed PRs, and PRs without a
+/// local branch, are left out.
+pub fn stack_pushes(
+    repo: &Repository,
+    remote: &str,
+    deps: &FlatDep,
+) -> Result<(Vec<BranchPush>, Vec<RejectedPush>), Box<dyn Error>> {
+    let leases = load_leases(repo)?;
+    let mut pushes = vec![];
+    let mut refused = vec![];
+
+    for (pr, _) in deps {
+        if *pr.state() != PullRequestStatus::Open {
+            continue;
+        }
+        let Some(local) = repo
+            .find_branch(pr.head(), BranchType::Local)
+            .ok()
+            .and_then(|b| b.get().target())
+        else {
+            continue;
+        };
+        if local.to_string() == pr.head_sha() {
+            continue;
+        }
+
+        let tracking = repo
+            .refname_to_id(&format!("refs/remotes/{}", remote_ref(remote, pr.head())))
+            .ok();
+        let behind = [Oid::from_str(pr.head_sha()).ok(), tracking]
+            .into_iter()
+            .flatten()
+            .any(|theirs| {
+                theirs != local && repo.graph_descendant_of(theirs, local).unwrap_or(false)
+            });
+        if behind {
+            refused.push(RejectedPush {
+                branch: pr.head().to_string(),
+                reason: "behind the remote".to_string(),
+            });
+            continue;
+        }
+
+        let expected = leases
+            .get(pr.head())
+            .cloned()
+            .or_else(|| tracking.map(|oid| oid.to_string()))
+            .unwrap_or_default();
+        pushes.push(BranchPush {
+            branch: pr.head().to_string(),
+            sha: local.to_string(),
+            expected,
+        });
     }
 
-    let repo_dir = repo.workdir().unwrap().to_str().unwrap();
+    Ok((pushes, refused))
+}
+
+/// Branches `git push --porcelain` reported as rejected
+fn parse_rejections(porcelain: &str) -> Vec<RejectedPush> {
+    porcelain
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let (flag, refs, summary) = (fields.next()?, fields.next()?, fields.next()?);
+            if flag != "!" {
+                return None;
+            }
+            let to = refs.rsplit(':').next()?;
+            let reason = summary
+                .rsplit_once('(')
+                .map(|(_, r)| r.trim_end_matches(')'))
+                .unwrap_or(summary);
+            Some(RejectedPush {
+                branch: to.trim_start_matches("refs/heads/").to_string(),
+                reason: reason.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Force-push `pushes` to `remote` with leases, returning the rejected ones
+pub async fn push_branches(
+    repo: &Repository,
+    remote: &str,
+    pushes: &[BranchPush],
+    ci: bool,
+) -> Result<Vec<RejectedPush>, Box<dyn Error>> {
+    let repo_dir = workdir(repo)?;
+    let push_refspecs: Vec<String> = pushes.iter().map(BranchPush::refspec).collect();
 
     // `libgit2` doesn't support refspecs containing raw SHAs, so we shell out
     // to `git push` instead. https://github.com/libgit2/libgit2/issues/1125
     let mut command = Command::new("git");
-    command.arg("push").arg("-f").arg(remote);
-    command.args(push_refspecs.as_slice());
+    command.arg("push").arg("--porcelain");
+    command.args(pushes.iter().map(BranchPush::lease));
+    command.arg(remote);
+    command.args(&push_refspecs);
     command.current_dir(repo_dir);
+    command.stdout(Stdio::piped()).stderr(Stdio::inherit());
 
     println!("\n{:?}", push_refspecs);
     if ci {
@@ -218,13 +1076,1317 @@ pub async fn perform_rebase(
         loop_until_confirm("Going to push these refspecs ☝️ ");
     }
 
-    command.spawn()?.wait().await?;
+    let output = command.output().await?;
+    let rejected = parse_rejections(&String::from_utf8_lossy(&output.stdout));
+    if !output.status.success() && rejected.is_empty() {
+        return Err(format!("`git push {}` failed", remote).into());
+    }
 
-    println!("\nUpdating local branches so they point to the new stack.\n");
-    for (branch, target) in update_local_branches_to {
-        println!("  + Branch {} now points to {}", branch, target.id());
-        repo.branch(branch, &target, true).unwrap();
+    Ok(rejected)
+}
+
+/// What autorebase would do with one commit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlannedStep {
+    /// Kept as it is, since nothing below it changed
+    Keep,
+    /// Copied onto the rebuilt branch below
+    Pick,
+    /// Left out, for this reason
+    Skip(&'static str),
+    /// Would stop the autorebase with conflicts in these paths
+    Conflict(Vec<String>),
+}
+
+/// A commit of a branch, and what autorebase would do with it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedCommit {
+    pub sha: String,
+    pub summary: String,
+    pub step: PlannedStep,
+}
+
+/// What autorebase would do with one branch of the stack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedBranch {
+    pub name: String,
+    /// What the branch is rebuilt on: the base branch, or the branch below
+    pub onto: String,
+    pub commits: Vec<PlannedCommit>,
+    /// Whether the rebuilt branch differs from the remote one, and would be pushed
+    pub changes: bool,
+}
+
+/// Cherry-pick `commit` onto `onto` in memory, settling conflicting hunks in
+/// the commit's favour, to carry on a dry run past a conflict
+fn pick_past_conflict(
+    repo: &Repository,
+    commit: &Commit,
+    onto: &Commit,
+) -> Result<Option<Oid>, Box<dyn Error>> {
+    let mut opts = MergeOptions::new();
+    opts.file_favor(FileFavor::Theirs);
+    let mut index = repo.cherrypick_commit(commit, onto, 0, Some(&opts))?;
+    if index.has_conflicts() {
+        return Ok(None);
+    }
+    let tree = index.write_tree_to(repo)?;
+    commit_onto(repo, commit, tree, onto, None)
+}
+
+/// Work out what autorebase would do, without touching the working tree,
+/// HEAD, any branch or the object database
+///
+/// Every commit is replayed in memory, as autorebase would replay it. A
+/// commit that conflicts is reported with its conflicting paths, and the
+/// rest of the stack is replayed as if the conflict was resolved in the
+/// commit's favour, so later conflicts show up too. The trees and commits
+/// the replay makes are kept in memory and thrown away, so they aren't
+/// signed either.
+pub fn dry_run_rebase(
+    deps: &FlatDep,
+    repo: &Repository,
+    remote: &str,
+    boundary: Option<&str>,
+) -> Result<Vec<PlannedBranch>, Box<dyn Error>> {
+    // A handle of its own whose writes go to memory, ahead of the loose and
+    // pack backends, and vanish when it's dropped
+    let repo = &Repository::open(repo.path())?;
+    repo.odb()?.add_new_mempack_backend(1000)?;
+
+    let (state, base) = plan_rebase(deps, repo, remote, boundary)?;
+    let applied = state.applied()?;
+    let mut tip = Oid::from_str(&state.tip)?;
+    let mut onto = base;
+    let mut planned = vec![];
+
+    for branch in &state.branches {
+        let unchanged = Oid::from_str(&branch.base)? == tip;
+        let mut commits = vec![];
+
+        for oid in &branch.commits {
+            let commit = repo.find_commit(Oid::from_str(oid)?)?;
+            let step = if unchanged {
+                PlannedStep::Keep
+            } else {
+                let current = repo.find_commit(tip)?;
+                match replay_commit(repo, &commit, &current, &applied, None)? {
+                    Replayed::Picked(new) => {
+                        tip = new;
+                        if new == commit.id() {
+                            PlannedStep::Keep
+                        } else {
+                            PlannedStep::Pick
+                        }
+                    }
+                    Replayed::Skipped(reason) => PlannedStep::Skip(reason),
+                    Replayed::Conflict(paths) => {
+                        if let Some(new) = pick_past_conflict(repo, &commit, &current)? {
+                            tip = new;
+                        }
+                        PlannedStep::Conflict(paths)
+                    }
+                }
+            };
+
+            commits.push(PlannedCommit {
+                sha: commit.id().to_string(),
+                summary: commit.summary().unwrap_or_default().to_string(),
+                step,
+            });
+        }
+
+        if unchanged {
+            tip = Oid::from_str(&branch.head)?;
+        }
+        planned.push(PlannedBranch {
+            name: branch.name.clone(),
+            onto,
+            commits,
+            changes: tip.to_string() != branch.remote_sha,
+        });
+        onto = branch.name.clone();
+    }
+
+    Ok(planned)
+}
+
+/// Plain-text report of a dry run
+pub fn render_dry_run(planned: &[PlannedBranch]) -> String {
+    let mut out = String::new();
+    let mut conflicts = 0;
+
+    for branch in planned {
+        let outcome = if branch.changes {
+            "would be pushed"
+        } else {
+            "unchanged"
+        };
+        out.push_str(&format!(
+            "{} (onto {}): {}\n",
+            branch.name, branch.onto, outcome
+        ));
+
+        for commit in &branch.commits {
+            let short = &commit.sha[..7.min(commit.sha.len())];
+            let line = match &commit.step {
+                PlannedStep::Keep => format!("  keep      {} {}", short, commit.summary),
+                PlannedStep::Pick => format!("  pick      {} {}", short, commit.summary),
+                PlannedStep::Skip(reason) => {
+                    format!("  skip      {} {} ({})", short, commit.summary, reason)
+                }
+                PlannedStep::Conflict(paths) => {
+                    conflicts += 1;
+                    format!(
+                        "  CONFLICT  {} {}\n            in {}",
+                        short,
+                        commit.summary,
+                        paths.join(", ")
+                    )
+                }
+            };
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+
+    let pushed = planned.iter().filter(|b| b.changes).count();
+    out.push_str(&format!(
+        "\n{} of {} branch(es) would be pushed.\n",
+        pushed,
+        planned.len()
+    ));
+    if conflicts > 0 {
+        out.push_str(&format!(
+            "{} commit(s) would conflict. Steps after a conflict assume it was resolved in the commit's favour.\n",
+            conflicts
+        ));
+    }
+
+    out
+}
+
+/// Commit the user's resolution of the conflicted commit the rebase is
+/// stopped at, and move past it
+fn commit_resolution(repo: &Repository, state: &mut RebaseState) -> Result<(), Box<dyn Error>> {
+    let Some(oid) = state.current_commit() else {
+        return Ok(());
+    };
+    let path = conflict_worktree_path(repo);
+    if !path.exists() {
+        return Err(format!("The conflict worktree {} is missing", path.display()).into());
     }
+    let worktree = Repository::open(&path)?;
+    let commit = worktree.find_commit(Oid::from_str(oid)?)?;
 
+    let mut index = worktree.index()?;
+    index.read(true)?;
+    if index.has_conflicts() {
+        return Err(format!(
+            "There are still unresolved conflicts. Resolve them in {} and `git add` each file first.",
+            path.display()
+        )
+        .into());
+    }
+
+    let tree = index.write_tree_to(&worktree)?;
+    let tip = worktree.find_commit(Oid::from_str(&state.tip)?)?;
+    let signer = Signer::from_repo(&worktree)?;
+    if let Some(new) = commit_onto(&worktree, &commit, tree, &tip, signer.as_ref())? {
+        // The worktree already holds the resolved commit; keep its HEAD with it
+        worktree.set_head_detached(new)?;
+        state.tip = new.to_string();
+    }
+
+    // "Complete" the cherry-pick
+    worktree.cleanup_state()?;
+    state.commit += 1;
     Ok(())
 }
+
+/// Load the autorebase in progress, along with the checkout it was started from
+///
+/// `--continue` and friends may be run from anywhere in the repository,
+/// including the conflict worktree itself.
+fn load_rebase_state(repo: &Repository) -> Result<(Repository, RebaseState), Box<dyn Error>> {
+    let state = RebaseState::load(&RebaseState::path(repo))?.ok_or("No autorebase in progress")?;
+    let checkout = match &state.checkout {
+        Some(dir) => Repository::open(dir)?,
+        None => Repository::open(repo.path())?,
+    };
+    Ok((checkout, state))
+}
+
+/// Resume an autorebase stopped at a conflict, once the user has resolved it
+pub async fn continue_rebase(repo: &Repository, ci: bool) -> Result<RebaseOutcome, Box<dyn Error>> {
+    let (repo, mut state) = load_rebase_state(repo)?;
+    commit_resolution(&repo, &mut state)?;
+    finish_rebase(&repo, state, ci).await
+}
+
+/// Resume an autorebase stopped at a conflict, leaving out the conflicting commit
+pub async fn skip_commit(repo: &Repository, ci: bool) -> Result<RebaseOutcome, Box<dyn Error>> {
+    let (repo, mut state) = load_rebase_state(repo)?;
+
+    let path = conflict_worktree_path(&repo);
+    if path.exists() {
+        Repository::open(&path)?.cleanup_state()?;
+    }
+
+    state.commit += 1;
+    finish_rebase(&repo, state, ci).await
+}
+
+/// Give up on an autorebase
+///
+/// The user's checkout, local branches and remote branches are only
+/// touched once every branch has been rebuilt, so all that's left to undo
+/// is the conflict worktree.
+pub fn abort_rebase(repo: &Repository) -> Result<(), Box<dyn Error>> {
+    let (repo, _) = load_rebase_state(repo)?;
+
+    remove_conflict_worktree(&repo)?;
+    state::remove(&RebaseState::path(&repo))?;
+    Ok(())
+}
+
+/// Fetch the latest state of `remote` into its remote-tracking branches,
+/// dropping those of branches the remote no longer has
+pub async fn fetch(repo: &Repository, remote: &str) -> Result<(), Box<dyn Error>> {
+    let repo_dir = workdir(repo)?;
+
+    let status = Command::new("git")
+        .arg("fetch")
+        .arg("--prune")
+        .arg(remote)
+        .current_dir(repo_dir)
+        .spawn()?
+        .wait()
+        .await?;
+
+    if !status.success() {
+        return Err(format!("`git fetch --prune {}` failed", remote).into());
+    }
+
+    Ok(())
+}
+
+pub async fn perform_rebase(
+    deps: FlatDep,
+    repo: &Repository,
+    remote: &str,
+    boundary: Option<&str>,
+    ci: bool,
+    no_push: bool,
+) -> Result<RebaseOutcome, Box<dyn Error>> {
+    let path = RebaseState::path(repo);
+    if path.exists() {
+        return Err("An autorebase is already in progress. Finish it with --continue or --skip, or undo it with --abort.".into());
+    }
+
+    let (mut state, _) = plan_rebase(&deps, repo, remote, boundary)?;
+    state.no_push = no_push;
+
+    // Refuse up front rather than after the push, when the checkout can't follow
+    if let Ok(head) = repo.head() {
+        let on_stack = head
+            .shorthand()
+            .is_some_and(|name| head.is_branch() && state.branches.iter().any(|b| b.name == name));
+        if on_stack && has_uncommitted_changes(repo)? {
+            return Err(format!(
+                "{} has uncommitted changes. Commit or stash them before restacking.",
+                head.shorthand().unwrap_or_default()
+            )
+            .into());
+        }
+    }
+
+    state.save(&path)?;
+    finish_rebase(repo, state, ci).await
+}
+
+/// Work out every branch's commits up front, so the rebase can be resumed
+/// from the saved state alone
+///
+/// Also returns the name of the branch the stack is rebuilt on.
+fn plan_rebase(
+    deps: &FlatDep,
+    repo: &Repository,
+    remote: &str,
+    boundary: Option<&str>,
+) -> Result<(RebaseState, String), Box<dyn Error>> {
+    let open = deps
+        .iter()
+        .filter(|(dep, _)| *dep.state() == PullRequestStatus::Open)
+        .collect::<Vec<_>>();
+
+    let (pr, _) = open.first().ok_or("No open PRs to restack")?;
+
+    let onto = landed_base(deps, pr);
+    if onto != pr.base() {
+        println!(
+            "#{}'s base {} has been merged; rebuilding it on {}",
+            pr.number(),
+            pr.base(),
+            onto
+        );
+    }
+    // Fall back on the local branch when there's no remote-tracking one
+    let onto_ref = match repo.revparse_single(&remote_ref(remote, onto)) {
+        Ok(_) => remote_ref(remote, onto),
+        Err(_) => onto.to_string(),
+    };
+    let base = rev_to_commit(repo, &onto_ref)?;
+    let head = local_head(repo, pr.head())?;
+
+    let mut stop_cherry_pick_at = match boundary {
+        Some(rev) => rev_to_commit(repo, rev)?.id(),
+        None => fork_point(repo, remote, deps, pr, base.id())?,
+    };
+
+    let mut branches = vec![];
+    for (pr, _) in open {
+        let from = local_head(repo, pr.head())?;
+        let commits = commits_between(repo, stop_cherry_pick_at, from.id())?;
+        let base = stop_cherry_pick_at;
+
+        // Use remote branch as boundary for the next cherry-pick, or the
+        // local one if the branch was never pushed
+        let remote_head = repo
+            .revparse_single(&remote_ref(remote, pr.head()))
+            .and_then(|o| o.peel_to_commit())
+            .ok();
+        stop_cherry_pick_at = remote_head.as_ref().map_or(from.id(), Commit::id);
+
+        branches.push(BranchRebase {
+            name: pr.head().to_string(),
+            head: from.id().to_string(),
+            base: base.to_string(),
+            commits: commits.iter().map(Oid::to_string).collect(),
+            // An empty lease only lets the push create the branch
+            remote_sha: remote_head.map(|c| c.id().to_string()).unwrap_or_default(),
+            new_head: None,
+        });
+    }
+
+    let state = RebaseState {
+        remote: remote.to_string(),
+        checkout: repo.workdir().map(|dir| dir.to_string_lossy().into_owned()),
+        applied: upstream_patch_ids(repo, base.id(), head.id())?
+            .iter()
+            .map(Oid::to_string)
+            .collect(),
+        branches,
+        tip: base.id().to_string(),
+        branch: 0,
+        commit: 0,
+        no_push: false,
+    };
+
+    Ok((state, onto_ref))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{commit, deps, init_repo, pr};
+
+    /// Replay `commits` on `onto` in memory, returning the new tip
+    fn replay(repo: &Repository, commits: &[Oid], onto: Oid, applied: &HashSet<Oid>) -> Oid {
+        let mut tip = onto;
+        for oid in commits {
+            let commit = repo.find_commit(*oid).unwrap();
+            let onto = repo.find_commit(tip).unwrap();
+            match replay_commit(repo, &commit, &onto, applied, None).unwrap() {
+                Replayed::Picked(new) => tip = new,
+                Replayed::Skipped(_) => {}
+                Replayed::Conflict(paths) => panic!("unexpected conflict in {:?}", paths),
+            }
+        }
+        tip
+    }
+
+    /// A rebase of one branch holding `commits` onto `onto`, with HEAD on `start`
+    fn rebase_state(repo: &Repository, start: Oid, commits: &[Oid], onto: Oid) -> RebaseState {
+        repo.branch("feature", &repo.find_commit(start).unwrap(), true)
+            .unwrap();
+        repo.set_head("refs/heads/feature").unwrap();
+        let mut cb = CheckoutBuilder::new();
+        cb.force();
+        repo.checkout_head(Some(&mut cb)).unwrap();
+
+        RebaseState {
+            remote: "origin".to_string(),
+            checkout: repo.workdir().map(|dir| dir.to_string_lossy().into_owned()),
+            applied: vec![],
+            branches: vec![BranchRebase {
+                name: "feature".to_string(),
+                head: start.to_string(),
+                base: repo
+                    .find_commit(commits[0])
+                    .unwrap()
+                    .parent_id(0)
+                    .unwrap()
+                    .to_string(),
+                commits: commits.iter().map(Oid::to_string).collect(),
+                remote_sha: start.to_string(),
+                new_head: None,
+            }],
+            tip: onto.to_string(),
+            branch: 0,
+            commit: 0,
+            no_push: false,
+        }
+    }
+
+    fn file(repo: &Repository, commit: Oid, name: &str) -> String {
+        let tree = repo.find_commit(commit).unwrap().tree().unwrap();
+        let blob = tree.get_name(name).unwrap().to_object(repo).unwrap();
+        String::from_utf8(blob.as_blob().unwrap().content().to_vec()).unwrap()
+    }
+
+    fn messages(repo: &Repository, from: Oid, to: Oid) -> Vec<String> {
+        commits_between(repo, from, to)
+            .unwrap()
+            .iter()
+            .map(|c| repo.find_commit(*c).unwrap().summary().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_replay_keeps_author_and_message() {
+        let (_dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let feature = commit(&repo, &[root], &[("b", "1")], "feature\n\nbody\n");
+        let trunk = commit(&repo, &[root], &[("c", "1")], "trunk");
+
+        let tip = replay(&repo, &[feature], trunk, &HashSet::new());
+        let new = repo.find_commit(tip).unwrap();
+        let old = repo.find_commit(feature).unwrap();
+
+        assert_eq!(new.parent_id(0).unwrap(), trunk);
+        assert_eq!(new.message(), Some("feature\n\nbody\n"));
+        assert_eq!(new.author().name(), Some("Author"));
+        assert_eq!(new.author().when(), old.author().when());
+        assert_eq!(new.committer().name(), Some("Committer"));
+        assert_eq!(file(&repo, tip, "b"), "1");
+        assert_eq!(file(&repo, tip, "c"), "1");
+    }
+
+    /// Sign with a stand-in for gpg that signs as "by <key>"
+    #[cfg(unix)]
+    fn fake_gpg(dir: &Path, repo: &Repository) -> Signer {
+        use std::os::unix::fs::PermissionsExt;
+
+        // The key comes after --status-fd=2 -bsau
+        let program = dir.join("fake-gpg");
+        std::fs::write(
+            &program,
+            "#!/bin/sh\ncat > /dev/null\necho '[GNUPG:] SIG_CREATED D' >&2\n\
+             printf -- '-----BEGIN PGP SIGNATURE-----\\nby %s\\n-----END PGP SIGNATURE-----\\n' \"$3\"\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_bool("commit.gpgsign", true).unwrap();
+        config
+            .set_str("gpg.program", program.to_str().unwrap())
+            .unwrap();
+        config.set_str("user.signingkey", "KEY").unwrap();
+        Signer::from_repo(repo).unwrap().unwrap()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_replay_signs_commits_when_gpgsign_is_set() {
+        let (dir, repo) = init_repo();
+        let signer = fake_gpg(dir.path(), &repo);
+
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let feature = commit(&repo, &[root], &[("b", "1")], "feature");
+        let trunk = commit(&repo, &[root], &[("c", "1")], "trunk");
+
+        let feature = repo.find_commit(feature).unwrap();
+        let onto = repo.find_commit(trunk).unwrap();
+        let Replayed::Picked(tip) =
+            replay_commit(&repo, &feature, &onto, &HashSet::new(), Some(&signer)).unwrap()
+        else {
+            panic!("expected the commit to be picked");
+        };
+
+        let (signature, _) = repo.extract_signature(&tip, None).unwrap();
+        assert!(signature.as_str().unwrap().contains("by KEY"));
+        let new = repo.find_commit(tip).unwrap();
+        assert_eq!(new.author().email(), Some("author@example.com"));
+        assert_eq!(new.author().when(), feature.author().when());
+        assert_eq!(new.committer().name(), Some("Committer"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_replay_keeps_messages_in_other_encodings() {
+        let (dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let trunk = commit(&repo, &[root], &[("c", "1")], "trunk");
+        let tree = commit(&repo, &[root], &[("b", "1")], "tree");
+        let tree = repo.find_commit(tree).unwrap().tree_id();
+        // "Café" in ISO-8859-1, which isn't valid UTF-8
+        let mut raw = format!(
+            "tree {}\nparent {}\nauthor Author <author@example.com> 1600000000 +0100\n\
+             committer Author <author@example.com> 1600000000 +0100\nencoding ISO-8859-1\n\n",
+            tree, root
+        )
+        .into_bytes();
+        raw.extend_from_slice(b"Caf\xe9\n");
+        let feature = repo.odb().unwrap().write(ObjectType::Commit, &raw).unwrap();
+        let feature = repo.find_commit(feature).unwrap();
+        let onto = repo.find_commit(trunk).unwrap();
+
+        let replay = |signer: Option<&Signer>| match replay_commit(
+            &repo,
+            &feature,
+            &onto,
+            &HashSet::new(),
+            signer,
+        )
+        .unwrap()
+        {
+            Replayed::Picked(tip) => repo.find_commit(tip).unwrap(),
+            other => panic!("expected the commit to be picked, not {:?}", other),
+        };
+
+        let unsigned = replay(None);
+        assert_eq!(unsigned.message_raw_bytes(), b"Caf\xe9\n");
+        assert_eq!(unsigned.message_encoding(), Some("ISO-8859-1"));
+        assert_eq!(unsigned.author().when(), feature.author().when());
+        assert_eq!(unsigned.committer().name(), Some("Committer"));
+
+        let signer = fake_gpg(dir.path(), &repo);
+        let signed = replay(Some(&signer));
+        assert_eq!(signed.message_raw_bytes(), b"Caf\xe9\n");
+        assert_eq!(signed.message_encoding(), Some("ISO-8859-1"));
+        let (signature, data) = repo.extract_signature(&signed.id(), None).unwrap();
+        assert!(signature.as_str().unwrap().contains("by KEY"));
+        assert!(data.ends_with(b"encoding ISO-8859-1\n\nCaf\xe9\n"));
+    }
+
+    #[test]
+    fn test_replay_skips_already_applied() {
+        let (_dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let first = commit(&repo, &[root], &[("b", "1")], "first");
+        let second = commit(&repo, &[first], &[("c", "1")], "second");
+        // `first` was cherry-picked onto trunk
+        let trunk = commit(&repo, &[root], &[("b", "1")], "first (picked)");
+
+        let applied = upstream_patch_ids(&repo, trunk, second).unwrap();
+        let tip = replay(&repo, &[first, second], trunk, &applied);
+
+        assert_eq!(messages(&repo, trunk, tip), vec!["second"]);
+    }
+
+    #[test]
+    fn test_replay_drops_changes_already_upstream() {
+        let (_dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let feature = commit(&repo, &[root], &[("b", "1")], "feature");
+        // Upstream has the same change mixed into a bigger commit
+        let trunk = commit(&repo, &[root], &[("b", "1"), ("c", "1")], "squashed");
+
+        let tip = replay(&repo, &[feature], trunk, &HashSet::new());
+        assert_eq!(tip, trunk);
+    }
+
+    #[test]
+    fn test_replay_keeps_empty_commits() {
+        let (_dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let empty = commit(&repo, &[root], &[], "empty");
+        let trunk = commit(&repo, &[root], &[("c", "1")], "trunk");
+
+        let tip = replay(&repo, &[empty], trunk, &HashSet::new());
+        assert_eq!(messages(&repo, trunk, tip), vec!["empty"]);
+    }
+
+    #[test]
+    fn test_replay_drops_merge_commits() {
+        let (_dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let left = commit(&repo, &[root], &[("b", "1")], "left");
+        let right = commit(&repo, &[root], &[("c", "1")], "right");
+        let merge = commit(&repo, &[left, right], &[("c", "1")], "merge");
+        let trunk = commit(&repo, &[root], &[("d", "1")], "trunk");
+
+        let commits = commits_between(&repo, root, merge).unwrap();
+        let tip = replay(&repo, &commits, trunk, &HashSet::new());
+
+        let mut replayed = messages(&repo, trunk, tip);
+        replayed.sort();
+        assert_eq!(replayed, vec!["left", "right"]);
+        assert_eq!(file(&repo, tip, "b"), "1");
+        assert_eq!(file(&repo, tip, "c"), "1");
+        assert_eq!(file(&repo, tip, "d"), "1");
+    }
+
+    #[test]
+    fn test_conflict_saves_state_and_continues() {
+        let (dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let first = commit(&repo, &[root], &[("a", "2")], "first");
+        let second = commit(&repo, &[first], &[("b", "1")], "second");
+        let trunk = commit(&repo, &[root], &[("a", "3")], "trunk");
+
+        let path = dir.path().join("autorebase.json");
+        let mut state = rebase_state(&repo, second, &[first, second], trunk);
+
+        let outcome = replay_branches(&repo, &mut state, &path).unwrap();
+        assert!(
+            matches!(outcome, Some(RebaseOutcome::Conflict { ref branch, .. }) if branch == "feature")
+        );
+        assert_eq!(RebaseState::load(&path).unwrap(), Some(state.clone()));
+
+        // The conflict is left in a worktree of its own, not the user's checkout
+        let worktree = Repository::open(conflict_worktree_path(&repo)).unwrap();
+        assert!(worktree.index().unwrap().has_conflicts());
+        assert!(!repo.index().unwrap().has_conflicts());
+        assert_eq!(std::fs::read_to_string(dir.path().join("a")).unwrap(), "2");
+        assert_eq!(repo.head().unwrap().shorthand(), Some("feature"));
+
+        // Unresolved conflicts block --continue
+        assert!(commit_resolution(&repo, &mut state).is_err());
+
+        let workdir = worktree.workdir().unwrap();
+        std::fs::write(workdir.join("a"), "resolved").unwrap();
+        let mut index = worktree.index().unwrap();
+        index.add_path(Path::new("a")).unwrap();
+        index.write().unwrap();
+
+        commit_resolution(&repo, &mut state).unwrap();
+        assert_eq!(replay_branches(&repo, &mut state, &path).unwrap(), None);
+
+        let new_head = Oid::from_str(state.branches[0].new_head.as_deref().unwrap()).unwrap();
+        assert_eq!(messages(&repo, trunk, new_head), vec!["first", "second"]);
+        assert_eq!(file(&repo, new_head, "a"), "resolved");
+        assert_eq!(
+            repo.find_commit(new_head)
+                .unwrap()
+                .parent(0)
+                .unwrap()
+                .author()
+                .name(),
+            Some("Author")
+        );
+    }
+
+    #[test]
+    fn test_abort_removes_conflict_worktree() {
+        let (dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let feature = commit(&repo, &[root], &[("a", "2")], "feature");
+        let trunk = commit(&repo, &[root], &[("a", "3")], "trunk");
+
+        let mut state = rebase_state(&repo, feature, &[feature], trunk);
+        let path = RebaseState::path(&repo);
+        let outcome = replay_branches(&repo, &mut state, &path).unwrap();
+        assert!(outcome.is_some());
+
+        // Aborting from inside the conflict worktree works too
+        let worktree = Repository::open(conflict_worktree_path(&repo)).unwrap();
+        abort_rebase(&worktree).unwrap();
+
+        assert!(!path.exists());
+        assert!(!conflict_worktree_path(&repo).exists());
+        assert_eq!(repo.state(), git2::RepositoryState::Clean);
+        assert_eq!(repo.head().unwrap().shorthand(), Some("feature"));
+        assert_eq!(repo.head().unwrap().target(), Some(feature));
+        assert_eq!(std::fs::read_to_string(dir.path().join("a")).unwrap(), "2");
+    }
+
+    #[tokio::test]
+    async fn test_checkout_follows_its_branch() {
+        let (dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let feature = commit(&repo, &[root], &[("a", "2")], "feature");
+        let trunk = commit(&repo, &[root], &[("c", "1")], "trunk");
+
+        let mut state = rebase_state(&repo, feature, &[feature], trunk);
+        state.no_push = true;
+        let outcome = finish_rebase(&repo, state, true).await.unwrap();
+        assert_eq!(outcome, RebaseOutcome::Done);
+
+        let head = repo.head().unwrap();
+        assert_eq!(head.shorthand(), Some("feature"));
+        let new_head = head.target().unwrap();
+        assert_eq!(messages(&repo, trunk, new_head), vec!["feature"]);
+        assert_eq!(std::fs::read_to_string(dir.path().join("c")).unwrap(), "1");
+        assert!(!has_uncommitted_changes(&repo).unwrap());
+
+        // `gh-stack push` will lease on where the remote branch was
+        assert_eq!(
+            load_leases(&repo).unwrap().get("feature"),
+            Some(&feature.to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_uncommitted_changes_stop_the_checkout_moving() {
+        let (dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let feature = commit(&repo, &[root], &[("a", "2")], "feature");
+        let trunk = commit(&repo, &[root], &[("c", "1")], "trunk");
+
+        let mut state = rebase_state(&repo, feature, &[feature], trunk);
+        state.no_push = true;
+        std::fs::write(dir.path().join("a"), "work in progress").unwrap();
+
+        assert!(finish_rebase(&repo, state, true).await.is_err());
+        assert!(RebaseState::path(&repo).exists());
+        assert_eq!(repo.head().unwrap().target(), Some(feature));
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("a")).unwrap(),
+            "work in progress"
+        );
+    }
+
+    fn branch(name: &str, base: Oid, commits: &[Oid], remote: Oid) -> BranchRebase {
+        BranchRebase {
+            name: name.to_string(),
+            head: commits.last().unwrap_or(&base).to_string(),
+            base: base.to_string(),
+            commits: commits.iter().map(Oid::to_string).collect(),
+            remote_sha: remote.to_string(),
+            new_head: None,
+        }
+    }
+
+    fn stack_state(tip: Oid, branches: Vec<BranchRebase>) -> RebaseState {
+        RebaseState {
+            remote: "origin".to_string(),
+            checkout: None,
+            applied: vec![],
+            branches,
+            tip: tip.to_string(),
+            branch: 0,
+            commit: 0,
+            no_push: false,
+        }
+    }
+
+    #[test]
+    fn test_unchanged_stack_is_left_alone() {
+        let (dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let one = commit(&repo, &[root], &[("b", "1")], "one");
+        let two = commit(&repo, &[one], &[("c", "1")], "two");
+
+        let mut state = stack_state(
+            root,
+            vec![
+                branch("one", root, &[one], one),
+                branch("two", one, &[two], two),
+            ],
+        );
+        let path = dir.path().join("autorebase.json");
+        assert_eq!(replay_branches(&repo, &mut state, &path).unwrap(), None);
+
+        assert_eq!(state.branches[0].new_head, Some(one.to_string()));
+        assert_eq!(state.branches[1].new_head, Some(two.to_string()));
+        assert!(state.branches.iter().all(|b| !b.moved() && !b.needs_push()));
+        assert!(push_plan(&state).is_empty());
+    }
+
+    #[test]
+    fn test_only_branches_that_moved_are_pushed() {
+        let (dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let one = commit(&repo, &[root], &[("b", "1")], "one");
+        let two = commit(&repo, &[one], &[("c", "1")], "two");
+        // A new commit on `two` that hasn't been pushed yet
+        let two_more = commit(&repo, &[two], &[("c", "2")], "two, more");
+        let trunk = commit(&repo, &[root], &[("d", "1")], "trunk");
+
+        let mut state = stack_state(
+            root,
+            vec![
+                branch("one", root, &[one], one),
+                branch("two", one, &[two, two_more], two),
+            ],
+        );
+        let path = dir.path().join("autorebase.json");
+        replay_branches(&repo, &mut state, &path).unwrap();
+
+        let pushes = push_plan(&state);
+        assert_eq!(pushes.len(), 1);
+        assert_eq!(pushes[0].refspec(), format!("{}:refs/heads/two", two_more));
+        assert_eq!(pushes[0].lease(), format!("--force-with-lease=two:{}", two));
+        assert!(!state.branches[1].moved());
+
+        // Once trunk moves, every branch is rebuilt and pushed
+        let mut state = stack_state(
+            trunk,
+            vec![
+                branch("one", root, &[one], one),
+                branch("two", one, &[two], two),
+            ],
+        );
+        replay_branches(&repo, &mut state, &path).unwrap();
+        assert_eq!(push_plan(&state).len(), 2);
+        assert!(state.branches.iter().all(BranchRebase::moved));
+    }
+
+    #[test]
+    fn test_fork_point_uses_merged_parent_head() {
+        let (_dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let parent = commit(&repo, &[root], &[("a", "2")], "parent");
+        let child = commit(&repo, &[parent], &[("b", "1")], "child");
+        // The parent was squash-merged along with a fixup made during review
+        let trunk = commit(&repo, &[root], &[("a", "2"), ("z", "1")], "parent (#1)");
+        repo.branch("child", &repo.find_commit(child).unwrap(), true)
+            .unwrap();
+
+        let parent_pr = pr(1, "parent", "main", true).with_head_sha(&parent.to_string());
+        let child_pr = pr(2, "child", "parent", false);
+        let stack = deps(vec![parent_pr, child_pr.clone()]);
+
+        assert_eq!(
+            fork_point(&repo, "origin", &stack, &child_pr, trunk).unwrap(),
+            parent
+        );
+
+        // Without the merged PR, only the merge base is known
+        let stack = deps(vec![child_pr.clone()]);
+        assert_eq!(
+            fork_point(&repo, "origin", &stack, &child_pr, trunk).unwrap(),
+            root
+        );
+    }
+
+    #[test]
+    fn test_fork_point_uses_parent_branch() {
+        let (_dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let parent = commit(&repo, &[root], &[("a", "2")], "parent");
+        let child = commit(&repo, &[parent], &[("b", "1")], "child");
+        let trunk = commit(&repo, &[root], &[("a", "2"), ("z", "1")], "parent (#1)");
+        repo.branch("child", &repo.find_commit(child).unwrap(), true)
+            .unwrap();
+        repo.reference("refs/remotes/origin/parent", parent, true, "test")
+            .unwrap();
+
+        let child_pr = pr(2, "child", "parent", false);
+        let stack = deps(vec![child_pr.clone()]);
+        assert_eq!(
+            fork_point(&repo, "origin", &stack, &child_pr, trunk).unwrap(),
+            parent
+        );
+    }
+
+    #[test]
+    fn test_fork_point_matches_squash_commit() {
+        let (_dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let first = commit(&repo, &[root], &[("a", "2")], "parent, first");
+        let second = commit(&repo, &[first], &[("b", "1")], "parent, second");
+        let child = commit(&repo, &[second], &[("c", "1")], "child");
+        let squash = commit(&repo, &[root], &[("a", "2"), ("b", "1")], "parent (#1)");
+        let trunk = commit(&repo, &[squash], &[("d", "1")], "later");
+        repo.branch("child", &repo.find_commit(child).unwrap(), true)
+            .unwrap();
+
+        // The parent's branch is gone and nothing records its head
+        let child_pr = pr(2, "child", "parent", false);
+        let stack = deps(vec![child_pr.clone()]);
+        assert_eq!(
+            fork_point(&repo, "origin", &stack, &child_pr, trunk).unwrap(),
+            second
+        );
+    }
+
+    #[test]
+    fn test_landed_base_follows_merged_parents() {
+        let stack = deps(vec![
+            pr(1, "one", "main", true),
+            pr(2, "two", "one", true),
+            pr(3, "three", "two", false),
+        ]);
+        assert_eq!(landed_base(&stack, &stack[2].0), "main");
+        assert_eq!(landed_base(&stack, &pr(4, "four", "three", false)), "three");
+    }
+
+    #[test]
+    fn test_parse_rejections() {
+        let porcelain = "To /tmp/remote.git\n\
+            +\tabc:refs/heads/one\tdef...abc (forced update)\n\
+            !\t123:refs/heads/two\t[rejected] (stale info)\n\
+            =\t456:refs/heads/three\t[up to date]\n\
+            Done\n";
+
+        assert_eq!(
+            parse_rejections(porcelain),
+            vec![RejectedPush {
+                branch: "two".to_string(),
+                reason: "stale info".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_stack_pushes_skips_up_to_date_branches() {
+        let (_dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let one = commit(&repo, &[root], &[("b", "1")], "one");
+        let two = commit(&repo, &[one], &[("c", "1")], "two");
+        repo.branch("one", &repo.find_commit(one).unwrap(), true)
+            .unwrap();
+        repo.branch("two", &repo.find_commit(two).unwrap(), true)
+            .unwrap();
+        repo.reference("refs/remotes/origin/two", root, true, "test")
+            .unwrap();
+
+        let stack = deps(vec![
+            pr(1, "one", "main", false).with_head_sha(&one.to_string()),
+            pr(2, "two", "one", false).with_head_sha(&one.to_string()),
+            pr(3, "missing", "two", false),
+        ]);
+
+        // Leased on the remote-tracking branch, not on what GitHub reports
+        let (pushes, refused) = stack_pushes(&repo, "origin", &stack).unwrap();
+        assert!(refused.is_empty());
+        assert_eq!(
+            pushes,
+            vec![BranchPush {
+                branch: "two".to_string(),
+                sha: two.to_string(),
+                expected: root.to_string(),
+            }]
+        );
+
+        // A --no-push restack's lease wins, until the branch is pushed
+        let mut leases = BTreeMap::new();
+        leases.insert("two".to_string(), "0".repeat(40));
+        save_leases(&repo, &leases).unwrap();
+        let (pushes, _) = stack_pushes(&repo, "origin", &stack).unwrap();
+        assert_eq!(pushes[0].expected, "0".repeat(40));
+
+        clear_leases(&repo, &["two".to_string()]).unwrap();
+        assert!(!leases_path(&repo).exists());
+        let (pushes, _) = stack_pushes(&repo, "origin", &stack).unwrap();
+        assert_eq!(pushes[0].expected, root.to_string());
+    }
+
+    #[test]
+    fn test_stack_pushes_refuses_branches_behind_the_remote() {
+        let (_dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let one = commit(&repo, &[root], &[("b", "1")], "one");
+        let newer = commit(&repo, &[one], &[("b", "2")], "pushed by a teammate");
+        repo.branch("one", &repo.find_commit(one).unwrap(), true)
+            .unwrap();
+        repo.branch("two", &repo.find_commit(one).unwrap(), true)
+            .unwrap();
+        repo.reference("refs/remotes/origin/two", newer, true, "test")
+            .unwrap();
+
+        let stack = deps(vec![
+            // GitHub has the newer commit
+            pr(1, "one", "main", false).with_head_sha(&newer.to_string()),
+            // Only the remote-tracking branch knows about it
+            pr(2, "two", "one", false).with_head_sha(&root.to_string()),
+        ]);
+
+        let (pushes, refused) = stack_pushes(&repo, "origin", &stack).unwrap();
+        assert!(pushes.is_empty());
+        assert_eq!(
+            refused
+                .iter()
+                .map(|r| r.branch.as_str())
+                .collect::<Vec<_>>(),
+            vec!["one", "two"]
+        );
+        assert_eq!(refused[0].reason, "behind the remote");
+    }
+
+    #[tokio::test]
+    async fn test_push_branches_reports_lease_failures() {
+        let (_dir, repo) = init_repo();
+        let remote_dir = tempfile::tempdir().unwrap();
+        let remote = Repository::init_bare(remote_dir.path()).unwrap();
+        repo.remote("origin", remote_dir.path().to_str().unwrap())
+            .unwrap();
+
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let one = commit(&repo, &[root], &[("b", "1")], "one");
+        let two = commit(&repo, &[one], &[("c", "1")], "two");
+        let push = |branch: &str, sha: Oid, expected: &str| BranchPush {
+            branch: branch.to_string(),
+            sha: sha.to_string(),
+            expected: expected.to_string(),
+        };
+
+        // An empty lease means the branch mustn't exist yet
+        let initial = vec![push("one", one, ""), push("two", two, "")];
+        let rejected = push_branches(&repo, "origin", &initial, true)
+            .await
+            .unwrap();
+        assert!(rejected.is_empty());
+
+        // A teammate pushes to `one`
+        let theirs = commit(&remote, &[one], &[("b", "theirs")], "theirs");
+        remote
+            .reference("refs/heads/one", theirs, true, "test")
+            .unwrap();
+
+        let ours_one = commit(&repo, &[root], &[("b", "2")], "one, amended");
+        let ours_two = commit(&repo, &[ours_one], &[("c", "1")], "two");
+        let restacked = vec![
+            push("one", ours_one, &one.to_string()),
+            push("two", ours_two, &two.to_string()),
+        ];
+        let rejected = push_branches(&repo, "origin", &restacked, true)
+            .await
+            .unwrap();
+
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].branch, "one");
+        assert_eq!(remote.refname_to_id("refs/heads/one").unwrap(), theirs);
+        assert_eq!(remote.refname_to_id("refs/heads/two").unwrap(), ours_two);
+    }
+
+    #[test]
+    fn test_rebase_script_after_bottom_pr_merged() {
+        let stack = deps(vec![
+            pr(1, "one", "main", true).with_head_sha("aaa111"),
+            pr(2, "two", "one", false).with_head_sha("bbb222"),
+            pr(3, "three", "two", false).with_head_sha("ccc333"),
+        ]);
+
+        let roots = script_roots(None, "upstream", "main", &stack);
+        assert_eq!(
+            roots,
+            HashMap::from([(
+                2,
+                ScriptRoot {
+                    onto: "main".to_string(),
+                    fork_point: Some("aaa111".to_string()),
+                }
+            )])
+        );
+
+        insta::assert_snapshot!(generate_rebase_script(&stack, "upstream", &roots));
+    }
+
+    #[test]
+    fn test_rebase_script_without_fork_point() {
+        let stack = deps(vec![pr(1, "one", "main", false).with_head_sha("aaa111")]);
+        let script = generate_rebase_script(&stack, "origin", &HashMap::new());
+
+        assert!(script.contains("export TO=\"origin/main\""));
+        assert!(script.contains("export PREBASE=\"$(git merge-base \"$TO\" \"$FROM\")\""));
+    }
+
+    #[test]
+    fn test_script_roots_fall_back_to_trunk() {
+        let (_dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let one = commit(&repo, &[root], &[("b", "1")], "one");
+        repo.branch("one", &repo.find_commit(one).unwrap(), true)
+            .unwrap();
+        repo.reference("refs/remotes/origin/develop", root, true, "test")
+            .unwrap();
+
+        // `gone` was merged and deleted, and its PR isn't part of the stack
+        let stack = deps(vec![pr(2, "one", "gone", false)]);
+        let roots = script_roots(Some(&repo), "origin", "develop", &stack);
+
+        assert_eq!(
+            roots.get(&2),
+            Some(&ScriptRoot {
+                onto: "develop".to_string(),
+                fork_point: Some(root.to_string()),
+            })
+        );
+    }
+
+    /// Objects in the repository's object database
+    fn object_count(repo: &Repository) -> usize {
+        let mut count = 0;
+        repo.odb()
+            .unwrap()
+            .foreach(|_| {
+                count += 1;
+                true
+            })
+            .unwrap();
+        count
+    }
+
+    /// Point local and remote-tracking branches of the stack at `heads`, with HEAD on the last
+    fn push_stack(repo: &Repository, trunk: Oid, heads: &[(&str, Oid)]) {
+        repo.reference("refs/remotes/origin/main", trunk, true, "test")
+            .unwrap();
+        for (name, head) in heads {
+            repo.branch(name, &repo.find_commit(*head).unwrap(), true)
+                .unwrap();
+            repo.reference(
+                &format!("refs/remotes/origin/{}", name),
+                *head,
+                true,
+                "test",
+            )
+            .unwrap();
+        }
+        let (last, _) = heads.last().unwrap();
+        repo.set_head(&format!("refs/heads/{}", last)).unwrap();
+    }
+
+    #[test]
+    fn test_dry_run_predicts_conflicts_without_touching_anything() {
+        let (_dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let trunk = commit(&repo, &[root], &[("a", "main")], "trunk");
+        let part1 = commit(&repo, &[root], &[("a", "part-1")], "part 1");
+        let part2 = commit(&repo, &[part1], &[("b", "1")], "part 2");
+        push_stack(&repo, trunk, &[("part-1", part1), ("part-2", part2)]);
+        let objects = object_count(&repo);
+
+        let stack = deps(vec![
+            pr(1, "part-1", "main", false),
+            pr(2, "part-2", "part-1", false),
+        ]);
+        let planned = dry_run_rebase(&stack, &repo, "origin", None).unwrap();
+
+        assert_eq!(planned[0].onto, "origin/main");
+        assert_eq!(
+            planned[0].commits[0].step,
+            PlannedStep::Conflict(vec!["a".to_string()])
+        );
+        assert_eq!(planned[1].onto, "part-1");
+        assert_eq!(planned[1].commits[0].step, PlannedStep::Pick);
+        assert!(planned.iter().all(|b| b.changes));
+
+        let report = render_dry_run(&planned);
+        assert!(report.contains("CONFLICT"));
+        assert!(report.contains("in a"));
+        assert!(report.contains("1 commit(s) would conflict"));
+
+        // Nothing moved, and nothing was written
+        assert_eq!(repo.head().unwrap().shorthand(), Some("part-2"));
+        assert_eq!(object_count(&repo), objects);
+        assert_eq!(rev_to_commit(&repo, "part-1").unwrap().id(), part1);
+        assert_eq!(rev_to_commit(&repo, "part-2").unwrap().id(), part2);
+        assert!(!RebaseState::path(&repo).exists());
+    }
+
+    #[test]
+    fn test_dry_run_reports_which_branches_change() {
+        let (_dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let part1 = commit(&repo, &[root], &[("a", "2")], "part 1");
+        let part2 = commit(&repo, &[part1], &[("b", "1")], "part 2");
+        push_stack(&repo, root, &[("part-1", part1), ("part-2", part2)]);
+
+        let stack = deps(vec![
+            pr(1, "part-1", "main", false),
+            pr(2, "part-2", "part-1", false),
+        ]);
+        let planned = dry_run_rebase(&stack, &repo, "origin", None).unwrap();
+        assert!(planned.iter().all(|b| !b.changes));
+        assert_eq!(planned[0].commits[0].step, PlannedStep::Keep);
+        assert!(render_dry_run(&planned).contains("0 of 2 branch(es) would be pushed"));
+
+        // Amending part-1 locally moves both branches
+        let amended = commit(&repo, &[root], &[("a", "3")], "part 1, amended");
+        repo.branch("part-1", &repo.find_commit(amended).unwrap(), true)
+            .unwrap();
+        let planned = dry_run_rebase(&stack, &repo, "origin", None).unwrap();
+        assert!(planned.iter().all(|b| b.changes));
+        assert_eq!(planned[1].commits[0].step, PlannedStep::Pick);
+    }
+
+    #[test]
+    fn test_plan_rebase_of_never_pushed_branches() {
+        let (_dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let trunk = commit(&repo, &[root], &[("b", "main")], "trunk");
+        let part1 = commit(&repo, &[root], &[("a", "2")], "part 1");
+        let part2 = commit(&repo, &[part1], &[("c", "1")], "part 2");
+        for (name, at) in [("main", trunk), ("part-1", part1), ("part-2", part2)] {
+            repo.branch(name, &repo.find_commit(at).unwrap(), true)
+                .unwrap();
+        }
+
+        let stack = deps(vec![
+            pr(1, "part-1", "main", false),
+            pr(2, "part-2", "part-1", false),
+        ]);
+        let (state, onto) = plan_rebase(&stack, &repo, "origin", None).unwrap();
+        assert_eq!(onto, "main");
+        assert_eq!(state.branches[0].commits, vec![part1.to_string()]);
+        assert_eq!(state.branches[1].commits, vec![part2.to_string()]);
+        assert!(state.branches.iter().all(|b| b.remote_sha.is_empty()));
+
+        let planned = dry_run_rebase(&stack, &repo, "origin", None).unwrap();
+        assert_eq!(planned[0].onto, "main");
+        assert!(planned.iter().all(|b| b.changes));
+    }
+
+    #[test]
+    fn test_plan_rebase_errors() {
+        let (_dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let part1 = commit(&repo, &[root], &[("a", "2")], "part 1");
+        for (name, at) in [("main", root), ("part-1", part1)] {
+            repo.branch(name, &repo.find_commit(at).unwrap(), true)
+                .unwrap();
+        }
+
+        let landed = deps(vec![pr(1, "part-1", "main", true)]);
+        let err = plan_rebase(&landed, &repo, "origin", None).unwrap_err();
+        assert_eq!(err.to_string(), "No open PRs to restack");
+        assert!(dry_run_rebase(&landed, &repo, "origin", None).is_err());
+
+        let missing = deps(vec![
+            pr(1, "part-1", "main", false),
+            pr(2, "part-2", "part-1", false),
+        ]);
+        let err = plan_rebase(&missing, &repo, "origin", None).unwrap_err();
+        assert_eq!(err.to_string(), "part-2 isn't a local branch");
+    }
+
+    #[test]
+    fn test_fast_forward_branch() {
+        let (_dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let landed = commit(&repo, &[root], &[("b", "1")], "landed");
+        repo.branch("main", &repo.find_commit(root).unwrap(), true)
+            .unwrap();
+        repo.reference("refs/remotes/origin/main", landed, true, "test")
+            .unwrap();
+
+        assert_eq!(
+            fast_forward_branch(&repo, "origin", "main").unwrap(),
+            BranchUpdate::FastForwarded(root.to_string(), landed.to_string())
+        );
+        assert_eq!(rev_to_commit(&repo, "main").unwrap().id(), landed);
+        assert_eq!(
+            fast_forward_branch(&repo, "origin", "main").unwrap(),
+            BranchUpdate::UpToDate
+        );
+
+        // Local commits on trunk are never thrown away
+        let local = commit(&repo, &[landed], &[("c", "1")], "local");
+        repo.branch("main", &repo.find_commit(local).unwrap(), true)
+            .unwrap();
+        assert_eq!(
+            fast_forward_branch(&repo, "origin", "main").unwrap(),
+            BranchUpdate::Diverged
+        );
+        assert_eq!(rev_to_commit(&repo, "main").unwrap().id(), local);
+
+        assert_eq!(
+            fast_forward_branch(&repo, "origin", "develop").unwrap(),
+            BranchUpdate::Missing
+        );
+    }
+}
@@ -1,22 +1,26 @@
-use petgraph::visit::Bfs;
+use petgraph::algo::tarjan_scc;
+use petgraph::graph::NodeIndex;
 use petgraph::visit::EdgeRef;
 use petgraph::{Direction, Graph};
 use std::collections::HashMap;
+use std::fmt;
 use std::rc::Rc;
 
-use crate::api::PullRequest;
+use crate::api::{PullRequest, PullRequestStatus};
+use crate::identifier::is_trunk_branch;
 
 pub type FlatDep = Vec<(Rc<PullRequest>, Option<Rc<PullRequest>>)>;
 
 pub fn build(prs: &[Rc<PullRequest>]) -> Graph<Rc<PullRequest>, usize> {
     let mut tree = Graph::<Rc<PullRequest>, usize>::new();
-    let heads = prs.iter().map(|pr| pr.head());
+    // Branch names only link PRs within the same repository
+    let heads = prs.iter().map(|pr| (pr.repository(), pr.head()));
     let handles: Vec<_> = prs.iter().map(|pr| tree.add_node(pr.clone())).collect();
     let handles_by_head: HashMap<_, _> = heads.zip(handles.iter()).collect();
 
     for (i, pr) in prs.iter().enumerate() {
         let head_handle = handles[i];
-        if let Some(&base_handle) = handles_by_head.get(pr.base()) {
+        if let Some(&base_handle) = handles_by_head.get(&(pr.repository(), pr.base())) {
             tree.add_edge(*base_handle, head_handle, 1);
         }
     }
@@ -24,28 +28,253 @@ pub fn build(prs: &[Rc<PullRequest>]) -> Graph<Rc<PullRequest>, usize> {
     tree
 }
 
+/// Split a stack into one stack per repository, keeping its order
+///
+/// Repositories are listed in the order their first PR appears.
+pub fn split_by_repository(stack: &FlatDep) -> Vec<(String, FlatDep)> {
+    let mut groups: Vec<(String, FlatDep)> = Vec::new();
+    for (pr, parent) in stack {
+        let repository = pr.repository();
+        match groups.iter_mut().find(|(r, _)| *r == repository) {
+            Some((_, group)) => group.push((pr.clone(), parent.clone())),
+            None => groups.push((repository, vec![(pr.clone(), parent.clone())])),
+        }
+    }
+    groups
+}
+
+/// The canonical base-to-top order of the graph's nodes
+///
+/// Every command that walks a stack uses this order. It is a depth-first walk
+/// from the roots, so each branch of a fork stays contiguous and every PR
+/// comes after the PR it is based on. Roots and siblings are visited by PR
+/// number, which makes the order independent of how the PRs were fetched.
+/// Nodes that are only reachable through a cycle come last, by PR number.
+pub fn topological_order(graph: &Graph<Rc<PullRequest>, usize>) -> Vec<NodeIndex> {
+    let by_number = |nodes: &mut Vec<NodeIndex>| nodes.sort_by_key(|node| graph[*node].number());
+
+    let mut roots: Vec<NodeIndex> = graph.externals(Direction::Incoming).collect();
+    by_number(&mut roots);
+
+    let mut visited = vec![false; graph.node_count()];
+    let mut out = Vec::with_capacity(graph.node_count());
+
+    // Reversed so the lowest number is popped first
+    let mut pending: Vec<NodeIndex> = roots.into_iter().rev().collect();
+    while let Some(node) = pending.pop() {
+        if visited[node.index()] {
+            continue;
+        }
+        visited[node.index()] = true;
+        out.push(node);
+
+        let mut children: Vec<NodeIndex> = graph
+            .neighbors_directed(node, Direction::Outgoing)
+            .collect();
+        by_number(&mut children);
+        pending.extend(children.into_iter().rev());
+    }
+
+    let mut leftovers: Vec<NodeIndex> = graph
+        .node_indices()
+        .filter(|node| !visited[node.index()])
+        .collect();
+    by_number(&mut leftovers);
+    out.extend(leftovers);
+
+    out
+}
+
 /// Return a flattened list of graph nodes as tuples; each tuple is `(node, node's parent [if exists])`.
-/// TODO: Panic if this isn't a single flat list of dependencies
+///
+/// Nodes are in canonical order (see `topological_order`). This doesn't
+/// check that the graph is a single flat list of dependencies; use
+/// `validate` for that.
 pub fn log(graph: &Graph<Rc<PullRequest>, usize>) -> FlatDep {
-    let roots: Vec<_> = graph.externals(Direction::Incoming).collect();
-    let mut out = Vec::new();
+    topological_order(graph)
+        .into_iter()
+        .map(|node| {
+            let parent = graph
+                .edges_directed(node, Direction::Incoming)
+                .next()
+                .map(|edge| graph[edge.source()].clone());
+            (graph[node].clone(), parent)
+        })
+        .collect()
+}
 
-    for root in roots {
-        let mut bfs = Bfs::new(&graph, root);
-        while let Some(node) = bfs.next(&graph) {
-            let parent = graph.edges_directed(node, Direction::Incoming).next();
-            let node: Rc<PullRequest> = graph[node].clone();
-
-            match parent {
-                Some(parent) => out.push((node, Some(graph[parent.source()].clone()))),
-                None => out.push((node, None)),
+/// Put an existing stack into canonical order, re-deriving parents from bases
+pub fn reorder(stack: &FlatDep) -> FlatDep {
+    let prs: Vec<Rc<PullRequest>> = stack.iter().map(|(pr, _)| pr.clone()).collect();
+    log(&build(&prs))
+}
+
+/// Sort PRs into canonical order (see `topological_order`)
+pub fn sort_prs(prs: Vec<PullRequest>) -> Vec<PullRequest> {
+    let prs: Vec<Rc<PullRequest>> = prs.into_iter().map(Rc::new).collect();
+    let order = topological_order(&build(&prs));
+
+    // `build` adds nodes in input order, so node indices index into `prs`
+    order
+        .into_iter()
+        .map(|node| prs[node.index()].as_ref().clone())
+        .collect()
+}
+
+/// Something wrong with the shape of a stack
+#[derive(Debug, Clone, PartialEq)]
+pub enum StackProblem {
+    /// PRs whose bases form a loop
+    Cycle { prs: Vec<usize> },
+    /// More than one PR sits directly on trunk
+    MultipleRoots { roots: Vec<usize> },
+    /// More than one open PR is based on the same PR
+    Fork { parent: usize, children: Vec<usize> },
+    /// An open PR's base has no PR and isn't trunk
+    Orphaned { pr: usize, base: String },
+    /// An open PR's base belongs to a closed or merged PR
+    BasedOnClosed { pr: usize, parent: usize },
+}
+
+impl StackProblem {
+    /// A suggestion for fixing the problem
+    pub fn hint(&self) -> String {
+        match self {
+            StackProblem::Cycle { prs } => format!(
+                "Change the base of one of {} to trunk to break the loop",
+                format_numbers(prs)
+            ),
+            StackProblem::MultipleRoots { .. } => {
+                "These look like separate stacks; give each its own identifier, or rebase one onto the other"
+                    .to_string()
+            }
+            StackProblem::Fork { children, .. } => format!(
+                "Restack one of {} onto another, or land one path at a time with `land --path`",
+                format_numbers(children)
+            ),
+            StackProblem::Orphaned { pr, base } => format!(
+                "Retarget #{} onto trunk, or open a PR for '{}'",
+                pr, base
+            ),
+            StackProblem::BasedOnClosed { pr, parent } => format!(
+                "Retarget #{} onto trunk (or onto whatever replaced #{}) and restack it",
+                pr, parent
+            ),
+        }
+    }
+}
+
+impl fmt::Display for StackProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StackProblem::Cycle { prs } => {
+                write!(f, "Cycle: the bases of {} form a loop", format_numbers(prs))
             }
+            StackProblem::MultipleRoots { roots } => write!(
+                f,
+                "Multiple roots: {} are all based on trunk",
+                format_numbers(roots)
+            ),
+            StackProblem::Fork { parent, children } => write!(
+                f,
+                "Fork: {} are all based on #{}",
+                format_numbers(children),
+                parent
+            ),
+            StackProblem::Orphaned { pr, base } => write!(
+                f,
+                "Orphaned: #{} is based on '{}', which has no PR and isn't trunk",
+                pr, base
+            ),
+            StackProblem::BasedOnClosed { pr, parent } => write!(
+                f,
+                "Based on closed PR: #{} is based on #{}, which is no longer open",
+                pr, parent
+            ),
         }
     }
+}
 
-    out.sort_by_key(|(dep, _)| dep.state().clone());
+fn format_numbers(numbers: &[usize]) -> String {
+    numbers
+        .iter()
+        .map(|n| format!("#{}", n))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
 
-    out
+fn is_open(pr: &PullRequest) -> bool {
+    pr.state() == &PullRequestStatus::Open && !pr.is_merged()
+}
+
+/// Check that the graph forms a single, well-formed stack
+///
+/// `trunk` is the configured trunk branch, if any; the usual trunk names are
+/// always accepted. Returns every problem found, in a stable order.
+pub fn validate(graph: &Graph<Rc<PullRequest>, usize>, trunk: Option<&str>) -> Vec<StackProblem> {
+    let mut problems = Vec::new();
+    let number = |node: NodeIndex| graph[node].number();
+
+    for component in tarjan_scc(graph) {
+        let looped = component.len() > 1 || graph.contains_edge(component[0], component[0]);
+        if looped {
+            let mut prs: Vec<usize> = component.into_iter().map(number).collect();
+            prs.sort_unstable();
+            problems.push(StackProblem::Cycle { prs });
+        }
+    }
+
+    let mut roots: Vec<NodeIndex> = graph.externals(Direction::Incoming).collect();
+    roots.sort_by_key(|node| number(*node));
+
+    // Landed and closed roots are the part of the stack that's done, not
+    // another stack
+    let mut trunk_roots = Vec::new();
+    for root in roots {
+        let pr = &graph[root];
+        if !is_open(pr) {
+            continue;
+        }
+        if is_trunk_branch(pr.base(), trunk) {
+            trunk_roots.push(pr.number());
+        } else {
+            problems.push(StackProblem::Orphaned {
+                pr: pr.number(),
+                base: pr.base().to_string(),
+            });
+        }
+    }
+    if trunk_roots.len() > 1 {
+        problems.push(StackProblem::MultipleRoots { roots: trunk_roots });
+    }
+
+    let mut nodes: Vec<NodeIndex> = graph.node_indices().collect();
+    nodes.sort_by_key(|node| number(*node));
+
+    for node in nodes {
+        let mut open_children: Vec<usize> = graph
+            .neighbors_directed(node, Direction::Outgoing)
+            .filter(|child| is_open(&graph[*child]))
+            .map(number)
+            .collect();
+        open_children.sort_unstable();
+
+        if !is_open(&graph[node]) {
+            for child in open_children {
+                problems.push(StackProblem::BasedOnClosed {
+                    pr: child,
+                    parent: number(node),
+                });
+            }
+        } else if open_children.len() > 1 {
+            problems.push(StackProblem::Fork {
+                parent: number(node),
+                children: open_children,
+            });
+        }
+    }
+
+    problems
 }
 
 #[cfg(test)]
@@ -66,6 +295,69 @@ mod tests {
         ))
     }
 
+    #[test]
+    fn test_build_links_only_within_repository() {
+        let service = make_pr(1, "feature-1", "main");
+        let client = Rc::new(
+            PullRequest::new_for_test(
+                1,
+                "feature-2",
+                "feature-1",
+                "PR #1",
+                PullRequestStatus::Open,
+                false,
+                None,
+                vec![],
+            )
+            .in_repository("test/client"),
+        );
+
+        let graph = build(&[service, client]);
+        assert_eq!(graph.edge_count(), 0);
+    }
+
+    #[test]
+    fn test_split_by_repository() {
+        let in_repo = |number: usize, head: &str, base: &str, repo: &str| {
+            Rc::new(
+                PullRequest::new_for_test(
+                    number,
+                    head,
+                    base,
+                    &format!("PR #{}", number),
+                    PullRequestStatus::Open,
+                    false,
+                    None,
+                    vec![],
+                )
+                .in_repository(repo),
+            )
+        };
+        let prs = vec![
+            in_repo(1, "feature-1", "main", "org/service"),
+            in_repo(2, "feature-2", "feature-1", "org/service"),
+            in_repo(7, "feature-1", "main", "org/client"),
+        ];
+
+        let stack = log(&build(&prs));
+        let groups = split_by_repository(&stack);
+
+        let summary: Vec<(&str, Vec<usize>)> = groups
+            .iter()
+            .map(|(repo, group)| {
+                (
+                    repo.as_str(),
+                    group.iter().map(|(pr, _)| pr.number()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![("org/service", vec![1, 2]), ("org/client", vec![7])]
+        );
+        assert!(groups[1].1[0].1.is_none());
+    }
+
     #[test]
     fn test_build_empty_graph() {
         let prs: Vec<Rc<PullRequest>> = vec![];
@@ -145,34 +437,254 @@ mod tests {
     }
 
     #[test]
-    fn test_log_sorts_by_state() {
-        let open_pr = Rc::new(PullRequest::new_for_test(
-            1,
+    fn test_log_puts_closed_base_first() {
+        let closed_pr = Rc::new(PullRequest::new_for_test(
+            2,
             "feature-1",
             "main",
-            "Open PR",
-            PullRequestStatus::Open,
+            "Closed PR",
+            PullRequestStatus::Closed,
             false,
             None,
             vec![],
         ));
-        let closed_pr = Rc::new(PullRequest::new_for_test(
-            2,
-            "feature-2",
-            "other",
-            "Closed PR",
+        let open_pr = make_pr(1, "feature-2", "feature-1");
+
+        let prs = vec![open_pr, closed_pr];
+        let graph = build(&prs);
+        let flat = log(&graph);
+
+        // Base-to-top, whatever the state
+        assert_eq!(flat[0].0.number(), 2);
+        assert_eq!(flat[1].0.number(), 1);
+        assert_eq!(flat[1].1.as_ref().map(|p| p.number()), Some(2));
+    }
+
+    #[test]
+    fn test_log_is_independent_of_input_order() {
+        let prs = vec![
+            make_pr(1, "feature-1", "main"),
+            make_pr(2, "feature-2", "feature-1"),
+            make_pr(3, "feature-3", "feature-2"),
+            make_pr(4, "other-1", "main"),
+        ];
+        let mut reversed = prs.clone();
+        reversed.reverse();
+
+        let numbers = |prs: &[Rc<PullRequest>]| -> Vec<usize> {
+            log(&build(prs)).iter().map(|(pr, _)| pr.number()).collect()
+        };
+
+        assert_eq!(numbers(&prs), vec![1, 2, 3, 4]);
+        assert_eq!(numbers(&reversed), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_log_keeps_fork_branches_contiguous() {
+        let prs = vec![
+            make_pr(5, "feature-2b", "feature-1"),
+            make_pr(3, "feature-3a", "feature-2a"),
+            make_pr(2, "feature-2a", "feature-1"),
+            make_pr(1, "feature-1", "main"),
+        ];
+
+        let numbers: Vec<usize> = log(&build(&prs))
+            .iter()
+            .map(|(pr, _)| pr.number())
+            .collect();
+
+        assert_eq!(numbers, vec![1, 2, 3, 5]);
+    }
+
+    #[test]
+    fn test_log_includes_cycles_last() {
+        let prs = vec![
+            make_pr(3, "b", "a"),
+            make_pr(2, "a", "b"),
+            make_pr(1, "feature-1", "main"),
+        ];
+
+        let numbers: Vec<usize> = log(&build(&prs))
+            .iter()
+            .map(|(pr, _)| pr.number())
+            .collect();
+
+        assert_eq!(numbers, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_reorder() {
+        let pr1 = make_pr(1, "feature-1", "main");
+        let pr2 = make_pr(2, "feature-2", "feature-1");
+        let stack: FlatDep = vec![(pr2, None), (pr1, None)];
+
+        let ordered = reorder(&stack);
+
+        assert_eq!(ordered[0].0.number(), 1);
+        assert_eq!(ordered[1].0.number(), 2);
+        assert_eq!(ordered[1].1.as_ref().map(|p| p.number()), Some(1));
+    }
+
+    #[test]
+    fn test_sort_prs_linear() {
+        let pr1 = make_pr(1, "feature-1", "main").as_ref().clone();
+        let pr2 = make_pr(2, "feature-2", "feature-1").as_ref().clone();
+        let pr3 = make_pr(3, "feature-3", "feature-2").as_ref().clone();
+
+        // Give them in wrong order
+        let sorted = sort_prs(vec![pr3, pr1, pr2]);
+
+        assert_eq!(sorted.len(), 3);
+        assert_eq!(sorted[0].number(), 1); // base: main
+        assert_eq!(sorted[1].number(), 2); // base: feature-1
+        assert_eq!(sorted[2].number(), 3); // base: feature-2
+    }
+
+    #[test]
+    fn test_sort_prs_single() {
+        let pr = make_pr(1, "feature", "main").as_ref().clone();
+
+        let sorted = sort_prs(vec![pr]);
+        assert_eq!(sorted.len(), 1);
+        assert_eq!(sorted[0].number(), 1);
+    }
+
+    #[test]
+    fn test_sort_prs_empty() {
+        assert!(sort_prs(vec![]).is_empty());
+    }
+
+    fn make_closed_pr(number: usize, head: &str, base: &str) -> Rc<PullRequest> {
+        Rc::new(PullRequest::new_for_test(
+            number,
+            head,
+            base,
+            &format!("PR #{}", number),
             PullRequestStatus::Closed,
             false,
             None,
             vec![],
+        ))
+    }
+
+    #[test]
+    fn test_validate_linear_stack() {
+        let prs = vec![
+            make_pr(1, "feature-1", "main"),
+            make_pr(2, "feature-2", "feature-1"),
+            make_pr(3, "feature-3", "feature-2"),
+        ];
+        assert!(validate(&build(&prs), None).is_empty());
+    }
+
+    #[test]
+    fn test_validate_cycle() {
+        let prs = vec![
+            make_pr(1, "feature-1", "main"),
+            make_pr(2, "feature-2", "feature-3"),
+            make_pr(3, "feature-3", "feature-2"),
+        ];
+        assert_eq!(
+            validate(&build(&prs), None),
+            vec![StackProblem::Cycle { prs: vec![2, 3] }]
+        );
+    }
+
+    #[test]
+    fn test_validate_multiple_roots() {
+        let prs = vec![
+            make_pr(1, "feature-1", "main"),
+            make_pr(2, "other-1", "main"),
+        ];
+        assert_eq!(
+            validate(&build(&prs), None),
+            vec![StackProblem::MultipleRoots { roots: vec![1, 2] }]
+        );
+    }
+
+    #[test]
+    fn test_validate_half_landed_stack() {
+        // #1 was merged into main and #2 retargeted onto main
+        let merged = Rc::new(PullRequest::new_for_test(
+            1,
+            "feature-1",
+            "main",
+            "PR #1",
+            PullRequestStatus::Closed,
+            false,
+            Some("2024-01-01T00:00:00Z".to_string()),
+            vec![],
         ));
+        let prs = vec![
+            merged,
+            make_pr(2, "feature-2", "main"),
+            make_pr(3, "feature-3", "feature-2"),
+        ];
+        assert!(validate(&build(&prs), None).is_empty());
+    }
 
-        let prs = vec![closed_pr, open_pr];
-        let graph = build(&prs);
-        let flat = log(&graph);
+    #[test]
+    fn test_validate_fork() {
+        let prs = vec![
+            make_pr(1, "feature-1", "main"),
+            make_pr(2, "feature-2a", "feature-1"),
+            make_pr(3, "feature-2b", "feature-1"),
+        ];
+        assert_eq!(
+            validate(&build(&prs), None),
+            vec![StackProblem::Fork {
+                parent: 1,
+                children: vec![2, 3]
+            }]
+        );
+    }
+
+    #[test]
+    fn test_validate_orphaned() {
+        let prs = vec![
+            make_pr(1, "feature-2", "feature-1"),
+            make_pr(2, "feature-3", "feature-2"),
+        ];
+        assert_eq!(
+            validate(&build(&prs), None),
+            vec![StackProblem::Orphaned {
+                pr: 1,
+                base: "feature-1".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_validate_respects_configured_trunk() {
+        let prs = vec![make_pr(1, "feature-1", "develop")];
+        assert!(validate(&build(&prs), Some("develop")).is_empty());
+    }
+
+    #[test]
+    fn test_validate_based_on_closed() {
+        let prs = vec![
+            make_closed_pr(1, "feature-1", "main"),
+            make_pr(2, "feature-2", "feature-1"),
+        ];
+        assert_eq!(
+            validate(&build(&prs), None),
+            vec![StackProblem::BasedOnClosed { pr: 2, parent: 1 }]
+        );
+    }
 
-        // Open PRs should come before Closed PRs after sorting
-        assert_eq!(flat[0].0.number(), 1); // Open PR first
-        assert_eq!(flat[1].0.number(), 2); // Closed PR second
+    #[test]
+    fn test_stack_problem_display_and_hint() {
+        let problem = StackProblem::Orphaned {
+            pr: 4,
+            base: "gone".to_string(),
+        };
+        assert_eq!(
+            problem.to_string(),
+            "Orphaned: #4 is based on 'gone', which has no PR and isn't trunk"
+        );
+        assert_eq!(
+            problem.hint(),
+            "Retarget #4 onto trunk, or open a PR for 'gone'"
+        );
     }
 }
@@ -22,13 +22,16 @@ pub struct StackSummary {
     pub pr_numbers: Vec<usize>,
     /// First part of the root PR's title
     pub title_snippet: String,
+    /// Branch the stack is rooted on (the root PR's base)
+    pub trunk: String,
 }
 
 impl StackSummary {
     /// Create a summary from a list of PRs
     ///
-    /// PRs should be sorted bottom-to-top (root first)
-    pub fn from_prs(prs: &[PullRequest], _trunk: &str) -> Self {
+    /// PRs should be sorted bottom-to-top (root first). `trunk` is used as
+    /// the stack's trunk when there are no PRs.
+    pub fn from_prs(prs: &[PullRequest], trunk: &str) -> Self {
         let root_branch = prs
             .first()
             .map(|pr| pr.head().to_string())
@@ -48,11 +51,17 @@ impl StackSummary {
             })
             .unwrap_or_default();
 
+        let trunk = prs
+            .first()
+            .map(|pr| pr.base().to_string())
+            .unwrap_or_else(|| trunk.to_string());
+
         StackSummary {
             root_branch,
             pr_count: prs.len(),
             pr_numbers,
             title_snippet,
+            trunk,
         }
     }
 
@@ -66,15 +75,67 @@ impl StackSummary {
             .join(", ");
 
         format!(
-            "{} ({} PR{}): {}",
+            "{} ({} PR{}) on {}: {}",
             self.root_branch,
             self.pr_count,
             if self.pr_count == 1 { "" } else { "s" },
+            self.trunk,
             prs
         )
     }
 }
 
+/// Base branches that stacks may be rooted on
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trunks {
+    /// Branch names or patterns where `*` matches any run of characters,
+    /// e.g. `release/*`
+    pub patterns: Vec<String>,
+    /// Also treat any base branch that has no PR of its own as a trunk
+    pub any_base: bool,
+}
+
+impl Trunks {
+    /// A single trunk branch
+    pub fn new(trunk: &str) -> Self {
+        Trunks {
+            patterns: vec![trunk.to_string()],
+            any_base: false,
+        }
+    }
+
+    /// Whether `branch` matches one of the trunk patterns
+    pub fn matches(&self, branch: &str) -> bool {
+        self.patterns.iter().any(|p| glob_match(p, branch))
+    }
+}
+
+/// Match `text` against a pattern where `*` matches any run of characters
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !text.starts_with(first) || text.len() < first.len() + last.len() {
+        return false;
+    }
+    if !text.ends_with(last) {
+        return false;
+    }
+
+    // Middle parts must appear in order between the prefix and suffix
+    let mut rest = &text[first.len()..text.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
 /// Check if a branch name is a trunk branch
 ///
 /// Returns true if the branch matches the configured trunk or any common trunk name.
@@ -323,6 +384,7 @@ mod tests {
             pr_count: 2,
             pr_numbers: vec![42, 43],
             title_snippet: "Add feature".to_string(),
+            trunk: "release/1.2".to_string(),
         };
 
         let display = summary.display();
@@ -330,6 +392,7 @@ mod tests {
         assert!(display.contains("2 PRs"));
         assert!(display.contains("#42"));
         assert!(display.contains("#43"));
+        assert!(display.contains("on release/1.2"));
     }
 
     #[test]
@@ -339,6 +402,7 @@ mod tests {
             pr_count: 1,
             pr_numbers: vec![99],
             title_snippet: "Single PR".to_string(),
+            trunk: "main".to_string(),
         };
 
         let display = summary.display();
@@ -352,5 +416,50 @@ mod tests {
         assert!(summary.root_branch.is_empty());
         assert_eq!(summary.pr_count, 0);
         assert!(summary.pr_numbers.is_empty());
+        assert_eq!(summary.trunk, "main");
+    }
+
+    #[test]
+    fn test_stack_summary_labels_root_base_as_trunk() {
+        use crate::api::PullRequestStatus;
+
+        let pr = PullRequest::new_for_test(
+            1,
+            "fix/backport",
+            "release/1.2",
+            "Backport fix",
+            PullRequestStatus::Open,
+            false,
+            None,
+            vec![],
+        );
+
+        let summary = StackSummary::from_prs(&[pr], "main");
+        assert_eq!(summary.trunk, "release/1.2");
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("main", "main"));
+        assert!(!glob_match("main", "main2"));
+        assert!(glob_match("release/*", "release/1.2"));
+        assert!(!glob_match("release/*", "feature/release"));
+        assert!(glob_match("*-stable", "1.x-stable"));
+        assert!(glob_match("rel*/v*", "release/v2"));
+        assert!(!glob_match("rel*/v*", "release/2"));
+        assert!(glob_match("*", "anything"));
+    }
+
+    #[test]
+    fn test_trunks_matches_any_pattern() {
+        let trunks = Trunks {
+            patterns: vec!["main".to_string(), "release/*".to_string()],
+            any_base: false,
+        };
+
+        assert!(trunks.matches("main"));
+        assert!(trunks.matches("release/2024.1"));
+        assert!(!trunks.matches("feat/part-1"));
+        assert!(Trunks::new("develop").matches("develop"));
     }
 }
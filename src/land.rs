@@ -5,13 +5,21 @@
 //! 2. Update that PR's base to the target branch
 //! 3. Squash-merge that single PR (contains all commits from the stack)
 //! 4. Close all PRs below it with a comment linking to the merged PR
+//!
+//! Each completed step is recorded in a journal (`.git/gh-stack/land.json`)
+//! so an interrupted land can be resumed or aborted.
 
+use git2::Repository;
+use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 use crate::api::PullRequest;
-use crate::graph::FlatDep;
+use crate::graph::{self, FlatDep};
+use crate::identifier::is_trunk_branch;
+use crate::state;
 use crate::Credentials;
 
 /// Represents a plan for landing a stack of PRs
@@ -21,6 +29,9 @@ pub struct LandPlan {
     pub top_pr: Rc<PullRequest>,
     /// PRs below top that will be closed after merge
     pub prs_to_close: Vec<Rc<PullRequest>>,
+    /// PRs off the landed path whose base is a PR being closed; these are
+    /// retargeted onto the target branch so their work isn't stranded
+    pub retarget: Vec<Rc<PullRequest>>,
     /// Target branch to merge into (e.g., "main" or "master")
     pub target_branch: String,
     /// Repository in "owner/repo" format
@@ -34,10 +45,128 @@ pub struct LandResult {
     pub merged_pr: Rc<PullRequest>,
     /// PRs that were closed
     pub closed_prs: Vec<Rc<PullRequest>>,
+    /// SHA of the squash commit on the target branch
+    pub merge_sha: String,
     /// URL of the merged PR
     pub merge_url: String,
 }
 
+/// How land reports its progress
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable progress lines
+    Text,
+    /// One JSON object per line (see `LandEvent`)
+    Json,
+}
+
+/// A PR as it appears in machine-readable output
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PrSummary {
+    pub number: usize,
+    pub title: String,
+    pub head: String,
+    pub base: String,
+    pub url: String,
+}
+
+impl PrSummary {
+    pub fn from_pr(pr: &PullRequest) -> Self {
+        PrSummary {
+            number: pr.number(),
+            title: pr.raw_title().to_string(),
+            head: pr.head().to_string(),
+            base: pr.base().to_string(),
+            url: pr.html_url(),
+        }
+    }
+}
+
+/// A machine-readable land event, printed as a single JSON line with `--json`
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum LandEvent {
+    /// The plan about to be executed (or previewed with `--dry-run`)
+    Plan {
+        repository: String,
+        target_branch: String,
+        top_pr: PrSummary,
+        prs_to_close: Vec<PrSummary>,
+        retarget: Vec<PrSummary>,
+        remaining: Vec<PrSummary>,
+    },
+    /// A step that just completed
+    Step {
+        #[serde(flatten)]
+        step: LandStep,
+    },
+    /// The stack landed
+    Result {
+        merged_pr: usize,
+        closed_prs: Vec<usize>,
+        merge_sha: String,
+        merge_url: String,
+    },
+    /// An interrupted land was aborted
+    Aborted {
+        top_pr: usize,
+        base_restored: bool,
+        unclosed_prs: Vec<usize>,
+    },
+    /// Post-land cleanup finished
+    Cleanup {
+        retargeted: Vec<usize>,
+        deleted_remote: Vec<String>,
+        deleted_local: Vec<String>,
+        skipped: Vec<SkippedBranch>,
+    },
+    /// Land failed; `code` is also the process exit code
+    Error {
+        code: i32,
+        kind: String,
+        message: String,
+    },
+}
+
+impl LandEvent {
+    /// Build the plan event
+    pub fn plan(plan: &LandPlan, remaining_prs: &[Rc<PullRequest>]) -> Self {
+        LandEvent::Plan {
+            repository: plan.repository.clone(),
+            target_branch: plan.target_branch.clone(),
+            top_pr: PrSummary::from_pr(&plan.top_pr),
+            prs_to_close: plan
+                .prs_to_close
+                .iter()
+                .map(|pr| PrSummary::from_pr(pr))
+                .collect(),
+            retarget: plan
+                .retarget
+                .iter()
+                .map(|pr| PrSummary::from_pr(pr))
+                .collect(),
+            remaining: remaining_prs
+                .iter()
+                .map(|pr| PrSummary::from_pr(pr))
+                .collect(),
+        }
+    }
+
+    /// Build the error event for a land error
+    pub fn error(error: &LandError) -> Self {
+        LandEvent::Error {
+            code: error.exit_code(),
+            kind: error.kind().to_string(),
+            message: error.to_string(),
+        }
+    }
+
+    /// Print the event as a single JSON line
+    pub fn emit(&self) {
+        println!("{}", serde_json::to_string(self).unwrap());
+    }
+}
+
 /// Errors that can occur during landing
 #[derive(Debug)]
 pub enum LandError {
@@ -51,6 +180,27 @@ pub enum LandError {
     ApprovalRequired { pr_number: usize },
     /// API call failed
     ApiError { message: String },
+    /// A previous land was interrupted and must be resumed or aborted first
+    LandInProgress { top_pr: usize },
+    /// There is no interrupted land to resume or abort
+    NoLandInProgress,
+    /// Reading or writing the land journal failed
+    JournalError { message: String },
+    /// The requested `--through` target is not an open PR in the stack
+    TargetNotInStack { target: String },
+    /// The requested `--through` target sits above a PR that blocks landing
+    AboveApprovalBoundary {
+        target_pr: usize,
+        blocking_pr: usize,
+        reason: String,
+    },
+    /// The stack forks and no path was chosen with `--path`
+    AmbiguousStack {
+        /// Where the stack forks ("PR #N" or "branch 'main'")
+        fork: String,
+        /// Head branches of the stack's tips, one per path
+        heads: Vec<String>,
+    },
 }
 
 impl fmt::Display for LandError {
@@ -71,18 +221,111 @@ impl fmt::Display for LandError {
                 write!(f, "PR #{} requires approval", pr_number)
             }
             LandError::ApiError { message } => write!(f, "API error: {}", message),
+            LandError::LandInProgress { top_pr } => {
+                write!(f, "A land of PR #{} is already in progress", top_pr)
+            }
+            LandError::NoLandInProgress => write!(f, "No land in progress"),
+            LandError::JournalError { message } => write!(f, "Land journal error: {}", message),
+            LandError::TargetNotInStack { target } => {
+                write!(f, "{} is not an open PR in this stack", target)
+            }
+            LandError::AboveApprovalBoundary {
+                target_pr,
+                blocking_pr,
+                reason,
+            } => write!(
+                f,
+                "PR #{} is above the approval boundary: PR #{} below it is {}",
+                target_pr, blocking_pr, reason
+            ),
+            LandError::AmbiguousStack { fork, heads } => write!(
+                f,
+                "The stack forks at {}; choose a path to land ({})",
+                fork,
+                heads.join(", ")
+            ),
         }
     }
 }
 
 impl Error for LandError {}
 
+impl LandError {
+    /// Process exit code for this error
+    ///
+    /// These are part of the CLI contract (see docs/land.md) so automation can
+    /// tell failures apart. Don't renumber them.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            LandError::NoPRsInStack => 3,
+            LandError::NoPRsMergeable { .. } => 4,
+            LandError::DraftBlocking { .. } => 5,
+            LandError::ApprovalRequired { .. } => 6,
+            LandError::AboveApprovalBoundary { .. } => 7,
+            LandError::TargetNotInStack { .. } => 8,
+            LandError::AmbiguousStack { .. } => 9,
+            LandError::ApiError { .. } => 10,
+            LandError::LandInProgress { .. } => 11,
+            LandError::NoLandInProgress => 12,
+            LandError::JournalError { .. } => 13,
+        }
+    }
+
+    /// Stable machine-readable name for this error
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LandError::NoPRsInStack => "no_prs_in_stack",
+            LandError::NoPRsMergeable { .. } => "no_prs_mergeable",
+            LandError::DraftBlocking { .. } => "draft_blocking",
+            LandError::ApprovalRequired { .. } => "approval_required",
+            LandError::AboveApprovalBoundary { .. } => "above_approval_boundary",
+            LandError::TargetNotInStack { .. } => "target_not_in_stack",
+            LandError::AmbiguousStack { .. } => "ambiguous_stack",
+            LandError::ApiError { .. } => "api_error",
+            LandError::LandInProgress { .. } => "land_in_progress",
+            LandError::NoLandInProgress => "no_land_in_progress",
+            LandError::JournalError { .. } => "journal_error",
+        }
+    }
+}
+
+/// The topmost PR to land, as given by `--through` or `--branch`
+#[derive(Debug, Clone, PartialEq)]
+pub enum LandThrough {
+    /// A PR number
+    Number(usize),
+    /// A PR head branch name
+    Branch(String),
+}
+
+impl LandThrough {
+    fn matches(&self, pr: &PullRequest) -> bool {
+        match self {
+            LandThrough::Number(number) => pr.number() == *number,
+            LandThrough::Branch(branch) => pr.head() == branch,
+        }
+    }
+}
+
+impl fmt::Display for LandThrough {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LandThrough::Number(number) => write!(f, "PR #{}", number),
+            LandThrough::Branch(branch) => write!(f, "Branch '{}'", branch),
+        }
+    }
+}
+
 /// Options for creating a land plan
 pub struct LandOptions {
     /// Whether to require approval on all PRs
     pub require_approval: bool,
     /// Maximum number of PRs to land (None = all mergeable)
     pub max_count: Option<usize>,
+    /// Land every PR up to and including this one (None = all mergeable)
+    pub through: Option<LandThrough>,
+    /// Head branch of the tip whose path to land, for stacks that fork
+    pub path: Option<String>,
 }
 
 impl Default for LandOptions {
@@ -90,50 +333,274 @@ impl Default for LandOptions {
         LandOptions {
             require_approval: true,
             max_count: None,
+            through: None,
+            path: None,
         }
     }
 }
 
-/// Order the stack from base to top (PRs targeting main/master first)
-fn order_stack_base_to_top(stack: &FlatDep) -> Vec<Rc<PullRequest>> {
-    // Find root PRs (those with no parent in the stack)
-    let mut ordered = Vec::new();
-    let mut remaining: Vec<_> = stack.iter().collect();
+/// A completed step of a land, as recorded in the journal
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum LandStep {
+    /// The top PR's base was changed to the target branch
+    BaseUpdated,
+    /// The top PR was squash-merged
+    Merged {
+        url: String,
+        // Absent in journals written before the SHA was recorded
+        #[serde(default)]
+        sha: String,
+    },
+    /// A PR below the top PR was closed
+    Closed { pr_number: usize },
+    /// A PR off the landed path was retargeted onto the target branch
+    Retargeted { pr_number: usize },
+}
+
+/// Progress of a land operation, persisted after every step
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LandJournal {
+    /// Repository in "owner/repo" format
+    pub repository: String,
+    /// Target branch the top PR is merged into
+    pub target_branch: String,
+    /// The PR being merged
+    pub top_pr: usize,
+    /// Base branch of the top PR before landing (restored by abort)
+    pub original_base: String,
+    /// PRs below the top PR to close after the merge
+    pub prs_to_close: Vec<usize>,
+    /// PRs off the landed path to retarget onto the target branch
+    #[serde(default)]
+    pub prs_to_retarget: Vec<usize>,
+    /// Steps that have completed, in order
+    pub completed: Vec<LandStep>,
+}
+
+impl LandJournal {
+    /// Create a fresh journal for a plan with no completed steps
+    pub fn from_plan(plan: &LandPlan) -> Self {
+        LandJournal {
+            repository: plan.repository.clone(),
+            target_branch: plan.target_branch.clone(),
+            top_pr: plan.top_pr.number(),
+            original_base: plan.top_pr.base().to_string(),
+            prs_to_close: plan.prs_to_close.iter().map(|pr| pr.number()).collect(),
+            prs_to_retarget: plan.retarget.iter().map(|pr| pr.number()).collect(),
+            completed: vec![],
+        }
+    }
 
-    // Start with PRs that have no parent (base of stack)
-    while !remaining.is_empty() {
-        let mut found_any = false;
+    /// Location of the journal for a repository
+    pub fn path(repo: &Repository) -> PathBuf {
+        state::state_dir(repo).join("land.json")
+    }
 
-        remaining.retain(|(pr, parent)| {
-            let dominated_by_parent = parent
-                .as_ref()
-                .map(|p| {
-                    // Check if parent is already in ordered list
-                    ordered
-                        .iter()
-                        .any(|o: &Rc<PullRequest>| o.number() == p.number())
-                })
-                .unwrap_or(true); // No parent means it's a root
+    /// Load the journal at `path`, if a land is in progress
+    pub fn load(path: &Path) -> Result<Option<Self>, LandError> {
+        state::read_json(path).map_err(|e| LandError::JournalError {
+            message: format!("Failed to read {}: {}", path.display(), e),
+        })
+    }
 
-            if dominated_by_parent {
-                ordered.push(pr.clone());
-                found_any = true;
-                false // Remove from remaining
-            } else {
-                true // Keep in remaining
-            }
-        });
+    /// Persist the journal to `path`
+    pub fn save(&self, path: &Path) -> Result<(), LandError> {
+        state::write_json(path, self).map_err(|e| LandError::JournalError {
+            message: format!("Failed to write {}: {}", path.display(), e),
+        })
+    }
 
-        // Safety: prevent infinite loop if graph is malformed
-        if !found_any && !remaining.is_empty() {
-            // Add remaining PRs in any order
-            for (pr, _) in remaining.drain(..) {
-                ordered.push(pr.clone());
-            }
+    /// Whether the top PR's base has been updated
+    pub fn is_base_updated(&self) -> bool {
+        self.completed.contains(&LandStep::BaseUpdated)
+    }
+
+    /// Whether aborting has to put the top PR's base back: it was changed,
+    /// and the PR hasn't been merged since
+    pub fn base_needs_restoring(&self) -> bool {
+        self.is_base_updated() && self.merge_url().is_none()
+    }
+
+    /// URL of the merged PR, if the merge step has completed
+    pub fn merge_url(&self) -> Option<&str> {
+        self.completed.iter().find_map(|step| match step {
+            LandStep::Merged { url, .. } => Some(url.as_str()),
+            _ => None,
+        })
+    }
+
+    /// SHA of the squash commit, if the merge step has completed
+    pub fn merge_sha(&self) -> Option<&str> {
+        self.completed.iter().find_map(|step| match step {
+            LandStep::Merged { sha, .. } => Some(sha.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Build the result event for a finished land
+    pub fn result_event(&self) -> LandEvent {
+        LandEvent::Result {
+            merged_pr: self.top_pr,
+            closed_prs: self
+                .prs_to_close
+                .iter()
+                .copied()
+                .filter(|n| self.is_closed(*n))
+                .collect(),
+            merge_sha: self.merge_sha().unwrap_or_default().to_string(),
+            merge_url: self.merge_url().unwrap_or_default().to_string(),
+        }
+    }
+
+    /// Whether a PR below the top PR has been closed
+    pub fn is_closed(&self, pr_number: usize) -> bool {
+        self.completed.contains(&LandStep::Closed { pr_number })
+    }
+
+    /// Whether a PR off the landed path has been retargeted
+    pub fn is_retargeted(&self, pr_number: usize) -> bool {
+        self.completed.contains(&LandStep::Retargeted { pr_number })
+    }
+
+    /// Record a completed step and persist the journal
+    fn record(
+        &mut self,
+        step: LandStep,
+        path: Option<&Path>,
+        output: OutputFormat,
+    ) -> Result<(), LandError> {
+        self.completed.push(step.clone());
+        if let Some(path) = path {
+            self.save(path)?;
+        }
+        if output == OutputFormat::Json {
+            LandEvent::Step { step }.emit();
+        }
+        Ok(())
+    }
+}
+
+/// Pick the path of open PRs to land, base first
+///
+/// A linear stack is returned as is. When the stack forks, the path ends at
+/// `--path` (or the `--through` target) and runs down through its ancestors;
+/// without either, a forked stack is refused rather than guessing a tip and
+/// closing PRs on another branch of the tree.
+fn select_path(
+    open_prs: &[Rc<PullRequest>],
+    stack: &FlatDep,
+    options: &LandOptions,
+) -> Result<Vec<Rc<PullRequest>>, LandError> {
+    let open_parent = |pr: &PullRequest| -> Option<Rc<PullRequest>> {
+        stack
+            .iter()
+            .find(|(candidate, _)| candidate.number() == pr.number())
+            .and_then(|(_, parent)| parent.as_ref())
+            .and_then(|parent| {
+                open_prs
+                    .iter()
+                    .find(|open| open.number() == parent.number())
+                    .cloned()
+            })
+    };
+
+    let tip = if let Some(head) = &options.path {
+        Some(
+            open_prs
+                .iter()
+                .find(|pr| pr.head() == head)
+                .ok_or_else(|| LandError::TargetNotInStack {
+                    target: LandThrough::Branch(head.clone()).to_string(),
+                })?,
+        )
+    } else if let Some(through) = &options.through {
+        open_prs.iter().find(|pr| through.matches(pr))
+    } else {
+        None
+    };
+
+    let tip = match tip {
+        Some(tip) => tip,
+        None => {
+            check_linear(open_prs, &open_parent)?;
+            return Ok(open_prs.to_vec());
+        }
+    };
+
+    let mut path = vec![tip.clone()];
+    while let Some(parent) = open_parent(path.last().unwrap()) {
+        if path.iter().any(|pr| pr.number() == parent.number()) {
+            break; // cycle guard
+        }
+        path.push(parent);
+    }
+    path.reverse();
+
+    Ok(path)
+}
+
+/// Fail with `AmbiguousStack` if any open PR has more than one open child,
+/// or the open PRs have more than one root
+fn check_linear(
+    open_prs: &[Rc<PullRequest>],
+    open_parent: &dyn Fn(&PullRequest) -> Option<Rc<PullRequest>>,
+) -> Result<(), LandError> {
+    let parents: Vec<Option<usize>> = open_prs
+        .iter()
+        .map(|pr| open_parent(pr).map(|p| p.number()))
+        .collect();
+    let children_of = |parent: Option<usize>| parents.iter().filter(|p| **p == parent).count();
+
+    let fork = if children_of(None) > 1 {
+        let base = open_prs
+            .iter()
+            .zip(&parents)
+            .find(|(_, parent)| parent.is_none())
+            .map(|(pr, _)| pr.base().to_string())
+            .unwrap_or_default();
+        Some(format!("branch '{}'", base))
+    } else {
+        open_prs
+            .iter()
+            .find(|pr| children_of(Some(pr.number())) > 1)
+            .map(|pr| format!("PR #{}", pr.number()))
+    };
+
+    match fork {
+        Some(fork) => {
+            // Tips are the open PRs nothing else is stacked on
+            let heads = open_prs
+                .iter()
+                .filter(|pr| children_of(Some(pr.number())) == 0)
+                .map(|pr| pr.head().to_string())
+                .collect();
+            Err(LandError::AmbiguousStack { fork, heads })
         }
+        None => Ok(()),
     }
+}
 
-    ordered
+/// Open PRs outside the landed path whose parent is one of `prs_to_close`
+fn siblings_to_retarget(
+    open_prs: &[Rc<PullRequest>],
+    stack: &FlatDep,
+    path: &[Rc<PullRequest>],
+    prs_to_close: &[Rc<PullRequest>],
+) -> Vec<Rc<PullRequest>> {
+    open_prs
+        .iter()
+        .filter(|pr| !path.iter().any(|p| p.number() == pr.number()))
+        .filter(|pr| {
+            stack
+                .iter()
+                .find(|(candidate, _)| candidate.number() == pr.number())
+                .and_then(|(_, parent)| parent.as_ref())
+                .map(|parent| prs_to_close.iter().any(|c| c.number() == parent.number()))
+                .unwrap_or(false)
+        })
+        .cloned()
+        .collect()
 }
 
 /// Check if a PR is approved (has at least one approval review)
@@ -156,20 +623,24 @@ pub fn create_land_plan(
     }
 
     // Order from base to top
-    let ordered = order_stack_base_to_top(stack);
+    let stack = &graph::reorder(stack);
+    let ordered: Vec<Rc<PullRequest>> = stack.iter().map(|(pr, _)| pr.clone()).collect();
 
     // Filter to only open PRs
-    let open_prs: Vec<_> = ordered
+    let all_open: Vec<_> = ordered
         .into_iter()
         .filter(|pr| !pr.is_merged() && pr.state() == &crate::api::PullRequestStatus::Open)
         .collect();
 
-    if open_prs.is_empty() {
+    if all_open.is_empty() {
         return Err(LandError::NoPRsMergeable {
             reason: "All PRs are already merged or closed".to_string(),
         });
     }
 
+    // Only ever land along a single path of the tree
+    let open_prs = select_path(&all_open, stack, options)?;
+
     // Find the target branch (base of the first PR)
     let target_branch = stack
         .iter()
@@ -177,6 +648,55 @@ pub fn create_land_plan(
         .map(|(pr, _)| pr.base().to_string())
         .unwrap_or_else(|| "main".to_string());
 
+    // With an explicit target, every PR up to it must pass the checks
+    if let Some(through) = &options.through {
+        let target_index = open_prs
+            .iter()
+            .position(|pr| through.matches(pr))
+            .ok_or_else(|| LandError::TargetNotInStack {
+                target: through.to_string(),
+            })?;
+        let target = &open_prs[target_index];
+
+        for pr in &open_prs[..=target_index] {
+            let reason = if pr.is_draft() {
+                "a draft"
+            } else if options.require_approval && !is_pr_approved(pr) {
+                "not approved"
+            } else {
+                continue;
+            };
+
+            if pr.number() != target.number() {
+                return Err(LandError::AboveApprovalBoundary {
+                    target_pr: target.number(),
+                    blocking_pr: pr.number(),
+                    reason: reason.to_string(),
+                });
+            } else if pr.is_draft() {
+                return Err(LandError::DraftBlocking {
+                    pr_number: pr.number(),
+                });
+            } else {
+                return Err(LandError::ApprovalRequired {
+                    pr_number: pr.number(),
+                });
+            }
+        }
+
+        let mut mergeable = open_prs[..=target_index].to_vec();
+        let top_pr = mergeable.pop().unwrap();
+        let retarget = siblings_to_retarget(&all_open, stack, &open_prs, &mergeable);
+
+        return Ok(LandPlan {
+            top_pr,
+            prs_to_close: mergeable,
+            retarget,
+            target_branch,
+            repository: repository.to_string(),
+        });
+    }
+
     // Find mergeable PRs (stopping at first draft or unapproved PR)
     let mut mergeable: Vec<Rc<PullRequest>> = Vec::new();
 
@@ -221,10 +741,12 @@ pub fn create_land_plan(
     // Rest = PRs to close
     let top_pr = mergeable.pop().unwrap();
     let prs_to_close = mergeable;
+    let retarget = siblings_to_retarget(&all_open, stack, &open_prs, &prs_to_close);
 
     Ok(LandPlan {
         top_pr,
         prs_to_close,
+        retarget,
         target_branch,
         repository: repository.to_string(),
     })
@@ -261,8 +783,12 @@ pub fn format_dry_run(plan: &LandPlan, remaining_prs: &[Rc<PullRequest>]) -> Str
             remaining_prs.len()
         ));
         for pr in remaining_prs {
-            let reason = if pr.is_draft() {
+            let reason = if plan.retarget.iter().any(|r| r.number() == pr.number()) {
+                "off the landed path, will retarget"
+            } else if pr.is_draft() {
                 "draft"
+            } else if is_pr_approved(pr) {
+                "above target"
             } else {
                 "not approved"
             };
@@ -288,10 +814,20 @@ pub fn format_dry_run(plan: &LandPlan, remaining_prs: &[Rc<PullRequest>]) -> Str
         plan.target_branch
     ));
 
+    for (i, pr) in plan.retarget.iter().enumerate() {
+        output.push_str(&format!(
+            "    {}. Retarget PR #{} (off the landed path): {} -> {}\n",
+            i + 3,
+            pr.number(),
+            pr.base(),
+            plan.target_branch
+        ));
+    }
+
     for (i, pr) in plan.prs_to_close.iter().enumerate() {
         output.push_str(&format!(
             "    {}. Close PR #{} with comment: \"Landed via #{}\"\n",
-            i + 3,
+            i + 3 + plan.retarget.len(),
             pr.number(),
             plan.top_pr.number()
         ));
@@ -302,63 +838,426 @@ pub fn format_dry_run(plan: &LandPlan, remaining_prs: &[Rc<PullRequest>]) -> Str
     output
 }
 
-/// Execute the landing plan
-pub async fn execute_land(
-    plan: &LandPlan,
+/// Run every step of the journal that hasn't completed yet
+///
+/// The journal is saved to `journal_path` (if given) after each step, so a
+/// failure at any point leaves an accurate record of what was done.
+async fn run_journal(
+    journal: &mut LandJournal,
+    journal_path: Option<&Path>,
+    output: OutputFormat,
     credentials: &Credentials,
-) -> Result<LandResult, LandError> {
+) -> Result<(), LandError> {
     use crate::api::land::{close_pr_with_comment, merge_pr, update_pr_base};
 
+    let text = output == OutputFormat::Text;
+
     // Step 1: Update top PR's base to target branch
-    println!(
-        "  Updating PR #{} base to {}...",
-        plan.top_pr.number(),
-        plan.target_branch
-    );
-    update_pr_base(
-        plan.top_pr.number(),
-        &plan.target_branch,
-        &plan.repository,
-        credentials,
-    )
-    .await
-    .map_err(|e| LandError::ApiError {
-        message: format!("Failed to update PR base: {}", e),
-    })?;
+    if !journal.is_base_updated() {
+        if text {
+            println!(
+                "  Updating PR #{} base to {}...",
+                journal.top_pr, journal.target_branch
+            );
+        }
+        update_pr_base(
+            journal.top_pr,
+            &journal.target_branch,
+            &journal.repository,
+            credentials,
+        )
+        .await
+        .map_err(|e| LandError::ApiError {
+            message: format!("Failed to update PR base: {}", e),
+        })?;
+        journal.record(LandStep::BaseUpdated, journal_path, output)?;
+    }
 
     // Step 2: Merge the top PR
-    println!("  Merging PR #{}...", plan.top_pr.number());
-    let merge_url = merge_pr(plan.top_pr.number(), &plan.repository, credentials)
+    if journal.merge_url().is_none() {
+        if text {
+            println!("  Merging PR #{}...", journal.top_pr);
+        }
+        let merged = merge_pr(journal.top_pr, &journal.repository, credentials)
+            .await
+            .map_err(|e| LandError::ApiError {
+                message: format!("Failed to merge PR: {}", e),
+            })?;
+        journal.record(
+            LandStep::Merged {
+                url: merged.html_url,
+                sha: merged.sha,
+            },
+            journal_path,
+            output,
+        )?;
+    }
+
+    // Step 3: Move PRs that fork off the landed path onto the target branch,
+    // before the PRs they are based on get closed
+    for pr_number in journal.prs_to_retarget.clone() {
+        if journal.is_retargeted(pr_number) {
+            continue;
+        }
+
+        if text {
+            println!(
+                "  Retargeting PR #{} onto {}...",
+                pr_number, journal.target_branch
+            );
+        }
+        update_pr_base(
+            pr_number,
+            &journal.target_branch,
+            &journal.repository,
+            credentials,
+        )
         .await
         .map_err(|e| LandError::ApiError {
-            message: format!("Failed to merge PR: {}", e),
+            message: format!("Failed to retarget PR #{}: {}", pr_number, e),
         })?;
+        journal.record(LandStep::Retargeted { pr_number }, journal_path, output)?;
+    }
 
-    // Step 3: Close all PRs below with comment
-    let comment = format!("Landed via #{}", plan.top_pr.number());
-    let mut closed_prs = Vec::new();
+    // Step 4: Close all PRs below with comment
+    let comment = format!("Landed via #{}", journal.top_pr);
 
-    for pr in &plan.prs_to_close {
-        println!(
-            "  Closing PR #{} (landed via #{})...",
-            pr.number(),
-            plan.top_pr.number()
-        );
-        close_pr_with_comment(pr.number(), &comment, &plan.repository, credentials)
+    for pr_number in journal.prs_to_close.clone() {
+        if journal.is_closed(pr_number) {
+            continue;
+        }
+
+        if text {
+            println!(
+                "  Closing PR #{} (landed via #{})...",
+                pr_number, journal.top_pr
+            );
+        }
+        close_pr_with_comment(pr_number, &comment, &journal.repository, credentials)
             .await
             .map_err(|e| LandError::ApiError {
-                message: format!("Failed to close PR #{}: {}", pr.number(), e),
+                message: format!("Failed to close PR #{}: {}", pr_number, e),
             })?;
-        closed_prs.push(pr.clone());
+        journal.record(LandStep::Closed { pr_number }, journal_path, output)?;
+    }
+
+    Ok(())
+}
+
+/// Execute the landing plan
+///
+/// Progress is journaled to `journal_path` when given. The journal is
+/// removed once every step has completed.
+pub async fn execute_land(
+    plan: &LandPlan,
+    journal_path: Option<&Path>,
+    output: OutputFormat,
+    credentials: &Credentials,
+) -> Result<LandResult, LandError> {
+    if let Some(path) = journal_path {
+        if let Some(existing) = LandJournal::load(path)? {
+            return Err(LandError::LandInProgress {
+                top_pr: existing.top_pr,
+            });
+        }
+    }
+
+    let mut journal = LandJournal::from_plan(plan);
+    if let Some(path) = journal_path {
+        journal.save(path)?;
+    }
+
+    run_journal(&mut journal, journal_path, output, credentials).await?;
+
+    if let Some(path) = journal_path {
+        clear_journal(path)?;
     }
 
     Ok(LandResult {
         merged_pr: plan.top_pr.clone(),
-        closed_prs,
-        merge_url,
+        closed_prs: plan.prs_to_close.clone(),
+        merge_sha: journal.merge_sha().unwrap_or_default().to_string(),
+        merge_url: journal.merge_url().unwrap_or_default().to_string(),
+    })
+}
+
+/// Resume an interrupted land from its journal
+///
+/// Completed steps are skipped. Returns the finished journal.
+pub async fn resume_land(
+    journal_path: &Path,
+    output: OutputFormat,
+    credentials: &Credentials,
+) -> Result<LandJournal, LandError> {
+    let mut journal = LandJournal::load(journal_path)?.ok_or(LandError::NoLandInProgress)?;
+
+    run_journal(&mut journal, Some(journal_path), output, credentials).await?;
+    clear_journal(journal_path)?;
+
+    Ok(journal)
+}
+
+/// Abort an interrupted land
+///
+/// If the top PR's base was changed but the PR was not merged yet, the
+/// original base is restored. A merge cannot be undone, so once the top PR
+/// has merged this only discards the journal. Returns the discarded journal.
+pub async fn abort_land(
+    journal_path: &Path,
+    output: OutputFormat,
+    credentials: &Credentials,
+) -> Result<LandJournal, LandError> {
+    use crate::api::land::update_pr_base;
+
+    let journal = LandJournal::load(journal_path)?.ok_or(LandError::NoLandInProgress)?;
+
+    if journal.base_needs_restoring() {
+        if output == OutputFormat::Text {
+            println!(
+                "  Restoring PR #{} base to {}...",
+                journal.top_pr, journal.original_base
+            );
+        }
+        update_pr_base(
+            journal.top_pr,
+            &journal.original_base,
+            &journal.repository,
+            credentials,
+        )
+        .await
+        .map_err(|e| LandError::ApiError {
+            message: format!("Failed to restore PR base: {}", e),
+        })?;
+    }
+
+    clear_journal(journal_path)?;
+
+    Ok(journal)
+}
+
+fn clear_journal(path: &Path) -> Result<(), LandError> {
+    state::remove(path).map_err(|e| LandError::JournalError {
+        message: format!("Failed to remove {}: {}", path.display(), e),
     })
 }
 
+/// Plan for tidying up after a successful land
+#[derive(Debug)]
+pub struct CleanupPlan {
+    /// Remaining PRs based on a landed branch, to be retargeted onto the target branch
+    pub retarget: Vec<Rc<PullRequest>>,
+    /// Head branches of the landed PRs, to be deleted
+    pub branches_to_delete: Vec<String>,
+    /// Target branch the stack was landed into
+    pub target_branch: String,
+    /// Repository in "owner/repo" format
+    pub repository: String,
+}
+
+/// Outcome of the cleanup phase
+#[derive(Debug, Default)]
+pub struct CleanupResult {
+    /// PRs whose base was changed to the target branch
+    pub retargeted: Vec<usize>,
+    /// Branches deleted on the remote
+    pub deleted_remote: Vec<String>,
+    /// Branches deleted locally
+    pub deleted_local: Vec<String>,
+    /// Branches that were left alone
+    pub skipped: Vec<SkippedBranch>,
+}
+
+/// A landed branch that cleanup did not delete
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SkippedBranch {
+    pub branch: String,
+    pub reason: String,
+}
+
+impl SkippedBranch {
+    fn new(branch: &str, reason: impl Into<String>) -> Self {
+        SkippedBranch {
+            branch: branch.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl CleanupResult {
+    /// Build the cleanup event
+    pub fn event(&self) -> LandEvent {
+        LandEvent::Cleanup {
+            retargeted: self.retargeted.clone(),
+            deleted_remote: self.deleted_remote.clone(),
+            deleted_local: self.deleted_local.clone(),
+            skipped: self.skipped.clone(),
+        }
+    }
+}
+
+/// Work out which branches to delete and which PRs to retarget after landing `plan`
+///
+/// `remaining_prs` are the open PRs of the stack that were not landed.
+pub fn create_cleanup_plan(plan: &LandPlan, remaining_prs: &[Rc<PullRequest>]) -> CleanupPlan {
+    let branches_to_delete: Vec<String> = plan
+        .prs_to_close
+        .iter()
+        .chain(std::iter::once(&plan.top_pr))
+        .map(|pr| pr.head().to_string())
+        .collect();
+
+    // Siblings off the landed path were already retargeted by the land itself
+    let retarget = remaining_prs
+        .iter()
+        .filter(|pr| branches_to_delete.iter().any(|b| b == pr.base()))
+        .filter(|pr| !plan.retarget.iter().any(|r| r.number() == pr.number()))
+        .cloned()
+        .collect();
+
+    CleanupPlan {
+        retarget,
+        branches_to_delete,
+        target_branch: plan.target_branch.clone(),
+        repository: plan.repository.clone(),
+    }
+}
+
+/// Format the dry-run output for a cleanup plan
+pub fn format_cleanup_dry_run(cleanup: &CleanupPlan) -> String {
+    let mut output = String::new();
+
+    output.push_str("Cleanup Plan:\n");
+
+    for pr in &cleanup.retarget {
+        output.push_str(&format!(
+            "    Retarget PR #{}: {} -> {}\n",
+            pr.number(),
+            pr.base(),
+            cleanup.target_branch
+        ));
+    }
+
+    for branch in &cleanup.branches_to_delete {
+        output.push_str(&format!(
+            "    Delete branch {} (remote and local, unless protected)\n",
+            branch
+        ));
+    }
+
+    output
+}
+
+/// Retarget remaining PRs and delete the landed branches
+///
+/// Retargeting happens first: GitHub closes any open PR whose base branch is
+/// deleted. Protected and trunk branches are never deleted, nor is the branch
+/// currently checked out. Branch deletion failures are reported in
+/// `CleanupResult::skipped` rather than aborting the cleanup.
+pub async fn execute_cleanup(
+    cleanup: &CleanupPlan,
+    repo: Option<&Repository>,
+    remote: &str,
+    output: OutputFormat,
+    credentials: &Credentials,
+) -> Result<CleanupResult, LandError> {
+    use crate::api::land::{delete_branch, is_branch_protected, update_pr_base};
+
+    let text = output == OutputFormat::Text;
+    let mut result = CleanupResult::default();
+
+    for pr in &cleanup.retarget {
+        if text {
+            println!(
+                "  Retargeting PR #{} onto {}...",
+                pr.number(),
+                cleanup.target_branch
+            );
+        }
+        update_pr_base(
+            pr.number(),
+            &cleanup.target_branch,
+            &cleanup.repository,
+            credentials,
+        )
+        .await
+        .map_err(|e| LandError::ApiError {
+            message: format!("Failed to retarget PR #{}: {}", pr.number(), e),
+        })?;
+        result.retargeted.push(pr.number());
+    }
+
+    for branch in &cleanup.branches_to_delete {
+        if is_trunk_branch(branch, Some(&cleanup.target_branch)) {
+            result
+                .skipped
+                .push(SkippedBranch::new(branch, "trunk branch"));
+            continue;
+        }
+
+        match is_branch_protected(branch, &cleanup.repository, credentials).await {
+            Ok(true) => {
+                result
+                    .skipped
+                    .push(SkippedBranch::new(branch, "protected branch"));
+                continue;
+            }
+            Ok(false) => {}
+            Err(e) => {
+                result
+                    .skipped
+                    .push(SkippedBranch::new(branch, e.to_string()));
+                continue;
+            }
+        }
+
+        if text {
+            println!("  Deleting remote branch {}...", branch);
+        }
+        match delete_branch(branch, &cleanup.repository, credentials).await {
+            Ok(true) => result.deleted_remote.push(branch.clone()),
+            Ok(false) => {}
+            Err(e) => {
+                result
+                    .skipped
+                    .push(SkippedBranch::new(branch, e.to_string()));
+                continue;
+            }
+        }
+
+        if let Some(repo) = repo {
+            // Like `git push --delete`, drop the remote-tracking branch too, so
+            // it doesn't claim the remote still has the branch
+            if let Ok(mut tracking) =
+                repo.find_reference(&format!("refs/remotes/{}/{}", remote, branch))
+            {
+                let _ = tracking.delete();
+            }
+            match delete_local_branch(repo, branch) {
+                Ok(true) => result.deleted_local.push(branch.clone()),
+                Ok(false) => {}
+                Err(reason) => result.skipped.push(SkippedBranch::new(branch, reason)),
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Delete a local branch, returning `false` if it doesn't exist
+fn delete_local_branch(repo: &Repository, branch: &str) -> Result<bool, String> {
+    let mut local = match repo.find_branch(branch, git2::BranchType::Local) {
+        Ok(local) => local,
+        Err(_) => return Ok(false),
+    };
+
+    if local.is_head() {
+        return Err("checked out locally".to_string());
+    }
+
+    local.delete().map_err(|e| e.message().to_string())?;
+    Ok(true)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -504,6 +1403,8 @@ mod tests {
         let options = LandOptions {
             require_approval: true,
             max_count: Some(2),
+            through: None,
+            path: None,
         };
 
         let plan = create_land_plan(&stack, "owner/repo", &options).unwrap();
@@ -523,6 +1424,8 @@ mod tests {
         let options = LandOptions {
             require_approval: false,
             max_count: None,
+            through: None,
+            path: None,
         };
 
         let plan = create_land_plan(&stack, "owner/repo", &options).unwrap();
@@ -533,7 +1436,103 @@ mod tests {
     }
 
     #[test]
-    fn test_order_stack_base_to_top() {
+    fn test_create_plan_through_pr_number() {
+        let prs = vec![
+            make_pr(1, "feature-1", "main", true, false),
+            make_pr(2, "feature-2", "feature-1", true, false),
+            make_pr(3, "feature-3", "feature-2", true, false),
+        ];
+        let stack = make_stack(prs);
+        let options = LandOptions {
+            through: Some(LandThrough::Number(2)),
+            ..LandOptions::default()
+        };
+
+        let plan = create_land_plan(&stack, "owner/repo", &options).unwrap();
+
+        assert_eq!(plan.top_pr.number(), 2);
+        assert_eq!(plan.prs_to_close.len(), 1);
+        assert_eq!(plan.prs_to_close[0].number(), 1);
+    }
+
+    #[test]
+    fn test_create_plan_through_branch() {
+        let prs = vec![
+            make_pr(1, "feature-1", "main", true, false),
+            make_pr(2, "feature-2", "feature-1", true, false),
+        ];
+        let stack = make_stack(prs);
+        let options = LandOptions {
+            through: Some(LandThrough::Branch("feature-1".to_string())),
+            ..LandOptions::default()
+        };
+
+        let plan = create_land_plan(&stack, "owner/repo", &options).unwrap();
+
+        assert_eq!(plan.top_pr.number(), 1);
+        assert!(plan.prs_to_close.is_empty());
+    }
+
+    #[test]
+    fn test_create_plan_through_not_in_stack() {
+        let stack = make_stack(vec![make_pr(1, "feature-1", "main", true, false)]);
+        let options = LandOptions {
+            through: Some(LandThrough::Number(42)),
+            ..LandOptions::default()
+        };
+
+        let result = create_land_plan(&stack, "owner/repo", &options);
+        match result {
+            Err(LandError::TargetNotInStack { target }) => assert_eq!(target, "PR #42"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_create_plan_through_above_approval_boundary() {
+        let prs = vec![
+            make_pr(1, "feature-1", "main", true, false),
+            make_pr(2, "feature-2", "feature-1", false, false), // Not approved
+            make_pr(3, "feature-3", "feature-2", true, false),
+        ];
+        let stack = make_stack(prs);
+        let options = LandOptions {
+            through: Some(LandThrough::Number(3)),
+            ..LandOptions::default()
+        };
+
+        let result = create_land_plan(&stack, "owner/repo", &options);
+        assert!(matches!(
+            result,
+            Err(LandError::AboveApprovalBoundary {
+                target_pr: 3,
+                blocking_pr: 2,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_create_plan_through_target_not_approved() {
+        let prs = vec![
+            make_pr(1, "feature-1", "main", true, false),
+            make_pr(2, "feature-2", "feature-1", false, false), // Not approved
+        ];
+        let stack = make_stack(prs);
+        let options = LandOptions {
+            through: Some(LandThrough::Number(2)),
+            ..LandOptions::default()
+        };
+
+        let result = create_land_plan(&stack, "owner/repo", &options);
+        assert!(matches!(
+            result,
+            Err(LandError::ApprovalRequired { pr_number: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_create_plan_orders_stack_base_to_top() {
         // Create PRs in reverse order
         let pr3 = make_pr(3, "feature-3", "feature-2", true, false);
         let pr1 = make_pr(1, "feature-1", "main", true, false);
@@ -545,10 +1544,630 @@ mod tests {
             (pr2.clone(), Some(pr1.clone())),
         ];
 
-        let ordered = order_stack_base_to_top(&stack);
+        let plan = create_land_plan(&stack, "owner/repo", &LandOptions::default()).unwrap();
+
+        assert_eq!(plan.top_pr.number(), 3);
+        assert_eq!(plan.prs_to_close[0].number(), 1);
+        assert_eq!(plan.prs_to_close[1].number(), 2);
+    }
+
+    /// A stack whose parent links follow base branches, so it can fork
+    fn make_tree(prs: Vec<Rc<PullRequest>>) -> FlatDep {
+        prs.iter()
+            .map(|pr| {
+                let parent = prs.iter().find(|p| p.head() == pr.base()).cloned();
+                (pr.clone(), parent)
+            })
+            .collect()
+    }
+
+    /// main <- 1 <- 2 <- 3, with 4 also stacked on 1 and 5 on 4
+    fn make_forked_prs() -> Vec<Rc<PullRequest>> {
+        vec![
+            make_pr(1, "feature-1", "main", true, false),
+            make_pr(2, "feature-2", "feature-1", true, false),
+            make_pr(3, "feature-3", "feature-2", true, false),
+            make_pr(4, "other-1", "feature-1", true, false),
+            make_pr(5, "other-2", "other-1", true, false),
+        ]
+    }
+
+    #[test]
+    fn test_create_plan_refuses_forked_stack() {
+        let stack = make_tree(make_forked_prs());
+
+        let result = create_land_plan(&stack, "owner/repo", &LandOptions::default());
+
+        match result {
+            Err(LandError::AmbiguousStack { fork, heads }) => {
+                assert_eq!(fork, "PR #1");
+                assert_eq!(heads, vec!["feature-3", "other-2"]);
+            }
+            other => panic!("expected AmbiguousStack, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_create_plan_refuses_multiple_roots() {
+        let stack = make_tree(vec![
+            make_pr(1, "feature-1", "main", true, false),
+            make_pr(2, "other-1", "main", true, false),
+        ]);
+
+        let result = create_land_plan(&stack, "owner/repo", &LandOptions::default());
+
+        assert!(matches!(
+            result,
+            Err(LandError::AmbiguousStack { ref fork, .. }) if fork == "branch 'main'"
+        ));
+    }
+
+    #[test]
+    fn test_create_plan_with_path_retargets_siblings() {
+        let stack = make_tree(make_forked_prs());
+        let options = LandOptions {
+            path: Some("feature-3".to_string()),
+            ..LandOptions::default()
+        };
+
+        let plan = create_land_plan(&stack, "owner/repo", &options).unwrap();
+
+        assert_eq!(plan.top_pr.number(), 3);
+        let closed: Vec<usize> = plan.prs_to_close.iter().map(|pr| pr.number()).collect();
+        assert_eq!(closed, vec![1, 2]);
+        // #4 sits on the closed #1; #5 moves along with it
+        let retarget: Vec<usize> = plan.retarget.iter().map(|pr| pr.number()).collect();
+        assert_eq!(retarget, vec![4]);
+    }
+
+    #[test]
+    fn test_create_plan_path_not_in_stack() {
+        let stack = make_tree(make_forked_prs());
+        let options = LandOptions {
+            path: Some("nope".to_string()),
+            ..LandOptions::default()
+        };
+
+        let result = create_land_plan(&stack, "owner/repo", &options);
+
+        assert!(matches!(result, Err(LandError::TargetNotInStack { .. })));
+    }
+
+    #[test]
+    fn test_create_plan_through_selects_path() {
+        let stack = make_tree(make_forked_prs());
+        let options = LandOptions {
+            through: Some(LandThrough::Number(4)),
+            ..LandOptions::default()
+        };
+
+        let plan = create_land_plan(&stack, "owner/repo", &options).unwrap();
+
+        assert_eq!(plan.top_pr.number(), 4);
+        let closed: Vec<usize> = plan.prs_to_close.iter().map(|pr| pr.number()).collect();
+        assert_eq!(closed, vec![1]);
+        let retarget: Vec<usize> = plan.retarget.iter().map(|pr| pr.number()).collect();
+        assert_eq!(retarget, vec![2]);
+    }
+
+    #[test]
+    fn test_cleanup_plan_skips_prs_retargeted_by_land() {
+        let prs = make_forked_prs();
+        let stack = make_tree(prs.clone());
+        let options = LandOptions {
+            path: Some("feature-3".to_string()),
+            ..LandOptions::default()
+        };
+        let plan = create_land_plan(&stack, "owner/repo", &options).unwrap();
+
+        let cleanup = create_cleanup_plan(&plan, &prs[3..]);
+
+        assert!(cleanup.retarget.is_empty());
+    }
+
+    fn make_journal(completed: Vec<LandStep>) -> LandJournal {
+        LandJournal {
+            repository: "owner/repo".to_string(),
+            target_branch: "main".to_string(),
+            top_pr: 3,
+            original_base: "feature-2".to_string(),
+            prs_to_close: vec![1, 2],
+            prs_to_retarget: vec![],
+            completed,
+        }
+    }
+
+    #[test]
+    fn test_journal_from_plan() {
+        let prs = vec![
+            make_pr(1, "feature-1", "main", true, false),
+            make_pr(2, "feature-2", "feature-1", true, false),
+            make_pr(3, "feature-3", "feature-2", true, false),
+        ];
+        let stack = make_stack(prs);
+        let plan = create_land_plan(&stack, "owner/repo", &LandOptions::default()).unwrap();
+
+        let journal = LandJournal::from_plan(&plan);
+
+        assert_eq!(journal, make_journal(vec![]));
+    }
+
+    #[test]
+    fn test_journal_step_queries() {
+        let journal = make_journal(vec![
+            LandStep::BaseUpdated,
+            LandStep::Merged {
+                url: "https://github.com/owner/repo/pull/3".to_string(),
+                sha: "abc123".to_string(),
+            },
+            LandStep::Closed { pr_number: 1 },
+        ]);
+
+        assert!(journal.is_base_updated());
+        assert!(!journal.base_needs_restoring());
+        assert_eq!(
+            journal.merge_url(),
+            Some("https://github.com/owner/repo/pull/3")
+        );
+        assert!(journal.is_closed(1));
+        assert!(!journal.is_closed(2));
+
+        let fresh = make_journal(vec![]);
+        assert!(!fresh.is_base_updated());
+        assert!(!fresh.base_needs_restoring());
+        assert!(fresh.merge_url().is_none());
+
+        let retargeted = make_journal(vec![LandStep::BaseUpdated]);
+        assert!(retargeted.base_needs_restoring());
+    }
+
+    #[test]
+    fn test_journal_save_load_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("land.json");
+
+        assert!(LandJournal::load(&path).unwrap().is_none());
+
+        let journal = make_journal(vec![LandStep::BaseUpdated]);
+        journal.save(&path).unwrap();
+
+        assert_eq!(LandJournal::load(&path).unwrap(), Some(journal));
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn test_execute_land_refuses_when_in_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("land.json");
+        make_journal(vec![LandStep::BaseUpdated])
+            .save(&path)
+            .unwrap();
+
+        let stack = make_stack(vec![make_pr(5, "other", "main", true, false)]);
+        let plan = create_land_plan(&stack, "owner/repo", &LandOptions::default()).unwrap();
+        let creds = Credentials::new("test-token");
+
+        let result = execute_land(&plan, Some(&path), OutputFormat::Text, &creds).await;
+        assert!(matches!(
+            result,
+            Err(LandError::LandInProgress { top_pr: 3 })
+        ));
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn test_resume_land_retargets_before_closing() {
+        let mut server = mockito::Server::new_async().await;
+
+        let retarget_mock = server
+            .mock("PATCH", "/repos/owner/repo/pulls/4")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({"base": "main"}),
+            ))
+            .with_status(200)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let comment_mock = server
+            .mock(
+                "POST",
+                mockito::Matcher::Regex(r"^/repos/owner/repo/issues/\d+/comments$".to_string()),
+            )
+            .with_status(201)
+            .with_body("{}")
+            .expect(2)
+            .create_async()
+            .await;
+        let close_mock = server
+            .mock(
+                "PATCH",
+                mockito::Matcher::Regex(r"^/repos/owner/repo/pulls/[12]$".to_string()),
+            )
+            .with_status(200)
+            .with_body("{}")
+            .expect(2)
+            .create_async()
+            .await;
+
+        std::env::set_var("GITHUB_API_BASE", server.url());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("land.json");
+        let mut journal = make_journal(vec![
+            LandStep::BaseUpdated,
+            LandStep::Merged {
+                url: "https://github.com/owner/repo/pull/3".to_string(),
+                sha: "abc123".to_string(),
+            },
+        ]);
+        journal.prs_to_retarget = vec![4];
+        journal.save(&path).unwrap();
+
+        let creds = Credentials::new("test-token");
+        let journal = resume_land(&path, OutputFormat::Text, &creds)
+            .await
+            .unwrap();
+
+        let steps: Vec<LandStep> = journal.completed[2..].to_vec();
+        assert_eq!(
+            steps,
+            vec![
+                LandStep::Retargeted { pr_number: 4 },
+                LandStep::Closed { pr_number: 1 },
+                LandStep::Closed { pr_number: 2 },
+            ]
+        );
+        retarget_mock.assert_async().await;
+        comment_mock.assert_async().await;
+        close_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn test_resume_land_skips_completed_steps() {
+        let mut server = mockito::Server::new_async().await;
+
+        let base_mock = server
+            .mock("PATCH", "/repos/owner/repo/pulls/3")
+            .expect(0)
+            .create_async()
+            .await;
+        let merge_mock = server
+            .mock("PUT", "/repos/owner/repo/pulls/3/merge")
+            .expect(0)
+            .create_async()
+            .await;
+        let comment_mock = server
+            .mock("POST", "/repos/owner/repo/issues/2/comments")
+            .with_status(201)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let close_mock = server
+            .mock("PATCH", "/repos/owner/repo/pulls/2")
+            .with_status(200)
+            .with_body("{}")
+            .create_async()
+            .await;
+
+        std::env::set_var("GITHUB_API_BASE", server.url());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("land.json");
+        make_journal(vec![
+            LandStep::BaseUpdated,
+            LandStep::Merged {
+                url: "https://github.com/owner/repo/pull/3".to_string(),
+                sha: "abc123".to_string(),
+            },
+            LandStep::Closed { pr_number: 1 },
+        ])
+        .save(&path)
+        .unwrap();
+
+        let creds = Credentials::new("test-token");
+        let journal = resume_land(&path, OutputFormat::Text, &creds)
+            .await
+            .unwrap();
+
+        assert!(journal.is_closed(2));
+        assert!(!path.exists());
+        base_mock.assert_async().await;
+        merge_mock.assert_async().await;
+        comment_mock.assert_async().await;
+        close_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn test_abort_land_restores_original_base() {
+        let mut server = mockito::Server::new_async().await;
+
+        let restore_mock = server
+            .mock("PATCH", "/repos/owner/repo/pulls/3")
+            .match_body(mockito::Matcher::Json(
+                serde_json::json!({"base": "feature-2"}),
+            ))
+            .with_status(200)
+            .with_body("{}")
+            .create_async()
+            .await;
+
+        std::env::set_var("GITHUB_API_BASE", server.url());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("land.json");
+        make_journal(vec![LandStep::BaseUpdated])
+            .save(&path)
+            .unwrap();
+
+        let creds = Credentials::new("test-token");
+        abort_land(&path, OutputFormat::Text, &creds).await.unwrap();
+
+        assert!(!path.exists());
+        restore_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_abort_land_without_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let creds = Credentials::new("test-token");
+
+        let result = abort_land(&dir.path().join("land.json"), OutputFormat::Text, &creds).await;
+        assert!(matches!(result, Err(LandError::NoLandInProgress)));
+    }
+
+    #[test]
+    fn test_create_cleanup_plan() {
+        let prs = vec![
+            make_pr(1, "feature-1", "main", true, false),
+            make_pr(2, "feature-2", "feature-1", true, false),
+            make_pr(3, "feature-3", "feature-2", false, false),
+            make_pr(4, "feature-4", "feature-3", false, false),
+        ];
+        let stack = make_stack(prs.clone());
+        let plan = create_land_plan(&stack, "owner/repo", &LandOptions::default()).unwrap();
+
+        let cleanup = create_cleanup_plan(&plan, &prs[2..]);
+
+        assert_eq!(cleanup.branches_to_delete, vec!["feature-1", "feature-2"]);
+        // Only the PR directly above the landed ones needs a new base
+        assert_eq!(cleanup.retarget.len(), 1);
+        assert_eq!(cleanup.retarget[0].number(), 3);
+        assert_eq!(cleanup.target_branch, "main");
+    }
+
+    #[test]
+    fn test_format_cleanup_dry_run() {
+        let prs = vec![
+            make_pr(1, "feature-1", "main", true, false),
+            make_pr(2, "feature-2", "feature-1", false, false),
+        ];
+        let stack = make_stack(prs.clone());
+        let plan = create_land_plan(&stack, "owner/repo", &LandOptions::default()).unwrap();
+        let cleanup = create_cleanup_plan(&plan, &prs[1..]);
+
+        let output = format_cleanup_dry_run(&cleanup);
+
+        assert!(output.contains("Retarget PR #2: feature-1 -> main"));
+        assert!(output.contains("Delete branch feature-1"));
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn test_execute_cleanup_skips_protected_branches() {
+        let mut server = mockito::Server::new_async().await;
+
+        let retarget_mock = server
+            .mock("PATCH", "/repos/owner/repo/pulls/3")
+            .match_body(mockito::Matcher::Json(serde_json::json!({"base": "main"})))
+            .with_status(200)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let protected_mock = server
+            .mock("GET", "/repos/owner/repo/branches/feature-1")
+            .with_status(200)
+            .with_body(r#"{"protected": true}"#)
+            .create_async()
+            .await;
+        let unprotected_mock = server
+            .mock("GET", "/repos/owner/repo/branches/feature-2")
+            .with_status(200)
+            .with_body(r#"{"protected": false}"#)
+            .create_async()
+            .await;
+        let delete_mock = server
+            .mock("DELETE", "/repos/owner/repo/git/refs/heads/feature-2")
+            .with_status(204)
+            .create_async()
+            .await;
+
+        std::env::set_var("GITHUB_API_BASE", server.url());
+
+        let prs = vec![
+            make_pr(1, "feature-1", "main", true, false),
+            make_pr(2, "feature-2", "feature-1", true, false),
+            make_pr(3, "feature-3", "feature-2", false, false),
+        ];
+        let stack = make_stack(prs.clone());
+        let plan = create_land_plan(&stack, "owner/repo", &LandOptions::default()).unwrap();
+        let cleanup = create_cleanup_plan(&plan, &prs[2..]);
+
+        let creds = Credentials::new("test-token");
+        let result = execute_cleanup(&cleanup, None, "origin", OutputFormat::Text, &creds)
+            .await
+            .unwrap();
+
+        assert_eq!(result.retargeted, vec![3]);
+        assert_eq!(result.deleted_remote, vec!["feature-2"]);
+        assert_eq!(
+            result.skipped,
+            vec![SkippedBranch::new("feature-1", "protected branch")]
+        );
+        retarget_mock.assert_async().await;
+        protected_mock.assert_async().await;
+        unprotected_mock.assert_async().await;
+        delete_mock.assert_async().await;
+    }
 
-        assert_eq!(ordered[0].number(), 1);
-        assert_eq!(ordered[1].number(), 2);
-        assert_eq!(ordered[2].number(), 3);
+    #[test]
+    fn test_delete_local_branch() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let sig = git2::Signature::now("Test", "test@example.com").unwrap();
+        let tree_id = repo.index().unwrap().write_tree().unwrap();
+        let tree = repo.find_tree(tree_id).unwrap();
+        let oid = repo
+            .commit(Some("HEAD"), &sig, &sig, "initial", &tree, &[])
+            .unwrap();
+        let commit = repo.find_commit(oid).unwrap();
+        repo.branch("feature-1", &commit, false).unwrap();
+
+        assert_eq!(delete_local_branch(&repo, "feature-1"), Ok(true));
+        assert_eq!(delete_local_branch(&repo, "feature-1"), Ok(false));
+
+        // The checked-out branch is never deleted
+        let current = crate::tree::current_branch(&repo).unwrap();
+        assert!(delete_local_branch(&repo, &current).is_err());
+    }
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let errors = [
+            LandError::NoPRsInStack,
+            LandError::NoPRsMergeable {
+                reason: String::new(),
+            },
+            LandError::DraftBlocking { pr_number: 1 },
+            LandError::ApprovalRequired { pr_number: 1 },
+            LandError::AboveApprovalBoundary {
+                target_pr: 2,
+                blocking_pr: 1,
+                reason: String::new(),
+            },
+            LandError::TargetNotInStack {
+                target: String::new(),
+            },
+            LandError::AmbiguousStack {
+                fork: String::new(),
+                heads: vec![],
+            },
+            LandError::ApiError {
+                message: String::new(),
+            },
+            LandError::LandInProgress { top_pr: 1 },
+            LandError::NoLandInProgress,
+            LandError::JournalError {
+                message: String::new(),
+            },
+        ];
+
+        let mut codes: Vec<i32> = errors.iter().map(|e| e.exit_code()).collect();
+        assert!(codes.iter().all(|c| *c > 1));
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+
+        // Automation relies on these never changing
+        assert_eq!(LandError::ApprovalRequired { pr_number: 1 }.exit_code(), 6);
+        assert_eq!(
+            LandError::ApiError {
+                message: String::new()
+            }
+            .exit_code(),
+            10
+        );
+    }
+
+    #[test]
+    fn test_land_event_json() {
+        let step = LandEvent::Step {
+            step: LandStep::Closed { pr_number: 2 },
+        };
+        assert_eq!(
+            serde_json::to_value(&step).unwrap(),
+            serde_json::json!({"event": "step", "step": "closed", "pr_number": 2})
+        );
+
+        let error = LandEvent::error(&LandError::ApprovalRequired { pr_number: 5 });
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "event": "error",
+                "code": 6,
+                "kind": "approval_required",
+                "message": "PR #5 requires approval"
+            })
+        );
+    }
+
+    #[test]
+    fn test_land_event_plan() {
+        let prs = vec![
+            make_pr(1, "feature-1", "main", true, false),
+            make_pr(2, "feature-2", "feature-1", true, false),
+            make_pr(3, "feature-3", "feature-2", false, false),
+        ];
+        let stack = make_stack(prs.clone());
+        let plan = create_land_plan(&stack, "owner/repo", &LandOptions::default()).unwrap();
+
+        let value = serde_json::to_value(LandEvent::plan(&plan, &prs[2..])).unwrap();
+
+        assert_eq!(value["event"], "plan");
+        assert_eq!(value["target_branch"], "main");
+        assert_eq!(value["top_pr"]["number"], 2);
+        assert_eq!(
+            value["top_pr"]["url"],
+            "https://github.com/test/repo/pull/2"
+        );
+        assert_eq!(value["prs_to_close"][0]["number"], 1);
+        assert_eq!(value["remaining"][0]["number"], 3);
+    }
+
+    #[test]
+    fn test_journal_result_event() {
+        let journal = make_journal(vec![
+            LandStep::BaseUpdated,
+            LandStep::Merged {
+                url: "https://github.com/owner/repo/pull/3".to_string(),
+                sha: "abc123".to_string(),
+            },
+            LandStep::Closed { pr_number: 1 },
+            LandStep::Closed { pr_number: 2 },
+        ]);
+
+        assert_eq!(
+            serde_json::to_value(journal.result_event()).unwrap(),
+            serde_json::json!({
+                "event": "result",
+                "merged_pr": 3,
+                "closed_prs": [1, 2],
+                "merge_sha": "abc123",
+                "merge_url": "https://github.com/owner/repo/pull/3"
+            })
+        );
+    }
+
+    #[test]
+    fn test_journal_reads_merged_step_without_sha() {
+        let json = r#"{
+            "repository": "owner/repo",
+            "target_branch": "main",
+            "top_pr": 3,
+            "original_base": "feature-2",
+            "prs_to_close": [1, 2],
+            "completed": [
+                {"step": "base_updated"},
+                {"step": "merged", "url": "https://github.com/owner/repo/pull/3"}
+            ]
+        }"#;
+
+        let journal: LandJournal = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            journal.merge_url(),
+            Some("https://github.com/owner/repo/pull/3")
+        );
+        assert_eq!(journal.merge_sha(), Some(""));
     }
 }
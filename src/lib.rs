@@ -1,4 +1,5 @@
 pub mod api;
+pub mod backup;
 pub mod browser;
 pub mod git;
 pub mod graph;
@@ -6,7 +7,13 @@ pub mod identifier;
 pub mod land;
 pub mod markdown;
 pub mod persist;
+pub mod sign;
+pub mod state;
 pub mod status;
+pub mod store;
+pub mod sync;
+#[cfg(test)]
+mod test_support;
 pub mod tree;
 pub mod util;
 
@@ -7,20 +7,21 @@ use std::error::Error;
 use std::io::IsTerminal;
 use std::rc::Rc;
 
+use gh_stack::api::search::Discovery;
 use gh_stack::api::PullRequest;
 use gh_stack::graph::FlatDep;
-use gh_stack::identifier::{self, StackSummary, TrunkAction};
-use gh_stack::land::{self, LandError, LandOptions};
+use gh_stack::identifier::{self, StackSummary, TrunkAction, Trunks};
+use gh_stack::land::{self, LandError, LandOptions, LandThrough};
 use gh_stack::status::{self, StatusConfig};
 use gh_stack::util::loop_until_confirm;
 use gh_stack::Credentials;
-use gh_stack::{api, browser, git, graph, markdown, persist, tree};
+use gh_stack::{api, backup, browser, git, graph, markdown, persist, store, sync, tree};
 
 fn clap<'a, 'b>() -> App<'a, 'b> {
     let identifier = Arg::with_name("identifier")
         .index(1)
         .required(true)
-        .help("All pull requests containing this identifier in their title form a stack");
+        .help("All pull requests containing this identifier in their title form a stack (see --discover for other ways to find them)");
 
     let repository = Arg::with_name("repository")
         .long("repository")
@@ -28,6 +29,12 @@ fn clap<'a, 'b>() -> App<'a, 'b> {
         .takes_value(true)
         .help("Remote repository to filter identifier search results by");
 
+    // For commands that can show a stack spanning several repositories
+    let repositories =
+        repository.clone().multiple(true).number_of_values(1).help(
+            "Remote repository to search; pass multiple times for a stack spanning repositories",
+        );
+
     let exclude = Arg::with_name("exclude")
         .long("excl")
         .short("e")
@@ -57,33 +64,59 @@ fn clap<'a, 'b>() -> App<'a, 'b> {
         .default_value("origin")
         .help("Name of the git remote to detect repository from (default: origin)");
 
+    let discover = Arg::with_name("discover")
+        .long("discover")
+        .takes_value(true)
+        .value_name("STRATEGY")
+        .possible_values(Discovery::NAMES)
+        .help("How to find the stack's PRs: identifier in the title, PR chain from a branch, gh-stack body marker, label, tracked local branches, or local branch ancestry without GitHub (default: title)");
+
     let annotate = SubCommand::with_name("annotate")
         .about("Annotate the descriptions of all PRs in a stack with metadata about all PRs in the stack")
         .setting(AppSettings::ArgRequiredElseHelp)
         .arg(identifier.clone())
         .arg(exclude.clone())
-        .arg(repository.clone())
+        .arg(discover.clone())
+        .arg(repositories.clone())
         .arg(origin.clone())
         .arg(ci.clone())
         .arg(prefix.clone())
         .arg(badges.clone())
+        .arg(Arg::with_name("include-closed")
+                .long("include-closed")
+                .help("With --discover chain, also follow closed and merged PRs"))
         .arg(Arg::with_name("prelude")
                 .long("prelude")
                 .short("p")
                 .value_name("FILE")
                 .help("Prepend the annotation with the contents of this file"));
 
+    // Extra trunks for --all and the trunk picker
+    let trunk_pattern = Arg::with_name("trunk-pattern")
+        .long("trunk-pattern")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .value_name("PATTERN")
+        .help("Also root stacks on branches matching this pattern, e.g. 'release/*' (repeatable)");
+
+    let any_base = Arg::with_name("any-base")
+        .long("any-base")
+        .takes_value(false)
+        .help("Root stacks on any base branch that has no open PR");
+
     // For log command, identifier is optional (can infer from branch)
     let log_identifier = Arg::with_name("identifier")
         .index(1)
         .required(false)
-        .help("Stack identifier in PR titles (optional - infers from current branch if omitted)");
+        .help("Stack identifier in PR titles, or as chosen by --discover (optional - infers from current branch if omitted)");
 
     let log = SubCommand::with_name("log")
         .about("Print a visual tree of all pull requests in a stack")
         .arg(log_identifier)
         .arg(exclude.clone())
-        .arg(repository.clone())
+        .arg(discover.clone())
+        .arg(repositories.clone())
         .arg(origin.clone())
         .arg(
             Arg::with_name("branch")
@@ -111,6 +144,8 @@ fn clap<'a, 'b>() -> App<'a, 'b> {
                 .takes_value(true)
                 .help("Trunk branch name (default: auto-detect or 'main')"),
         )
+        .arg(trunk_pattern.clone())
+        .arg(any_base.clone())
         .arg(
             Arg::with_name("short")
                 .long("short")
@@ -128,7 +163,7 @@ fn clap<'a, 'b>() -> App<'a, 'b> {
         .arg(
             Arg::with_name("include-closed")
                 .long("include-closed")
-                .help("Show local branches whose remote PRs are closed or merged"),
+                .help("Show closed and merged PRs whose branches exist locally, following landed PRs during discovery"),
         )
         .arg(
             Arg::with_name("no-color")
@@ -152,13 +187,14 @@ fn clap<'a, 'b>() -> App<'a, 'b> {
     let status_identifier = Arg::with_name("identifier")
         .index(1)
         .required(false)
-        .help("Stack identifier in PR titles (optional - infers from current branch if omitted)");
+        .help("Stack identifier in PR titles, or as chosen by --discover (optional - infers from current branch if omitted)");
 
     let status_cmd = SubCommand::with_name("status")
         .about("Show stack status with CI, approval, and merge readiness indicators")
         .arg(status_identifier)
         .arg(exclude.clone())
-        .arg(repository.clone())
+        .arg(discover.clone())
+        .arg(repositories.clone())
         .arg(origin.clone())
         .arg(
             Arg::with_name("branch")
@@ -186,6 +222,8 @@ fn clap<'a, 'b>() -> App<'a, 'b> {
                 .takes_value(true)
                 .help("Trunk branch name (default: auto-detect or 'main')"),
         )
+        .arg(trunk_pattern)
+        .arg(any_base)
         .arg(
             Arg::with_name("project")
                 .long("project")
@@ -240,12 +278,60 @@ fn clap<'a, 'b>() -> App<'a, 'b> {
                 .long("initial-cherry-pick-boundary")
                 .short("b")
                 .value_name("SHA")
-                .help("Stop the initial cherry-pick at this SHA (exclusive)"))
+                .help("Stop the initial cherry-pick at this SHA (exclusive), instead of the detected fork point"))
+        .arg(Arg::with_name("continue")
+                .long("continue")
+                .takes_value(false)
+                .conflicts_with_all(&["skip", "abort"])
+                .help("Resume a stopped autorebase once its conflicts are resolved and staged"))
+        .arg(Arg::with_name("skip")
+                .long("skip")
+                .takes_value(false)
+                .conflicts_with_all(&["continue", "abort"])
+                .help("Resume a stopped autorebase, leaving out the commit that conflicted"))
+        .arg(Arg::with_name("abort")
+                .long("abort")
+                .takes_value(false)
+                .conflicts_with_all(&["continue", "skip"])
+                .help("Give up on a stopped autorebase and check out where you started"))
+        .arg(Arg::with_name("no-push")
+                .long("no-push")
+                .takes_value(false)
+                .conflicts_with_all(&["continue", "skip", "abort"])
+                .help("Only update local branches; push them later with `gh-stack push`"))
+        .arg(Arg::with_name("dry-run")
+                .long("dry-run")
+                .takes_value(false)
+                .conflicts_with_all(&["continue", "skip", "abort", "no-push"])
+                .help("Show which commits each branch would get and which would conflict, without changing anything"))
         .setting(AppSettings::ArgRequiredElseHelp)
         .arg(exclude.clone())
+        .arg(discover.clone())
         .arg(repository.clone())
         .arg(ci.clone())
-        .arg(identifier.clone());
+        .arg(
+            identifier
+                .clone()
+                .required(false)
+                .required_unless_one(&["continue", "skip", "abort"]),
+        );
+
+    let push = SubCommand::with_name("push")
+        .about("Push a restacked stack's branches, refusing to overwrite anything pushed since gh-stack last looked")
+        .setting(AppSettings::ArgRequiredElseHelp)
+        .arg(identifier.clone())
+        .arg(exclude.clone())
+        .arg(discover.clone())
+        .arg(repository.clone())
+        .arg(origin.clone())
+        .arg(ci.clone())
+        .arg(
+            Arg::with_name("project")
+                .long("project")
+                .short("C")
+                .value_name("PATH")
+                .help("Path to local repository (auto-detected if omitted)"),
+        );
 
     let rebase = SubCommand::with_name("rebase")
         .about(
@@ -253,13 +339,36 @@ fn clap<'a, 'b>() -> App<'a, 'b> {
         )
         .setting(AppSettings::ArgRequiredElseHelp)
         .arg(exclude.clone())
+        .arg(discover.clone())
+        .arg(repository.clone())
+        .arg(origin.clone())
+        .arg(
+            Arg::with_name("trunk")
+                .long("trunk")
+                .takes_value(true)
+                .help("Trunk branch name (default: auto-detect or 'main')"),
+        )
+        .arg(
+            Arg::with_name("project")
+                .long("project")
+                .short("C")
+                .value_name("PATH")
+                .help("Path to local repository, used to find where each branch's commits start (auto-detected if omitted)"),
+        )
         .arg(identifier.clone());
 
+    // For land, identifier is not needed when resuming or aborting
+    let land_identifier = Arg::with_name("identifier")
+        .index(1)
+        .required_unless_one(&["resume", "abort"])
+        .help("All pull requests containing this identifier in their title form a stack (see --discover for other ways to find them)");
+
     let land = SubCommand::with_name("land")
         .about("Land a stack of PRs by merging the topmost mergeable PR and closing the rest")
         .setting(AppSettings::ArgRequiredElseHelp)
-        .arg(identifier.clone())
+        .arg(land_identifier)
         .arg(exclude.clone())
+        .arg(discover.clone())
         .arg(repository.clone())
         .arg(origin.clone())
         .arg(
@@ -275,11 +384,200 @@ fn clap<'a, 'b>() -> App<'a, 'b> {
                 .value_name("N")
                 .help("Only land the bottom N PRs in the stack"),
         )
+        .arg(
+            Arg::with_name("through")
+                .long("through")
+                .takes_value(true)
+                .value_name("PR")
+                .conflicts_with_all(&["count", "branch"])
+                .validator(|v| match parse_pr_number(&v) {
+                    Some(_) => Ok(()),
+                    None => Err(format!("'{}' isn't a PR number", v)),
+                })
+                .help("Land every PR up to and including this PR number"),
+        )
+        .arg(
+            Arg::with_name("branch")
+                .long("branch")
+                .short("b")
+                .takes_value(true)
+                .value_name("BRANCH")
+                .conflicts_with("count")
+                .help("Land every PR up to and including the PR for this head branch"),
+        )
+        .arg(
+            Arg::with_name("path")
+                .long("path")
+                .takes_value(true)
+                .value_name("HEAD")
+                .help("For a stack that forks, land the path ending at this head branch"),
+        )
         .arg(
             Arg::with_name("dry-run")
                 .long("dry-run")
                 .takes_value(false)
                 .help("Preview what would happen without making changes"),
+        )
+        .arg(
+            Arg::with_name("resume")
+                .long("resume")
+                .takes_value(false)
+                .conflicts_with_all(&["abort", "dry-run"])
+                .help("Resume an interrupted land from its journal"),
+        )
+        .arg(
+            Arg::with_name("abort")
+                .long("abort")
+                .takes_value(false)
+                .conflicts_with("dry-run")
+                .help("Abort an interrupted land, restoring the original base if not yet merged"),
+        )
+        .arg(
+            Arg::with_name("project")
+                .long("project")
+                .short("C")
+                .value_name("PATH")
+                .help("Path to local repository where the land journal is kept (auto-detected if omitted)"),
+        )
+        .arg(
+            Arg::with_name("cleanup")
+                .long("cleanup")
+                .takes_value(false)
+                .conflicts_with_all(&["resume", "abort"])
+                .help("After landing, retarget the next PR onto trunk and delete the landed branches"),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .takes_value(false)
+                .conflicts_with("restack")
+                .help("Emit the plan, each step and the result as JSON lines"),
+        )
+        .arg(
+            Arg::with_name("restack")
+                .long("restack")
+                .takes_value(false)
+                .conflicts_with_all(&["resume", "abort"])
+                .help("Implies --cleanup; also restack the remaining PRs onto trunk and force-push them with lease"),
+        )
+        .arg(ci.clone());
+
+    let check = SubCommand::with_name("check")
+        .about("Check that a stack is well-formed: no cycles, forks, orphaned PRs or closed bases")
+        .setting(AppSettings::ArgRequiredElseHelp)
+        .arg(identifier.clone())
+        .arg(exclude.clone())
+        .arg(discover.clone())
+        .arg(repositories.clone())
+        .arg(origin.clone())
+        .arg(
+            Arg::with_name("trunk")
+                .long("trunk")
+                .takes_value(true)
+                .help("Trunk branch name (default: auto-detect or 'main')"),
+        );
+
+    let sync = SubCommand::with_name("sync")
+        .about("Fetch, update trunk, restack local stacks above merged PRs, prune merged branches and push")
+        .arg(
+            identifier
+                .clone()
+                .required(false)
+                .help("Only sync the stack with this identifier (default: every stack with local branches)"),
+        )
+        .arg(exclude.clone())
+        .arg(discover.clone())
+        .arg(repository.clone())
+        .arg(origin.clone())
+        .arg(ci.clone())
+        .arg(
+            Arg::with_name("trunk")
+                .long("trunk")
+                .takes_value(true)
+                .help("Trunk branch name (default: auto-detect or 'main')"),
+        )
+        .arg(
+            Arg::with_name("project")
+                .long("project")
+                .short("C")
+                .value_name("PATH")
+                .help("Path to local repository (auto-detected if omitted)"),
+        );
+
+    let undo = SubCommand::with_name("undo")
+        .about("Put back the branches a gh-stack command moved or deleted")
+        .arg(
+            Arg::with_name("operation")
+                .index(1)
+                .value_name("OP_ID")
+                .help("Operation to undo (default: the latest)"),
+        )
+        .arg(
+            Arg::with_name("push")
+                .long("push")
+                .takes_value(false)
+                .conflicts_with("list")
+                .help("Also force-push the remote branches back, with lease"),
+        )
+        .arg(
+            Arg::with_name("list")
+                .long("list")
+                .takes_value(false)
+                .help("List the recorded operations, newest first"),
+        )
+        .arg(ci.clone())
+        .arg(
+            Arg::with_name("project")
+                .long("project")
+                .short("C")
+                .value_name("PATH")
+                .help("Path to local repository (auto-detected if omitted)"),
+        );
+
+    let track = SubCommand::with_name("track")
+        .about("Record a branch's parent (and stack) locally so stacks work before PRs exist")
+        .arg(
+            Arg::with_name("branch")
+                .index(1)
+                .help("Branch to track (default: current branch)"),
+        )
+        .arg(
+            Arg::with_name("parent")
+                .long("parent")
+                .short("p")
+                .takes_value(true)
+                .value_name("BRANCH")
+                .required_unless_one(&["remove", "list"])
+                .help("Branch this one is stacked on"),
+        )
+        .arg(
+            Arg::with_name("stack")
+                .long("stack")
+                .short("s")
+                .takes_value(true)
+                .value_name("ID")
+                .help("Stack identifier to record with the branch"),
+        )
+        .arg(
+            Arg::with_name("remove")
+                .long("remove")
+                .takes_value(false)
+                .conflicts_with_all(&["parent", "stack", "list"])
+                .help("Forget the branch's recorded parent and stack"),
+        )
+        .arg(
+            Arg::with_name("list")
+                .long("list")
+                .takes_value(false)
+                .conflicts_with("branch")
+                .help("List all tracked branches"),
+        )
+        .arg(
+            Arg::with_name("project")
+                .long("project")
+                .short("C")
+                .value_name("PATH")
+                .help("Path to local repository (auto-detected if omitted)"),
         );
 
     let app = App::new("gh-stack")
@@ -291,47 +589,260 @@ fn clap<'a, 'b>() -> App<'a, 'b> {
         .subcommand(log)
         .subcommand(rebase)
         .subcommand(autorebase)
+        .subcommand(push)
         .subcommand(land)
-        .subcommand(status_cmd);
+        .subcommand(check)
+        .subcommand(status_cmd)
+        .subcommand(track)
+        .subcommand(sync)
+        .subcommand(undo);
 
     app
 }
 
-async fn build_pr_stack(
-    pattern: &str,
-    credentials: &Credentials,
-    exclude: Vec<String>,
+async fn build_pr_stack_for_repo(
+    identifier: &str,
+    repository: &str,
+    credentials: Option<&Credentials>,
+    lookup: StackLookup,
 ) -> Result<FlatDep, Box<dyn Error>> {
-    let prs = api::search::fetch_pull_requests_matching(pattern, credentials).await?;
-
-    let prs = prs
-        .into_iter()
-        .filter(|pr| !exclude.contains(&pr.number().to_string()))
-        .map(Rc::new)
-        .collect::<Vec<Rc<PullRequest>>>();
+    let prs = fetch_prs_for_repo(identifier, repository, credentials, lookup).await?;
     let graph = graph::build(&prs);
     let stack = graph::log(&graph);
     Ok(stack)
 }
 
-async fn build_pr_stack_for_repo(
-    pattern: &str,
+async fn fetch_prs_for_repo(
+    identifier: &str,
     repository: &str,
-    credentials: &Credentials,
-    exclude: Vec<String>,
-) -> Result<FlatDep, Box<dyn Error>> {
-    let prs =
-        api::search::fetch_matching_pull_requests_from_repository(pattern, repository, credentials)
-            .await?;
+    credentials: Option<&Credentials>,
+    lookup: StackLookup,
+) -> Result<Vec<Rc<PullRequest>>, Box<dyn Error>> {
+    let mut prs = Vec::new();
+    for repository in std::iter::once(repository).chain(lookup.also.iter().map(String::as_str)) {
+        prs.extend(discover_in_repo(identifier, repository, credentials, &lookup).await?);
+    }
 
-    let prs = prs
+    Ok(prs
         .into_iter()
-        .filter(|pr| !exclude.contains(&pr.number().to_string()))
+        .filter(|pr| !lookup.exclude.contains(&pr.number().to_string()))
         .map(Rc::new)
-        .collect::<Vec<Rc<PullRequest>>>();
-    let graph = graph::build(&prs);
-    let stack = graph::log(&graph);
-    Ok(stack)
+        .collect())
+}
+
+async fn discover_in_repo(
+    identifier: &str,
+    repository: &str,
+    credentials: Option<&Credentials>,
+    lookup: &StackLookup,
+) -> Result<Vec<PullRequest>, Box<dyn Error>> {
+    if lookup.discovery == Discovery::Git {
+        let repo = lookup
+            .repo()
+            .ok_or("--discover git needs a local repository (use -C <path>)")?;
+        let branches = store::inferred_stack(&repo, &lookup.trunk, identifier)?;
+        Ok(store::unopened_prs(&repo, &branches, &lookup.remote))
+    } else if lookup.discovery == Discovery::Local {
+        let repo = lookup
+            .repo()
+            .ok_or("--discover local needs a local repository (use -C <path>)")?;
+        let branches = store::stack_branches(&repo, identifier);
+        let credentials = credentials.ok_or(NO_TOKEN)?;
+        api::search::fetch_pull_requests_for_branches(repository, &branches, credentials).await
+    } else {
+        let credentials = credentials.ok_or(NO_TOKEN)?;
+        api::search::discover_pull_requests(
+            lookup.discovery,
+            identifier,
+            repository,
+            &lookup.trunk,
+            lookup.include_closed,
+            credentials,
+        )
+        .await
+    }
+}
+
+/// How to find a stack's PRs, from the flags shared by every subcommand
+struct StackLookup {
+    discovery: Discovery,
+    trunk: String,
+    exclude: Vec<String>,
+    project: Option<String>,
+    /// Remote whose branches stand in for GitHub's with --discover git
+    remote: String,
+    include_closed: bool,
+    /// Repositories to search besides the main one (repeated -r)
+    also: Vec<String>,
+}
+
+impl StackLookup {
+    /// Local repository given with -C, or the one we're running in
+    fn repo(&self) -> Option<Repository> {
+        match &self.project {
+            Some(path) => Repository::open(path).ok(),
+            None => tree::detect_repo(),
+        }
+    }
+}
+
+fn stack_lookup(m: &ArgMatches) -> StackLookup {
+    StackLookup {
+        discovery: get_discovery(m),
+        trunk: m
+            .value_of("trunk")
+            .map(String::from)
+            .or_else(identifier::detect_trunk_branch)
+            .unwrap_or_else(|| "main".to_string()),
+        exclude: get_excluded(m),
+        project: m.value_of("project").map(String::from),
+        remote: m.value_of("origin").unwrap_or("origin").to_string(),
+        include_closed: m.is_present("include-closed"),
+        also: m
+            .values_of("repository")
+            .map(|r| r.skip(1).map(String::from).collect())
+            .unwrap_or_default(),
+    }
+}
+
+/// Trunks that stacks may be rooted on: `trunk` plus any --trunk-pattern
+fn get_trunks(m: &ArgMatches, trunk: &str) -> Trunks {
+    let mut trunks = Trunks::new(trunk);
+    if let Some(patterns) = m.values_of("trunk-pattern") {
+        trunks.patterns.extend(patterns.map(String::from));
+    }
+    trunks.any_base = m.is_present("any-base");
+    trunks
+}
+
+fn get_discovery(m: &ArgMatches) -> Discovery {
+    m.value_of("discover")
+        .map(|d| d.parse().unwrap())
+        .unwrap_or_default()
+}
+
+/// A PR number, with or without a leading `#`
+fn parse_pr_number(value: &str) -> Option<usize> {
+    value.trim_start_matches('#').parse().ok()
+}
+
+/// Refuse `--discover git` for commands that read or edit PRs on GitHub
+fn require_prs(m: &ArgMatches, command: &str) -> Result<(), Box<dyn Error>> {
+    match get_discovery(m) {
+        Discovery::Git => Err(format!(
+            "{} needs the stack's PRs on GitHub; --discover git only finds local branches",
+            command
+        )
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+/// Stop with instructions if an autorebase was interrupted by a conflict,
+/// or if some of its pushes were rejected
+fn exit_unless_done(outcome: git::RebaseOutcome) {
+    if let git::RebaseOutcome::Rejected(rejected) = &outcome {
+        if rejected.is_empty() {
+            return;
+        }
+        eprintln!(
+            "\n{} Some branches weren't pushed:",
+            style("Error:").red().bold()
+        );
+        for push in rejected {
+            eprintln!("  ✗ {} ({})", push.branch, push.reason);
+        }
+        eprintln!(
+            "\nThe remote branches changed since gh-stack last saw them. Fetch, check what was pushed, and restack again."
+        );
+        std::process::exit(1);
+    }
+
+    if let git::RebaseOutcome::Conflict {
+        branch,
+        commit,
+        summary,
+        worktree,
+    } = outcome
+    {
+        eprintln!(
+            "\n{} {} {} (on {}) doesn't apply cleanly.",
+            style("Conflict:").red().bold(),
+            &commit[..7.min(commit.len())],
+            summary,
+            branch
+        );
+        eprintln!(
+            "It was cherry-picked into {}; your own checkout is untouched.",
+            style(worktree.display()).cyan()
+        );
+        eprintln!(
+            "Resolve the conflicts there and `git add` each file, then run {}.",
+            style("gh-stack autorebase --continue").cyan()
+        );
+        eprintln!(
+            "Use {} to leave the commit out, or {} to give up.",
+            style("--skip").cyan(),
+            style("--abort").cyan()
+        );
+        std::process::exit(1);
+    }
+}
+
+/// Split a stack by repository for display
+///
+/// A stack in a single repository comes back whole, without a name.
+fn repository_groups(stack: &FlatDep) -> Vec<(Option<String>, FlatDep)> {
+    let groups = graph::split_by_repository(stack);
+    if groups.len() <= 1 {
+        return vec![(None, stack.clone())];
+    }
+    groups
+        .into_iter()
+        .map(|(name, group)| (Some(name), group))
+        .collect()
+}
+
+/// The local checkout, if it belongs to this group's repository
+///
+/// Branch names only mean something in their own repository, so other
+/// repositories of a multi-repo stack are shown without local details.
+fn local_repo_for<'a>(
+    repo: Option<&'a Repository>,
+    group: Option<&str>,
+    repository: &str,
+) -> Option<&'a Repository> {
+    repo.filter(|_| group.is_none_or(|g| g == repository))
+}
+
+/// Progress message for a stack search
+fn search_message(discovery: Discovery, identifier: &str, repository: &str) -> String {
+    let identifier = style(identifier).bold();
+    let repository = style(repository).bold();
+
+    match discovery {
+        Discovery::Title => format!(
+            "Searching for {} identifier in {} repo",
+            identifier, repository
+        ),
+        Discovery::Chain => format!(
+            "Following the PR chain through {} in {} repo",
+            identifier, repository
+        ),
+        Discovery::Marker => format!(
+            "Searching for {} stack marker in {} repo",
+            identifier, repository
+        ),
+        Discovery::Label => format!("Searching for {} label in {} repo", identifier, repository),
+        Discovery::Local => format!(
+            "Reading stack {} from local branches for {} repo",
+            identifier, repository
+        ),
+        Discovery::Git => format!(
+            "Inferring stack {} from the ancestry of local branches",
+            identifier
+        ),
+    }
 }
 
 fn get_excluded(m: &ArgMatches) -> Vec<String> {
@@ -381,6 +892,48 @@ fn resolve_repository(
         .to_string())
 }
 
+/// The repository a stack is looked up in; `--discover git` doesn't need
+/// one, so it isn't detected then
+fn stack_repository(
+    m: &ArgMatches,
+    env_value: &str,
+    remote_name: &str,
+) -> Result<String, Box<dyn Error>> {
+    if get_discovery(m) == Discovery::Git {
+        let explicit = m.value_of("repository").unwrap_or(env_value);
+        return Ok(explicit.to_string());
+    }
+    Ok(resolve_repository(
+        m.value_of("repository"),
+        env_value,
+        remote_name,
+    )?)
+}
+
+const NO_TOKEN: &str = "You didn't pass `GHSTACK_OAUTH_TOKEN`";
+
+/// Credentials for the GitHub API, from `GHSTACK_OAUTH_TOKEN`
+fn credentials() -> Result<Credentials, Box<dyn Error>> {
+    let token = env::var("GHSTACK_OAUTH_TOKEN").map_err(|_| NO_TOKEN)?;
+    Ok(Credentials::new(&token))
+}
+
+/// Credentials, unless the stack is found with `--discover git`, which
+/// works offline and before any PR is opened
+fn stack_credentials(m: &ArgMatches) -> Result<Option<Credentials>, Box<dyn Error>> {
+    if get_discovery(m) == Discovery::Git {
+        return Ok(env::var("GHSTACK_OAUTH_TOKEN")
+            .ok()
+            .map(|token| Credentials::new(&token)));
+    }
+    credentials().map(Some)
+}
+
+/// The credentials from `stack_credentials`, for a call that needs GitHub
+fn required(credentials: &Option<Credentials>) -> Result<&Credentials, &'static str> {
+    credentials.as_ref().ok_or(NO_TOKEN)
+}
+
 fn remove_title_prefixes(title: String, prefix: &str) -> String {
     let regex = Regex::new(&format!("[{}]", prefix).to_string()).unwrap();
     regex.replace_all(&title, "").into_owned()
@@ -448,7 +1001,7 @@ async fn create_pr_interactive(
     };
 
     // Get default body with stack marker
-    let default_body = format!("<!-- gh-stack:[{}] -->", identifier.unwrap_or(""));
+    let default_body = api::search::stack_marker(identifier.unwrap_or(""));
 
     let (title, body) = if ci_mode || !std::io::stdout().is_terminal() {
         // Non-interactive: use defaults
@@ -479,21 +1032,94 @@ async fn create_pr_interactive(
         api::create::create_pr(repository, head, base, &title, body_opt, credentials).await?;
     println!("Created: {} (PR #{})\n", style(&url).cyan(), pr_num);
 
+    // Remember the parent so the stack can be found locally later
+    if let Some(r) = repo {
+        if let Err(e) = store::track(r, head, base, identifier) {
+            eprintln!(
+                "{} Could not record '{}' in the local stack store: {}",
+                style("Warning:").yellow().bold(),
+                head,
+                e
+            );
+        }
+    }
+
     Ok((pr_num, url))
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    dotenvy::from_filename(".gh-stack.env").ok();
+/// Report a land error and exit with its documented exit code
+fn exit_land_error(e: &LandError, json: bool, resume_hint: bool) -> ! {
+    if json {
+        land::LandEvent::error(e).emit();
+        std::process::exit(e.exit_code());
+    }
 
-    let token = env::var("GHSTACK_OAUTH_TOKEN").expect("You didn't pass `GHSTACK_OAUTH_TOKEN`");
-    // store the value of GHSTACK_TARGET_REPOSITORY
-    let repository = env::var("GHSTACK_TARGET_REPOSITORY").unwrap_or_default();
-    let credentials = Credentials::new(&token);
+    match e {
+        LandError::ApprovalRequired { pr_number } => {
+            eprintln!(
+                "{} PR #{} requires approval",
+                style("Error:").red().bold(),
+                pr_number
+            );
+            eprintln!(
+                "  Hint: Get approval for #{}, or use {} to skip this check",
+                pr_number,
+                style("--no-approval").cyan()
+            );
+        }
+        LandError::DraftBlocking { pr_number } => {
+            eprintln!(
+                "{} PR #{} is a draft and blocks landing",
+                style("Error:").red().bold(),
+                pr_number
+            );
+            eprintln!(
+                "  Hint: Mark PR #{} as ready for review before landing",
+                pr_number
+            );
+        }
+        LandError::AmbiguousStack { heads, .. } => {
+            eprintln!("{} {}", style("Error:").red().bold(), e);
+            if let Some(head) = heads.first() {
+                eprintln!(
+                    "  Hint: Pick one with {}; PRs forking off it are retargeted onto trunk",
+                    style(format!("--path {}", head)).cyan()
+                );
+            }
+        }
+        LandError::AboveApprovalBoundary { blocking_pr, .. } => {
+            eprintln!("{} {}", style("Error:").red().bold(), e);
+            eprintln!(
+                "  Hint: Land through a PR below #{}, or get #{} ready first",
+                blocking_pr, blocking_pr
+            );
+        }
+        _ => {
+            eprintln!("{} {}", style("Error:").red().bold(), e);
+            if resume_hint && !matches!(e, LandError::JournalError { .. }) {
+                eprintln!(
+                    "  Hint: Run {} to retry the remaining steps, or {} to roll back",
+                    style("gh-stack land --resume").cyan(),
+                    style("gh-stack land --abort").cyan()
+                );
+            }
+        }
+    }
+    std::process::exit(e.exit_code());
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    dotenvy::from_filename(".gh-stack.env").ok();
+
+    // store the value of GHSTACK_TARGET_REPOSITORY
+    let repository = env::var("GHSTACK_TARGET_REPOSITORY").unwrap_or_default();
     let matches = clap().get_matches();
 
     match matches.subcommand() {
         ("annotate", Some(m)) => {
+            let credentials = credentials()?;
+            require_prs(m, "annotate")?;
             let identifier = m.value_of("identifier").unwrap();
             let prefix = m.value_of("prefix").unwrap_or("[]");
             let prefix = regex::escape(prefix);
@@ -507,14 +1133,17 @@ async fn main() -> Result<(), Box<dyn Error>> {
             let identifier = remove_title_prefixes(identifier.to_string(), &prefix);
 
             println!(
-                "Searching for {} identifier in {} repo",
-                style(&identifier).bold(),
-                style(&repository).bold()
+                "{}",
+                search_message(get_discovery(m), &identifier, &repository)
             );
 
-            let stack =
-                build_pr_stack_for_repo(&identifier, &repository, &credentials, get_excluded(m))
-                    .await?;
+            let stack = build_pr_stack_for_repo(
+                &identifier,
+                &repository,
+                Some(&credentials),
+                stack_lookup(m),
+            )
+            .await?;
 
             let use_badges = m.is_present("badges");
             let table = markdown::build_table(
@@ -540,8 +1169,22 @@ async fn main() -> Result<(), Box<dyn Error>> {
         }
 
         ("log", Some(m)) => {
-            let explicit_identifier = m.value_of("identifier");
             let branch_override = m.value_of("branch");
+            let local = matches!(get_discovery(m), Discovery::Local | Discovery::Git);
+            let include_closed = m.is_present("include-closed");
+
+            // The local store can name the stack from the branch alone
+            let local_identifier = if local && m.value_of("identifier").is_none() {
+                branch_override.map(String::from).or_else(|| {
+                    stack_lookup(m)
+                        .repo()
+                        .as_ref()
+                        .and_then(tree::current_branch)
+                })
+            } else {
+                None
+            };
+            let explicit_identifier = m.value_of("identifier").or(local_identifier.as_deref());
             let show_all = m.is_present("all");
             let ci_mode = m.is_present("ci");
 
@@ -554,18 +1197,15 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
             // Resolve repository with fallback chain
             let remote_name = m.value_of("origin").unwrap_or("origin");
-            let repository = resolve_repository(m.value_of("repository"), &repository, remote_name)
-                .unwrap_or_else(|e| panic!("{}", e));
+            let repository = stack_repository(m, &repository, remote_name)?;
+            let credentials = stack_credentials(m)?;
 
             // Determine how to find the stack
             let stack: FlatDep = if let Some(id) = explicit_identifier {
                 // === EXISTING BEHAVIOR: Search by identifier ===
-                println!(
-                    "Searching for {} identifier in {} repo",
-                    style(id).bold(),
-                    style(&repository).bold()
-                );
-                build_pr_stack_for_repo(id, &repository, &credentials, get_excluded(m)).await?
+                println!("{}", search_message(get_discovery(m), id, &repository));
+                build_pr_stack_for_repo(id, &repository, credentials.as_ref(), stack_lookup(m))
+                    .await?
             } else if show_all {
                 // === NEW: --all flag - show all stacks ===
                 if ci_mode {
@@ -578,8 +1218,13 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
                 println!("Discovering stacks in {}...", style(&repository).bold());
 
-                let stacks =
-                    api::stack::discover_all_stacks(&repository, &trunk, &credentials).await?;
+                let stacks = api::stack::discover_all_stacks(
+                    &repository,
+                    &get_trunks(m, &trunk),
+                    include_closed,
+                    required(&credentials)?,
+                )
+                .await?;
 
                 if stacks.is_empty() {
                     println!("No open stacks found.");
@@ -612,7 +1257,9 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 match branch {
                     Some(branch) => {
                         // Check if on trunk
-                        if identifier::is_trunk_branch(&branch, Some(&trunk)) {
+                        if identifier::is_trunk_branch(&branch, Some(&trunk))
+                            || get_trunks(m, &trunk).matches(&branch)
+                        {
                             if ci_mode {
                                 eprintln!(
                                     "{} On trunk branch '{}'. Provide an identifier or use --branch.",
@@ -625,9 +1272,13 @@ async fn main() -> Result<(), Box<dyn Error>> {
                             println!("You're on '{}' (trunk branch).\n", style(&branch).cyan());
 
                             // Discover all stacks for selection
-                            let stacks =
-                                api::stack::discover_all_stacks(&repository, &trunk, &credentials)
-                                    .await?;
+                            let stacks = api::stack::discover_all_stacks(
+                                &repository,
+                                &get_trunks(m, &trunk),
+                                include_closed,
+                                required(&credentials)?,
+                            )
+                            .await?;
 
                             let summaries: Vec<StackSummary> = stacks
                                 .iter()
@@ -637,15 +1288,14 @@ async fn main() -> Result<(), Box<dyn Error>> {
                             match identifier::prompt_trunk_action(&summaries)? {
                                 TrunkAction::EnterIdentifier(id) => {
                                     println!(
-                                        "\nSearching for {} identifier in {} repo",
-                                        style(&id).bold(),
-                                        style(&repository).bold()
+                                        "\n{}",
+                                        search_message(get_discovery(m), &id, &repository)
                                     );
                                     build_pr_stack_for_repo(
                                         &id,
                                         &repository,
-                                        &credentials,
-                                        get_excluded(m),
+                                        credentials.as_ref(),
+                                        stack_lookup(m),
                                     )
                                     .await?
                                 }
@@ -666,15 +1316,20 @@ async fn main() -> Result<(), Box<dyn Error>> {
                                 style(&branch).cyan()
                             );
 
-                            match api::stack::fetch_pr_by_head(&repository, &branch, &credentials)
-                                .await?
+                            match api::stack::fetch_pr_by_head(
+                                &repository,
+                                &branch,
+                                required(&credentials)?,
+                            )
+                            .await?
                             {
                                 Some(pr) => {
                                     let prs = api::stack::discover_stack(
                                         &repository,
                                         pr,
                                         &trunk,
-                                        &credentials,
+                                        include_closed,
+                                        required(&credentials)?,
                                     )
                                     .await?;
                                     let prs: Vec<Rc<PullRequest>> =
@@ -696,7 +1351,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
                                             &trunk,
                                             repo_handle.as_ref(),
                                             None, // No identifier known
-                                            &credentials,
+                                            required(&credentials)?,
                                             ci_mode,
                                         )
                                         .await?;
@@ -706,7 +1361,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
                                         if let Some(pr) = api::stack::fetch_pr_by_head(
                                             &repository,
                                             &branch,
-                                            &credentials,
+                                            required(&credentials)?,
                                         )
                                         .await?
                                         {
@@ -714,7 +1369,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
                                                 &repository,
                                                 pr,
                                                 &trunk,
-                                                &credentials,
+                                                include_closed,
+                                                required(&credentials)?,
                                             )
                                             .await?;
                                             let prs: Vec<Rc<PullRequest>> =
@@ -748,16 +1404,21 @@ async fn main() -> Result<(), Box<dyn Error>> {
                                             &trunk,
                                         )? {
                                             // Poll for PR creation
-                                            if let Some(pr) =
-                                                wait_for_pr(&repository, &branch, &credentials, 30)
-                                                    .await
+                                            if let Some(pr) = wait_for_pr(
+                                                &repository,
+                                                &branch,
+                                                required(&credentials)?,
+                                                30,
+                                            )
+                                            .await
                                             {
                                                 println!("\nRetrying stack discovery...");
                                                 let prs = api::stack::discover_stack(
                                                     &repository,
                                                     pr,
                                                     &trunk,
-                                                    &credentials,
+                                                    include_closed,
+                                                    required(&credentials)?,
                                                 )
                                                 .await?;
                                                 let prs: Vec<Rc<PullRequest>> =
@@ -814,8 +1475,14 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 }
             };
 
+            // Tracked branches that don't have a PR yet are still part of the tree
+            let local_branches = match (local, explicit_identifier, stack_lookup(m).repo()) {
+                (true, Some(id), Some(repo)) => store::stack_branches(&repo, id),
+                _ => vec![],
+            };
+
             // Check for empty stack
-            if stack.is_empty() {
+            if stack.is_empty() && local_branches.is_empty() {
                 if let Some(id) = explicit_identifier {
                     println!("No PRs found matching '{}'", id);
                 } else {
@@ -826,6 +1493,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
             // === DISPLAY LOGIC (unchanged) ===
             if m.is_present("status") {
+                require_prs(m, "log --status")?;
                 let no_color = m.is_present("no-color");
                 let show_legend = status::should_show_legend();
 
@@ -842,17 +1510,24 @@ async fn main() -> Result<(), Box<dyn Error>> {
                     .and_then(|p| Repository::open(p).ok())
                     .or_else(tree::detect_repo);
 
-                let entries = status::build_status_entries(
-                    &stack,
-                    repo.as_ref(),
-                    &repository,
-                    &credentials,
-                    &config,
-                )
-                .await;
+                for (name, group) in repository_groups(&stack) {
+                    let local = local_repo_for(repo.as_ref(), name.as_deref(), &repository);
+                    if let Some(name) = &name {
+                        println!("{}\n", style(name).bold());
+                    }
 
-                let output = status::render_status(&entries, &config, repo.is_some());
-                print!("{}", output);
+                    let entries = status::build_status_entries(
+                        &group,
+                        local,
+                        name.as_deref().unwrap_or(&repository),
+                        required(&credentials)?,
+                        &config,
+                    )
+                    .await;
+
+                    let output = status::render_status(&entries, &config, repo.is_some());
+                    print!("{}", output);
+                }
 
                 // Mark legend as seen after first display
                 if show_legend {
@@ -860,16 +1535,29 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 }
             } else if m.is_present("short") {
                 // Original flat output
-                for (pr, maybe_parent) in stack {
-                    match maybe_parent {
-                        Some(parent) => {
-                            let into = style(format!("(Merges into #{})", parent.number())).green();
-                            println!("#{}: {} {}", pr.number(), pr.title(), into);
-                        }
+                for (name, group) in repository_groups(&stack) {
+                    if let Some(name) = &name {
+                        println!("{}", style(name).bold());
+                    }
+
+                    // Branches without a PR yet go by their name
+                    let label = |pr: &PullRequest| match pr.is_unopened() {
+                        true => pr.head().to_string(),
+                        false => format!("#{}", pr.number()),
+                    };
+
+                    for (pr, maybe_parent) in group {
+                        match maybe_parent {
+                            Some(parent) => {
+                                let into =
+                                    style(format!("(Merges into {})", label(&parent))).green();
+                                println!("{}: {} {}", label(&pr), pr.title(), into);
+                            }
 
-                        None => {
-                            let into = style("(Base)").red();
-                            println!("#{}: {} {}", pr.number(), pr.title(), into);
+                            None => {
+                                let into = style("(Base)").red();
+                                println!("{}: {} {}", label(&pr), pr.title(), into);
+                            }
                         }
                     }
                 }
@@ -884,62 +1572,318 @@ async fn main() -> Result<(), Box<dyn Error>> {
                     .and_then(|p| Repository::open(p).ok())
                     .or_else(tree::detect_repo);
 
-                let entries = tree::build_entries(&stack, repo.as_ref(), &config);
-                let output = tree::render(&entries, &config, repo.is_some());
-                print!("{}", output);
+                for (i, (name, group)) in repository_groups(&stack).into_iter().enumerate() {
+                    let local = local_repo_for(repo.as_ref(), name.as_deref(), &repository);
+                    if let Some(name) = &name {
+                        if i > 0 {
+                            println!();
+                        }
+                        println!("{}\n", style(name).bold());
+                    }
+
+                    let entries = tree::build_entries(&group, local, &config);
+                    let entries = if local.is_some() {
+                        tree::add_local_branches(entries, &local_branches, local)
+                    } else {
+                        entries
+                    };
+                    let output = tree::render(&entries, &config, repo.is_some());
+                    print!("{}", output);
+                }
             }
         }
 
         ("rebase", Some(m)) => {
             let identifier = m.value_of("identifier").unwrap();
-            let stack = build_pr_stack(identifier, &credentials, get_excluded(m)).await?;
+            let remote_name = m.value_of("origin").unwrap_or("origin");
+            let repository = stack_repository(m, &repository, remote_name)?;
+            let credentials = stack_credentials(m)?;
+
+            let stack = build_pr_stack_for_repo(
+                identifier,
+                &repository,
+                credentials.as_ref(),
+                stack_lookup(m),
+            )
+            .await?;
+
+            let project = match m.value_of("project") {
+                Some(path) => Some(Repository::open(path)?),
+                None => tree::detect_repo(),
+            };
+            let trunk = m
+                .value_of("trunk")
+                .map(String::from)
+                .or_else(identifier::detect_trunk_branch)
+                .unwrap_or_else(|| "main".to_string());
 
-            let script = git::generate_rebase_script(stack);
+            let roots = git::script_roots(project.as_ref(), remote_name, &trunk, &stack);
+            let script = git::generate_rebase_script(&stack, remote_name, &roots);
             println!("{}", script);
         }
 
         ("autorebase", Some(m)) => {
+            let ci = m.is_present("ci");
+
+            if m.is_present("continue") || m.is_present("skip") || m.is_present("abort") {
+                let project = match m.value_of("project") {
+                    Some(path) => Some(Repository::open(path)?),
+                    None => tree::detect_repo(),
+                };
+                let Some(project) = project else {
+                    eprintln!(
+                        "{} Could not find a git repository to read the autorebase state from",
+                        style("Error:").red().bold()
+                    );
+                    std::process::exit(1);
+                };
+
+                let result = if m.is_present("abort") {
+                    git::abort_rebase(&project).map(|_| {
+                        println!("Autorebase aborted; nothing was changed.");
+                        None
+                    })
+                } else if m.is_present("skip") {
+                    git::skip_commit(&project, ci).await.map(Some)
+                } else {
+                    git::continue_rebase(&project, ci).await.map(Some)
+                };
+
+                match result {
+                    Ok(Some(outcome)) => {
+                        exit_unless_done(outcome);
+                        println!("All done!");
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        eprintln!("{} {}", style("Error:").red().bold(), e);
+                        std::process::exit(1);
+                    }
+                }
+                return Ok(());
+            }
+
             let identifier = m.value_of("identifier").unwrap();
 
             // defaults to "origin" if no remote is specified
             let remote_name = m.value_of("origin").unwrap_or("origin");
 
             // resolve repository with fallback chain
-            let repository = resolve_repository(m.value_of("repository"), &repository, remote_name)
-                .unwrap_or_else(|e| panic!("{}", e));
+            let repository = stack_repository(m, &repository, remote_name)?;
+            let credentials = stack_credentials(m)?;
 
             println!(
-                "Searching for {} identifier in {} repo",
-                style(identifier).bold(),
-                style(&repository).bold()
+                "{}",
+                search_message(get_discovery(m), identifier, &repository)
             );
-            let stack =
-                build_pr_stack_for_repo(identifier, &repository, &credentials, get_excluded(m))
-                    .await?;
+            let stack = build_pr_stack_for_repo(
+                identifier,
+                &repository,
+                credentials.as_ref(),
+                stack_lookup(m),
+            )
+            .await?;
 
             let project = m
                 .value_of("project")
                 .expect("The --project argument is required.");
             let project = Repository::open(project)?;
 
-            // use the same remote name for finding the remote to push to
-            let remote = project.find_remote(remote_name).unwrap();
+            // Only pushing needs the remote; --dry-run and --no-push work offline
+            if !m.is_present("dry-run") && !m.is_present("no-push") {
+                project
+                    .find_remote(remote_name)
+                    .map_err(|_| format!("There's no remote named '{}' to push to", remote_name))?;
+            }
 
-            // if ci flag is set, set ci to true
-            let ci = m.is_present("ci");
+            if m.is_present("dry-run") {
+                let planned =
+                    git::dry_run_rebase(&stack, &project, remote_name, m.value_of("boundary"))?;
+                print!("\n{}", git::render_dry_run(&planned));
+                let conflicts = planned
+                    .iter()
+                    .flat_map(|b| &b.commits)
+                    .any(|c| matches!(c.step, git::PlannedStep::Conflict(_)));
+                if conflicts {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
 
-            git::perform_rebase(
+            let outcome = git::perform_rebase(
                 stack,
                 &project,
-                remote.name().unwrap(),
+                remote_name,
                 m.value_of("boundary"),
                 ci,
+                m.is_present("no-push"),
             )
             .await?;
+            exit_unless_done(outcome);
+            if m.is_present("no-push") {
+                let discover = match get_discovery(m) {
+                    Discovery::Title => String::new(),
+                    other => format!(" --discover {}", other),
+                };
+                println!(
+                    "\nPush the stack with {}",
+                    style(format!("gh-stack push '{}'{}", identifier, discover)).cyan()
+                );
+            }
             println!("All done!");
         }
 
+        ("push", Some(m)) => {
+            let identifier = m.value_of("identifier").unwrap();
+            let remote_name = m.value_of("origin").unwrap_or("origin");
+            let repository = stack_repository(m, &repository, remote_name)?;
+            let credentials = stack_credentials(m)?;
+
+            let project = match m.value_of("project") {
+                Some(path) => Some(Repository::open(path)?),
+                None => tree::detect_repo(),
+            };
+            let Some(project) = project else {
+                eprintln!(
+                    "{} Could not find a git repository to push from",
+                    style("Error:").red().bold()
+                );
+                std::process::exit(1);
+            };
+
+            println!(
+                "{}",
+                search_message(get_discovery(m), identifier, &repository)
+            );
+            let stack = build_pr_stack_for_repo(
+                identifier,
+                &repository,
+                credentials.as_ref(),
+                stack_lookup(m),
+            )
+            .await?;
+
+            // Branches behind the remote are reported with the rejected ones
+            let (pushes, refused) = git::stack_pushes(&project, remote_name, &stack)?;
+            if pushes.is_empty() && refused.is_empty() {
+                println!("Every branch already matches its PR on GitHub; nothing to push.");
+                return Ok(());
+            }
+
+            let mut rejected = vec![];
+            if !pushes.is_empty() {
+                let names: Vec<String> = pushes.iter().map(|p| p.branch.clone()).collect();
+                let op = backup::record(&project, "push", remote_name, &names)?;
+                println!("Backed up as {}", style(&op.id).cyan());
+
+                rejected =
+                    git::push_branches(&project, remote_name, &pushes, m.is_present("ci")).await?;
+                let pushed: Vec<String> = names
+                    .into_iter()
+                    .filter(|name| !rejected.iter().any(|r| &r.branch == name))
+                    .collect();
+                git::clear_leases(&project, &pushed)?;
+            }
+            rejected.extend(refused);
+            exit_unless_done(git::RebaseOutcome::Rejected(rejected));
+            println!(
+                "\n{} Pushed {} branch(es)",
+                style("Done!").green().bold(),
+                pushes.len()
+            );
+        }
+
         ("land", Some(m)) => {
+            let credentials = credentials()?;
+            require_prs(m, "land")?;
+            let json = m.is_present("json");
+            let output = if json {
+                land::OutputFormat::Json
+            } else {
+                land::OutputFormat::Text
+            };
+
+            // The journal lives in the local repository; without one, land still
+            // works but can't be resumed
+            let local_repo = m
+                .value_of("project")
+                .and_then(|p| Repository::open(p).ok())
+                .or_else(tree::detect_repo);
+            let journal_path = local_repo.as_ref().map(land::LandJournal::path);
+
+            if m.is_present("resume") || m.is_present("abort") {
+                let journal_path = match journal_path {
+                    Some(path) => path,
+                    None => {
+                        eprintln!(
+                            "{} Could not find a git repository to read the land journal from",
+                            style("Error:").red().bold()
+                        );
+                        std::process::exit(1);
+                    }
+                };
+
+                if m.is_present("resume") {
+                    if !json {
+                        println!("Resuming land...\n");
+                    }
+                    match land::resume_land(&journal_path, output, &credentials).await {
+                        Ok(journal) if json => journal.result_event().emit(),
+                        Ok(journal) => {
+                            println!(
+                                "\n{} Stack landed via {}",
+                                style("Done!").green().bold(),
+                                style(journal.merge_url().unwrap_or_default()).cyan()
+                            );
+                        }
+                        Err(e) => exit_land_error(&e, json, false),
+                    }
+                } else {
+                    if !json {
+                        println!("Aborting land...\n");
+                    }
+                    match land::abort_land(&journal_path, output, &credentials).await {
+                        Ok(journal) => {
+                            let unclosed: Vec<usize> = journal
+                                .prs_to_close
+                                .iter()
+                                .copied()
+                                .filter(|n| !journal.is_closed(*n))
+                                .collect();
+                            match journal.merge_url() {
+                                _ if json => land::LandEvent::Aborted {
+                                    top_pr: journal.top_pr,
+                                    base_restored: journal.base_needs_restoring(),
+                                    unclosed_prs: unclosed,
+                                }
+                                .emit(),
+                                Some(url) => {
+                                    println!(
+                                        "{} PR #{} was already merged ({}); the journal was discarded.",
+                                        style("Note:").yellow(),
+                                        journal.top_pr,
+                                        url
+                                    );
+                                    if !unclosed.is_empty() {
+                                        let unclosed: Vec<String> =
+                                            unclosed.iter().map(|n| format!("#{}", n)).collect();
+                                        println!(
+                                            "  These PRs were not closed: {}",
+                                            unclosed.join(", ")
+                                        );
+                                    }
+                                }
+                                None => {
+                                    println!("{} Land aborted.", style("Done!").green().bold());
+                                }
+                            }
+                        }
+                        Err(e) => exit_land_error(&e, json, false),
+                    }
+                }
+                return Ok(());
+            }
+
             let identifier = m.value_of("identifier").unwrap();
 
             // resolve repository with fallback chain
@@ -947,19 +1891,25 @@ async fn main() -> Result<(), Box<dyn Error>> {
             let repository = resolve_repository(m.value_of("repository"), &repository, remote_name)
                 .unwrap_or_else(|e| panic!("{}", e));
 
-            println!(
-                "Analyzing stack for {} in {}...\n",
-                style(identifier).bold(),
-                style(&repository).bold()
-            );
+            if !json {
+                println!(
+                    "Analyzing stack for {} in {}...\n",
+                    style(identifier).bold(),
+                    style(&repository).bold()
+                );
+            }
 
-            let stack =
-                build_pr_stack_for_repo(identifier, &repository, &credentials, get_excluded(m))
-                    .await?;
+            let stack = build_pr_stack_for_repo(
+                identifier,
+                &repository,
+                Some(&credentials),
+                stack_lookup(m),
+            )
+            .await?;
 
-            if stack.is_empty() {
+            if stack.is_empty() && !json {
                 println!("No PRs found matching '{}'", identifier);
-                return Ok(());
+                std::process::exit(LandError::NoPRsInStack.exit_code());
             }
 
             // Parse options
@@ -967,47 +1917,25 @@ async fn main() -> Result<(), Box<dyn Error>> {
             let max_count = m
                 .value_of("count")
                 .map(|s| s.parse::<usize>().expect("--count must be a number"));
+            let through = match (m.value_of("through"), m.value_of("branch")) {
+                // clap has already checked it's a number
+                (Some(number), _) => parse_pr_number(number).map(LandThrough::Number),
+                (None, Some(branch)) => Some(LandThrough::Branch(branch.to_string())),
+                (None, None) => None,
+            };
             let dry_run = m.is_present("dry-run");
 
             let options = LandOptions {
                 require_approval,
                 max_count,
+                through,
+                path: m.value_of("path").map(String::from),
             };
 
             // Create the landing plan
             let plan = match land::create_land_plan(&stack, &repository, &options) {
                 Ok(plan) => plan,
-                Err(e) => {
-                    match &e {
-                        LandError::ApprovalRequired { pr_number } => {
-                            eprintln!(
-                                "{} PR #{} requires approval",
-                                style("Error:").red().bold(),
-                                pr_number
-                            );
-                            eprintln!(
-                                "  Hint: Get approval for #{}, or use {} to skip this check",
-                                pr_number,
-                                style("--no-approval").cyan()
-                            );
-                        }
-                        LandError::DraftBlocking { pr_number } => {
-                            eprintln!(
-                                "{} PR #{} is a draft and blocks landing",
-                                style("Error:").red().bold(),
-                                pr_number
-                            );
-                            eprintln!(
-                                "  Hint: Mark PR #{} as ready for review before landing",
-                                pr_number
-                            );
-                        }
-                        _ => {
-                            eprintln!("{} {}", style("Error:").red().bold(), e);
-                        }
-                    }
-                    std::process::exit(1);
-                }
+                Err(e) => exit_land_error(&e, json, false),
             };
 
             // Calculate remaining PRs (those not in the plan)
@@ -1021,17 +1949,68 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 .map(|(pr, _)| pr.clone())
                 .collect();
 
+            let restack = m.is_present("restack");
+            let cleanup = (m.is_present("cleanup") || restack)
+                .then(|| land::create_cleanup_plan(&plan, &remaining_prs));
+
+            if restack && local_repo.is_none() {
+                eprintln!(
+                    "{} --restack needs a local repository (run from one or pass -C)",
+                    style("Error:").red().bold()
+                );
+                std::process::exit(1);
+            }
+
+            // autorebase replays one line of PRs, so the PRs forking off the
+            // landed path would be rebuilt on top of the ones above it
+            if restack && !plan.retarget.is_empty() {
+                let forks: Vec<String> = plan
+                    .retarget
+                    .iter()
+                    .map(|pr| format!("#{}", pr.number()))
+                    .collect();
+                eprintln!(
+                    "{} --restack can't restack {}, which fork off the landed path",
+                    style("Error:").red().bold(),
+                    forks.join(", ")
+                );
+                eprintln!(
+                    "  Hint: Use {} instead, then restack each stack with {}",
+                    style("--cleanup").cyan(),
+                    style("gh-stack autorebase").cyan()
+                );
+                std::process::exit(1);
+            }
+
+            if json {
+                land::LandEvent::plan(&plan, &remaining_prs).emit();
+            }
+
             if dry_run {
                 // Print dry-run output
-                println!("{}", land::format_dry_run(&plan, &remaining_prs));
+                if !json {
+                    println!("{}", land::format_dry_run(&plan, &remaining_prs));
+                    if let Some(cleanup) = &cleanup {
+                        println!("{}", land::format_cleanup_dry_run(cleanup));
+                    }
+                }
                 return Ok(());
             }
 
             // Execute the landing
-            let total_to_land = plan.prs_to_close.len() + 1;
-            println!("Landing {} PR(s)...\n", total_to_land);
+            if !json {
+                let total_to_land = plan.prs_to_close.len() + 1;
+                println!("Landing {} PR(s)...\n", total_to_land);
+            }
 
-            match land::execute_land(&plan, &credentials).await {
+            match land::execute_land(&plan, journal_path.as_deref(), output, &credentials).await {
+                Ok(result) if json => land::LandEvent::Result {
+                    merged_pr: result.merged_pr.number(),
+                    closed_prs: result.closed_prs.iter().map(|pr| pr.number()).collect(),
+                    merge_sha: result.merge_sha,
+                    merge_url: result.merge_url,
+                }
+                .emit(),
                 Ok(result) => {
                     println!(
                         "\n{} Stack landed via {}",
@@ -1039,14 +2018,349 @@ async fn main() -> Result<(), Box<dyn Error>> {
                         style(&result.merge_url).cyan()
                     );
                 }
-                Err(e) => {
-                    eprintln!("\n{} {}", style("Error:").red().bold(), e);
+                Err(e) => exit_land_error(&e, json, journal_path.is_some()),
+            }
+
+            let cleanup = match cleanup {
+                Some(cleanup) => cleanup,
+                None => return Ok(()),
+            };
+
+            if !json {
+                println!("\nCleaning up...\n");
+            }
+            if let Some(repo) = &local_repo {
+                let op = backup::record(repo, "land", remote_name, &cleanup.branches_to_delete)?;
+                if !json {
+                    println!(
+                        "  Backed up the landed branches as {}",
+                        style(&op.id).cyan()
+                    );
+                }
+            }
+            let result = match land::execute_cleanup(
+                &cleanup,
+                local_repo.as_ref(),
+                remote_name,
+                output,
+                &credentials,
+            )
+            .await
+            {
+                Ok(result) => result,
+                Err(e) => exit_land_error(&e, json, false),
+            };
+
+            if json {
+                result.event().emit();
+            } else {
+                for skipped in &result.skipped {
+                    println!(
+                        "  {} Kept branch {} ({})",
+                        style("Note:").yellow(),
+                        skipped.branch,
+                        skipped.reason
+                    );
+                }
+                println!(
+                    "\n{} Retargeted {} PR(s), deleted {} remote and {} local branch(es)",
+                    style("Done!").green().bold(),
+                    result.retargeted.len(),
+                    result.deleted_remote.len(),
+                    result.deleted_local.len()
+                );
+            }
+
+            if restack {
+                let project = local_repo.as_ref().unwrap();
+
+                // The PRs left in the stack, with retargeted ones now sitting on trunk
+                let landed: Vec<&str> = cleanup
+                    .branches_to_delete
+                    .iter()
+                    .map(String::as_str)
+                    .collect();
+                let remaining_stack: FlatDep = stack
+                    .iter()
+                    .filter(|(pr, _)| remaining_prs.iter().any(|r| r.number() == pr.number()))
+                    .map(|(pr, parent)| {
+                        if landed.contains(&pr.base()) {
+                            (Rc::new(pr.with_base(&plan.target_branch)), None)
+                        } else {
+                            (pr.clone(), parent.clone())
+                        }
+                    })
+                    .collect();
+
+                if remaining_stack.is_empty() {
+                    return Ok(());
+                }
+
+                // The landed top PR's old head marks where the remaining commits start
+                let boundary = plan.top_pr.head_sha();
+                if project.revparse_single(boundary).is_err() {
+                    eprintln!(
+                        "{} Commit {} of the landed PR isn't available locally; skipping restack",
+                        style("Error:").red().bold(),
+                        boundary
+                    );
                     std::process::exit(1);
                 }
+
+                println!(
+                    "\nRestacking the remaining PRs onto {}...\n",
+                    plan.target_branch
+                );
+                git::fetch(project, remote_name).await?;
+                let outcome = git::perform_rebase(
+                    remaining_stack,
+                    project,
+                    remote_name,
+                    Some(boundary),
+                    m.is_present("ci"),
+                    false,
+                )
+                .await?;
+                exit_unless_done(outcome);
+            }
+        }
+
+        ("check", Some(m)) => {
+            require_prs(m, "check")?;
+            let identifier = m.value_of("identifier").unwrap();
+            let remote_name = m.value_of("origin").unwrap_or("origin");
+            let repository = stack_repository(m, &repository, remote_name)?;
+            let credentials = stack_credentials(m)?;
+            let trunk = m
+                .value_of("trunk")
+                .map(String::from)
+                .or_else(identifier::detect_trunk_branch);
+
+            println!(
+                "Checking stack {} in {}...\n",
+                style(identifier).bold(),
+                style(&repository).bold()
+            );
+
+            let prs = fetch_prs_for_repo(
+                identifier,
+                &repository,
+                credentials.as_ref(),
+                stack_lookup(m),
+            )
+            .await?;
+            if prs.is_empty() {
+                println!("No PRs found matching '{}'", identifier);
+                std::process::exit(1);
+            }
+
+            let problems = graph::validate(&graph::build(&prs), trunk.as_deref());
+            if problems.is_empty() {
+                println!(
+                    "{} Stack is well-formed ({} PRs)",
+                    style("OK").green().bold(),
+                    prs.len()
+                );
+                return Ok(());
+            }
+
+            for problem in &problems {
+                println!("{} {}", style("✗").red().bold(), problem);
+                println!("  Hint: {}", problem.hint());
+            }
+            println!("\n{} problem(s) found", problems.len());
+            std::process::exit(1);
+        }
+
+        ("sync", Some(m)) => {
+            let credentials = credentials()?;
+            require_prs(m, "sync")?;
+            let ci = m.is_present("ci");
+            let remote_name = m.value_of("origin").unwrap_or("origin");
+            let repository = resolve_repository(m.value_of("repository"), &repository, remote_name)
+                .unwrap_or_else(|e| panic!("{}", e));
+            let project = match m.value_of("project") {
+                Some(path) => Repository::open(path)?,
+                None => tree::detect_repo().ok_or("Not in a git repository (use -C <path>)")?,
+            };
+
+            // Merged PRs are needed to know where their children go
+            let mut lookup = stack_lookup(m);
+            lookup.include_closed = true;
+            let trunk = lookup.trunk.clone();
+
+            println!("Fetching {}...", remote_name);
+            git::fetch(&project, remote_name).await?;
+            let trunk_update = git::fast_forward_branch(&project, remote_name, &trunk)?;
+
+            let stacks: Vec<FlatDep> = match m.value_of("identifier") {
+                Some(identifier) => {
+                    println!(
+                        "{}",
+                        search_message(lookup.discovery, identifier, &repository)
+                    );
+                    vec![
+                        build_pr_stack_for_repo(
+                            identifier,
+                            &repository,
+                            Some(&credentials),
+                            lookup,
+                        )
+                        .await?,
+                    ]
+                }
+                None => {
+                    println!("Discovering stacks in {}...", style(&repository).bold());
+                    let stacks = api::stack::discover_all_stacks(
+                        &repository,
+                        &get_trunks(m, &trunk),
+                        true,
+                        &credentials,
+                    )
+                    .await?
+                    .into_iter()
+                    .map(|prs| {
+                        let prs: Vec<Rc<PullRequest>> = prs
+                            .into_iter()
+                            .filter(|pr| !lookup.exclude.contains(&pr.number().to_string()))
+                            .map(Rc::new)
+                            .collect();
+                        graph::log(&graph::build(&prs))
+                    })
+                    .collect();
+                    sync::local_stacks(&project, stacks)
+                }
+            };
+
+            let mut results = vec![];
+            for stack in stacks {
+                // A stopped autorebase has to be finished before another can start
+                if results.iter().any(|r: &sync::StackSync| {
+                    matches!(r.outcome, sync::SyncOutcome::Conflict { .. })
+                }) {
+                    results.push(sync::StackSync::skipped(
+                        &sync::stack_name(&stack),
+                        "resolve the conflict above first",
+                    ));
+                    continue;
+                }
+
+                println!("\nSyncing {}", style(sync::stack_name(&stack)).bold());
+                results.push(
+                    sync::sync_stack(stack, &project, remote_name, &repository, ci, &credentials)
+                        .await?,
+                );
+            }
+
+            println!(
+                "\n{}\n{}",
+                style("Sync summary").bold(),
+                sync::render_summary(&trunk, &trunk_update, &results)
+            );
+            if results.iter().any(sync::StackSync::needs_attention) {
+                std::process::exit(1);
+            }
+        }
+
+        ("undo", Some(m)) => {
+            let repo = match m.value_of("project") {
+                Some(path) => Repository::open(path)?,
+                None => tree::detect_repo().ok_or("Not in a git repository (use -C <path>)")?,
+            };
+
+            if m.is_present("list") {
+                let log = backup::operations(&repo)?;
+                if log.is_empty() {
+                    println!("No recorded operations.");
+                }
+                print!("{}", backup::render_operations(&log));
+                return Ok(());
+            }
+
+            let op = backup::find(&repo, m.value_of("operation"))?;
+            let names: Vec<String> = op.branches.iter().map(|b| b.name.clone()).collect();
+            let pushes = if m.is_present("push") {
+                // Branches deleted through the API still have remote-tracking refs
+                git::fetch(&repo, &op.remote).await?;
+                op.restore_pushes(&repo)
+            } else {
+                vec![]
+            };
+
+            // Undoing is itself undoable
+            let undo = backup::record(&repo, &format!("undo {}", op.id), &op.remote, &names)?;
+
+            let restored = backup::restore_local(&repo, &op)?;
+            for branch in &op.branches {
+                let mark = if restored.contains(&branch.name) {
+                    "+"
+                } else {
+                    "="
+                };
+                match &branch.local {
+                    Some(sha) => println!("  {} {} -> {}", mark, branch.name, &sha[..7]),
+                    None => println!("  = {} (no local branch)", branch.name),
+                }
+            }
+
+            if !pushes.is_empty() {
+                let rejected =
+                    git::push_branches(&repo, &op.remote, &pushes, m.is_present("ci")).await?;
+                exit_unless_done(git::RebaseOutcome::Rejected(rejected));
             }
+
+            println!(
+                "\n{} Undid {} ({}); undo this with {}",
+                style("Done!").green().bold(),
+                op.id,
+                op.command,
+                style(format!("gh-stack undo {}", undo.id)).cyan()
+            );
+        }
+
+        ("track", Some(m)) => {
+            let repo = match m.value_of("project") {
+                Some(path) => Repository::open(path)?,
+                None => tree::detect_repo().ok_or("Not in a git repository (use -C <path>)")?,
+            };
+
+            if m.is_present("list") {
+                let tracked = store::tracked_branches(&repo);
+                if tracked.is_empty() {
+                    println!("No tracked branches.");
+                }
+                for b in tracked {
+                    match &b.stack {
+                        Some(id) => println!("{} -> {} [{}]", b.name, b.parent, id),
+                        None => println!("{} -> {}", b.name, b.parent),
+                    }
+                }
+                return Ok(());
+            }
+
+            let branch = m
+                .value_of("branch")
+                .map(String::from)
+                .or_else(|| tree::current_branch(&repo))
+                .ok_or("No branch given and HEAD is detached")?;
+
+            if m.is_present("remove") {
+                store::untrack(&repo, &branch)?;
+                println!("Stopped tracking {}", style(&branch).cyan());
+                return Ok(());
+            }
+
+            let parent = m.value_of("parent").unwrap();
+            store::track(&repo, &branch, parent, m.value_of("stack"))?;
+            println!(
+                "Tracking {} on top of {}",
+                style(&branch).cyan(),
+                style(parent).cyan()
+            );
         }
 
         ("status", Some(m)) => {
+            require_prs(m, "status")?;
             let explicit_identifier = m.value_of("identifier");
             let branch_override = m.value_of("branch");
             let show_all = m.is_present("all");
@@ -1062,20 +2376,17 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
             // Resolve repository with fallback chain
             let remote_name = m.value_of("origin").unwrap_or("origin");
-            let repository = resolve_repository(m.value_of("repository"), &repository, remote_name)
-                .unwrap_or_else(|e| panic!("{}", e));
+            let repository = stack_repository(m, &repository, remote_name)?;
+            let credentials = stack_credentials(m)?;
 
             // Determine how to find the stack
             let stack: FlatDep = if let Some(id) = explicit_identifier {
                 // === EXISTING BEHAVIOR: Search by identifier ===
                 if !json_output {
-                    println!(
-                        "Searching for {} identifier in {} repo",
-                        style(id).bold(),
-                        style(&repository).bold()
-                    );
+                    println!("{}", search_message(get_discovery(m), id, &repository));
                 }
-                build_pr_stack_for_repo(id, &repository, &credentials, get_excluded(m)).await?
+                build_pr_stack_for_repo(id, &repository, credentials.as_ref(), stack_lookup(m))
+                    .await?
             } else if show_all {
                 // === NEW: --all flag - show all stacks ===
                 if ci_mode {
@@ -1090,8 +2401,13 @@ async fn main() -> Result<(), Box<dyn Error>> {
                     println!("Discovering stacks in {}...", style(&repository).bold());
                 }
 
-                let stacks =
-                    api::stack::discover_all_stacks(&repository, &trunk, &credentials).await?;
+                let stacks = api::stack::discover_all_stacks(
+                    &repository,
+                    &get_trunks(m, &trunk),
+                    false,
+                    required(&credentials)?,
+                )
+                .await?;
 
                 if stacks.is_empty() {
                     if json_output {
@@ -1128,7 +2444,9 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 match branch {
                     Some(branch) => {
                         // Check if on trunk
-                        if identifier::is_trunk_branch(&branch, Some(&trunk)) {
+                        if identifier::is_trunk_branch(&branch, Some(&trunk))
+                            || get_trunks(m, &trunk).matches(&branch)
+                        {
                             if ci_mode {
                                 eprintln!(
                                     "{} On trunk branch '{}'. Provide an identifier or use --branch.",
@@ -1143,9 +2461,13 @@ async fn main() -> Result<(), Box<dyn Error>> {
                             }
 
                             // Discover all stacks for selection
-                            let stacks =
-                                api::stack::discover_all_stacks(&repository, &trunk, &credentials)
-                                    .await?;
+                            let stacks = api::stack::discover_all_stacks(
+                                &repository,
+                                &get_trunks(m, &trunk),
+                                false,
+                                required(&credentials)?,
+                            )
+                            .await?;
 
                             let summaries: Vec<StackSummary> = stacks
                                 .iter()
@@ -1156,16 +2478,15 @@ async fn main() -> Result<(), Box<dyn Error>> {
                                 TrunkAction::EnterIdentifier(id) => {
                                     if !json_output {
                                         println!(
-                                            "\nSearching for {} identifier in {} repo",
-                                            style(&id).bold(),
-                                            style(&repository).bold()
+                                            "\n{}",
+                                            search_message(get_discovery(m), &id, &repository)
                                         );
                                     }
                                     build_pr_stack_for_repo(
                                         &id,
                                         &repository,
-                                        &credentials,
-                                        get_excluded(m),
+                                        credentials.as_ref(),
+                                        stack_lookup(m),
                                     )
                                     .await?
                                 }
@@ -1188,15 +2509,20 @@ async fn main() -> Result<(), Box<dyn Error>> {
                                 );
                             }
 
-                            match api::stack::fetch_pr_by_head(&repository, &branch, &credentials)
-                                .await?
+                            match api::stack::fetch_pr_by_head(
+                                &repository,
+                                &branch,
+                                required(&credentials)?,
+                            )
+                            .await?
                             {
                                 Some(pr) => {
                                     let prs = api::stack::discover_stack(
                                         &repository,
                                         pr,
                                         &trunk,
-                                        &credentials,
+                                        false,
+                                        required(&credentials)?,
                                     )
                                     .await?;
                                     let prs: Vec<Rc<PullRequest>> =
@@ -1218,7 +2544,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
                                             &trunk,
                                             repo_handle.as_ref(),
                                             None, // No identifier known
-                                            &credentials,
+                                            required(&credentials)?,
                                             ci_mode,
                                         )
                                         .await?;
@@ -1230,7 +2556,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
                                         if let Some(pr) = api::stack::fetch_pr_by_head(
                                             &repository,
                                             &branch,
-                                            &credentials,
+                                            required(&credentials)?,
                                         )
                                         .await?
                                         {
@@ -1238,7 +2564,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
                                                 &repository,
                                                 pr,
                                                 &trunk,
-                                                &credentials,
+                                                false,
+                                                required(&credentials)?,
                                             )
                                             .await?;
                                             let prs: Vec<Rc<PullRequest>> =
@@ -1286,16 +2613,21 @@ async fn main() -> Result<(), Box<dyn Error>> {
                                             &trunk,
                                         )? {
                                             // Poll for PR creation
-                                            if let Some(pr) =
-                                                wait_for_pr(&repository, &branch, &credentials, 30)
-                                                    .await
+                                            if let Some(pr) = wait_for_pr(
+                                                &repository,
+                                                &branch,
+                                                required(&credentials)?,
+                                                30,
+                                            )
+                                            .await
                                             {
                                                 println!("\nRetrying stack discovery...");
                                                 let prs = api::stack::discover_stack(
                                                     &repository,
                                                     pr,
                                                     &trunk,
-                                                    &credentials,
+                                                    false,
+                                                    required(&credentials)?,
                                                 )
                                                 .await?;
                                                 let prs: Vec<Rc<PullRequest>> =
@@ -1391,17 +2723,33 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 .and_then(|p| Repository::open(p).ok())
                 .or_else(tree::detect_repo);
 
-            let entries = status::build_status_entries(
-                &stack,
-                repo.as_ref(),
-                &repository,
-                &credentials,
-                &config,
-            )
-            .await;
+            let mut groups = vec![];
+            for (name, group) in repository_groups(&stack) {
+                let local = local_repo_for(repo.as_ref(), name.as_deref(), &repository);
+                let group_repository = name.clone().unwrap_or_else(|| repository.clone());
+                let entries = status::build_status_entries(
+                    &group,
+                    local,
+                    &group_repository,
+                    required(&credentials)?,
+                    &config,
+                )
+                .await;
+                groups.push((name.map(|_| group_repository), entries));
+            }
 
             if json_output {
-                match status::render_status_json(&entries) {
+                let json = match groups.as_slice() {
+                    [(None, entries)] => status::render_status_json(entries),
+                    _ => {
+                        let groups: Vec<(String, Vec<status::StatusEntry>)> = groups
+                            .into_iter()
+                            .map(|(name, entries)| (name.unwrap_or_default(), entries))
+                            .collect();
+                        status::render_grouped_status_json(&groups)
+                    }
+                };
+                match json {
                     Ok(json) => println!("{}", json),
                     Err(e) => {
                         eprintln!(
@@ -1413,8 +2761,13 @@ async fn main() -> Result<(), Box<dyn Error>> {
                     }
                 }
             } else {
-                let output = status::render_status(&entries, &config, repo.is_some());
-                print!("{}", output);
+                for (name, entries) in &groups {
+                    if let Some(name) = name {
+                        println!("{}\n", style(name).bold());
+                    }
+                    let output = status::render_status(entries, &config, repo.is_some());
+                    print!("{}", output);
+                }
 
                 // Mark legend as seen after first display
                 if show_legend && !help_legend {
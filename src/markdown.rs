@@ -1,7 +1,7 @@
 use std::fs;
 
-use crate::api::{PullRequestReviewState, PullRequestStatus};
-use crate::graph::FlatDep;
+use crate::api::{PullRequest, PullRequestReviewState, PullRequestStatus};
+use crate::graph::{self, FlatDep};
 
 pub fn build_table(
     deps: &FlatDep,
@@ -36,7 +36,24 @@ pub fn build_table(
         out.push_str("|:--:|:------|:-----------:|\n");
     }
 
+    // GitHub links `owner/repo#N` across repositories, but `#N` only within one
+    let cross_repo = graph::split_by_repository(deps).len() > 1;
+    let pr_ref = |pr: &PullRequest| {
+        if cross_repo {
+            format!("{}#{}", pr.repository(), pr.number())
+        } else {
+            format!("#{}", pr.number())
+        }
+    };
+
     for (node, parent) in deps {
+        let node_repository = node.repository();
+        let repository = if cross_repo {
+            node_repository.as_str()
+        } else {
+            repository
+        };
+
         let review_state = if use_badges {
             let state = match node.review_state() {
                 PullRequestReviewState::APPROVED => {
@@ -107,24 +124,19 @@ pub fn build_table(
 
         let row = if use_badges {
             match parent {
-                None => format!("|#{}|{}|{}|-|\n", node.number(), node.title(), review_state),
+                None => format!("|{}|{}|{}|-|\n", pr_ref(node), node.title(), review_state),
                 Some(parent) => format!(
-                    "|#{}|{}|{}|#{}|\n",
-                    node.number(),
+                    "|{}|{}|{}|{}|\n",
+                    pr_ref(node),
                     node.title(),
                     review_state,
-                    parent.number()
+                    pr_ref(parent)
                 ),
             }
         } else {
             match parent {
-                None => format!("|#{}|{}|-|\n", node.number(), node.title()),
-                Some(parent) => format!(
-                    "|#{}|{}|#{}|\n",
-                    node.number(),
-                    node.title(),
-                    parent.number()
-                ),
+                None => format!("|{}|{}|-|\n", pr_ref(node), node.title()),
+                Some(parent) => format!("|{}|{}|{}|\n", pr_ref(node), node.title(), pr_ref(parent)),
             }
         };
 
@@ -137,7 +149,6 @@ pub fn build_table(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::api::{PullRequest, PullRequestStatus};
     use std::rc::Rc;
 
     fn make_pr(
@@ -402,4 +413,40 @@ mod tests {
         let table = build_table(&deps, "BADGES-MIXED", None, "org/repo", true);
         insta::assert_snapshot!(table);
     }
+
+    #[test]
+    fn test_build_table_cross_repo_links() {
+        let service = Rc::new(
+            PullRequest::new_for_test(
+                12,
+                "feature-1",
+                "main",
+                "[JIRA-123] Add endpoint",
+                PullRequestStatus::Open,
+                false,
+                None,
+                vec![],
+            )
+            .in_repository("org/service"),
+        );
+        let client = Rc::new(
+            PullRequest::new_for_test(
+                3,
+                "feature-1",
+                "main",
+                "[JIRA-123] Call endpoint",
+                PullRequestStatus::Open,
+                false,
+                None,
+                vec![],
+            )
+            .in_repository("org/client"),
+        );
+        let deps: FlatDep = graph::log(&graph::build(&[service, client]));
+
+        let table = build_table(&deps, "JIRA-123", None, "org/service", false);
+        assert!(table.contains("|org/client#3|"));
+        assert!(table.contains("|org/service#12|"));
+        assert!(!table.contains("|#"));
+    }
 }
@@ -0,0 +1,226 @@
+//! Signing the commits autorebase writes, as `git commit -S` would
+//!
+//! When `commit.gpgsign` is set, each replayed commit is signed by the
+//! program `gpg.format` calls for: gpg for `openpgp` (the default), gpgsm
+//! for `x509` and ssh-keygen for `ssh`, with the key in `user.signingkey`.
+//! `gpg.program` and `gpg.<format>.program` override the program, as they do
+//! for git.
+
+use git2::{Config, Repository};
+use std::error::Error;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// The kinds of signature `gpg.format` can ask for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    OpenPgp,
+    X509,
+    Ssh,
+}
+
+/// Signs commits with the user's configured key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signer {
+    pub format: Format,
+    /// The program that makes the signature
+    pub program: String,
+    /// `user.signingkey`; for gpg, the committer's identity if that's unset
+    pub key: String,
+}
+
+fn config_string(config: &Config, name: &str) -> Option<String> {
+    config.get_string(name).ok().filter(|s| !s.is_empty())
+}
+
+impl Signer {
+    /// The signer `commit.gpgsign` asks for in `repo`, if it's set
+    pub fn from_repo(repo: &Repository) -> Result<Option<Signer>, Box<dyn Error>> {
+        let config = repo.config()?.snapshot()?;
+        if !config.get_bool("commit.gpgsign").unwrap_or(false) {
+            return Ok(None);
+        }
+
+        let (format, name, default_program) = match config_string(&config, "gpg.format").as_deref()
+        {
+            None | Some("openpgp") => (Format::OpenPgp, "openpgp", "gpg"),
+            Some("x509") => (Format::X509, "x509", "gpgsm"),
+            Some("ssh") => (Format::Ssh, "ssh", "ssh-keygen"),
+            Some(other) => {
+                return Err(format!("Unsupported gpg.format '{}'", other).into());
+            }
+        };
+
+        let program = config_string(&config, &format!("gpg.{}.program", name))
+            .or_else(|| match format {
+                Format::OpenPgp => config_string(&config, "gpg.program"),
+                _ => None,
+            })
+            .unwrap_or_else(|| default_program.to_string());
+
+        let key = match (config_string(&config, "user.signingkey"), format) {
+            (Some(key), _) => key,
+            (None, Format::Ssh) => {
+                return Err(
+                    "commit.gpgsign is set with gpg.format=ssh, but user.signingkey isn't".into(),
+                );
+            }
+            (None, _) => {
+                let committer = repo.signature()?;
+                format!(
+                    "{} <{}>",
+                    committer.name().unwrap_or_default(),
+                    committer.email().unwrap_or_default()
+                )
+            }
+        };
+
+        Ok(Some(Signer {
+            format,
+            program,
+            key,
+        }))
+    }
+
+    /// Detached signature of `buffer`, the contents of an unsigned commit
+    pub fn sign(&self, buffer: &[u8]) -> Result<String, Box<dyn Error>> {
+        match self.format {
+            Format::OpenPgp | Format::X509 => self.sign_with_gpg(buffer),
+            Format::Ssh => self.sign_with_ssh(buffer),
+        }
+    }
+
+    fn sign_with_gpg(&self, buffer: &[u8]) -> Result<String, Box<dyn Error>> {
+        let (signature, status) = run(
+            Command::new(&self.program).args(["--status-fd=2", "-bsau", &self.key]),
+            buffer,
+        )?;
+        // gpg can exit cleanly without signing anything
+        if !status.contains("[GNUPG:] SIG_CREATED ") {
+            return Err(format!("{} didn't sign the commit:\n{}", self.program, status).into());
+        }
+        Ok(signature)
+    }
+
+    fn sign_with_ssh(&self, buffer: &[u8]) -> Result<String, Box<dyn Error>> {
+        let mut command = Command::new(&self.program);
+        command.args(["-Y", "sign", "-n", "git"]);
+
+        // A literal public key means the private half is in ssh-agent;
+        // ssh-keygen wants it in a file all the same
+        let literal = self
+            .key
+            .strip_prefix("key::")
+            .or_else(|| self.key.starts_with("ssh-").then_some(self.key.as_str()));
+        let key_file = match literal {
+            Some(key) => {
+                let path = std::env::temp_dir()
+                    .join(format!("gh-stack-signing-key-{}.pub", std::process::id()));
+                std::fs::write(&path, key)?;
+                command.arg("-U");
+                Some(path)
+            }
+            None => None,
+        };
+        let path = key_file.clone().unwrap_or_else(|| expand_home(&self.key));
+        command.arg("-f").arg(&path);
+
+        let result = run(&mut command, buffer);
+        if let Some(path) = key_file {
+            let _ = std::fs::remove_file(path);
+        }
+        let (signature, _) = result?;
+        Ok(signature)
+    }
+}
+
+/// `path` with a leading `~/` swapped for the home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Run `command` with `input` on stdin, returning its stdout and stderr
+fn run(command: &mut Command, input: &[u8]) -> Result<(String, String), Box<dyn Error>> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Couldn't run {} to sign the commit: {}", program, e))?;
+    child
+        .stdin
+        .take()
+        .ok_or("Couldn't write to the signing program")?
+        .write_all(input)?;
+    let output = child.wait_with_output()?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    if !output.status.success() || stdout.is_empty() {
+        return Err(format!("{} failed to sign the commit:\n{}", program, stderr.trim()).into());
+    }
+    Ok((stdout, stderr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::init_repo;
+
+    #[test]
+    fn test_unsigned_without_gpgsign() {
+        let (_dir, repo) = init_repo();
+        assert_eq!(Signer::from_repo(&repo).unwrap(), None);
+
+        let mut config = repo.config().unwrap();
+        config.set_bool("commit.gpgsign", false).unwrap();
+        config.set_str("user.signingkey", "ABCD").unwrap();
+        assert_eq!(Signer::from_repo(&repo).unwrap(), None);
+    }
+
+    #[test]
+    fn test_gpg_defaults_to_committer_identity() {
+        let (_dir, repo) = init_repo();
+        let mut config = repo.config().unwrap();
+        config.set_bool("commit.gpgsign", true).unwrap();
+        assert_eq!(
+            Signer::from_repo(&repo).unwrap(),
+            Some(Signer {
+                format: Format::OpenPgp,
+                program: "gpg".to_string(),
+                key: "Committer <committer@example.com>".to_string(),
+            })
+        );
+
+        config.set_str("gpg.program", "gpg2").unwrap();
+        config.set_str("user.signingkey", "ABCD").unwrap();
+        let signer = Signer::from_repo(&repo).unwrap().unwrap();
+        assert_eq!(signer.program, "gpg2");
+        assert_eq!(signer.key, "ABCD");
+    }
+
+    #[test]
+    fn test_ssh_format() {
+        let (_dir, repo) = init_repo();
+        let mut config = repo.config().unwrap();
+        config.set_bool("commit.gpgsign", true).unwrap();
+        config.set_str("gpg.format", "ssh").unwrap();
+        config.set_str("gpg.program", "gpg2").unwrap();
+        assert!(Signer::from_repo(&repo).is_err());
+
+        config
+            .set_str("user.signingkey", "~/.ssh/id_ed25519.pub")
+            .unwrap();
+        let signer = Signer::from_repo(&repo).unwrap().unwrap();
+        assert_eq!(signer.format, Format::Ssh);
+        assert_eq!(signer.program, "ssh-keygen");
+
+        config.set_str("gpg.format", "pkcs11").unwrap();
+        assert!(Signer::from_repo(&repo).is_err());
+    }
+}
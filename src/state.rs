@@ -0,0 +1,126 @@
+//! Local gh-stack state stored under `.git/gh-stack/`
+//!
+//! Multi-step commands record their progress here so that an interrupted
+//! run can be resumed or rolled back later.
+
+use git2::Repository;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the state directory inside the git directory
+const STATE_DIR: &str = "gh-stack";
+
+/// Directory holding gh-stack state for a repository
+///
+/// Uses the common git directory so linked worktrees share the same state.
+pub fn state_dir(repo: &Repository) -> PathBuf {
+    common_dir(repo).join(STATE_DIR)
+}
+
+/// The repository's common git directory
+///
+/// For a linked worktree, `repo.path()` is `.git/worktrees/<name>/`, and the
+/// `commondir` file inside it points back at the main `.git` directory.
+fn common_dir(repo: &Repository) -> PathBuf {
+    let git_dir = repo.path();
+
+    if repo.is_worktree() {
+        if let Ok(contents) = fs::read_to_string(git_dir.join("commondir")) {
+            return git_dir.join(contents.trim());
+        }
+    }
+
+    git_dir.to_path_buf()
+}
+
+/// Read a JSON state file, returning `None` if it doesn't exist
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, Box<dyn Error>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let contents = fs::read_to_string(path)?;
+    Ok(Some(serde_json::from_str(&contents)?))
+}
+
+/// Write a JSON state file, creating its parent directory if needed
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // Write to a temporary file first so a crash never leaves a truncated file
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(value)?)?;
+    fs::rename(&tmp, path)?;
+
+    Ok(())
+}
+
+/// Remove a state file (no-op if it doesn't exist)
+pub fn remove(path: &Path) -> Result<(), Box<dyn Error>> {
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Sample {
+        name: String,
+        count: usize,
+    }
+
+    #[test]
+    fn test_read_json_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let result: Option<Sample> = read_json(&dir.path().join("missing.json")).unwrap();
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_write_then_read_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("sample.json");
+        let sample = Sample {
+            name: "stack".to_string(),
+            count: 3,
+        };
+
+        write_json(&path, &sample).unwrap();
+        let loaded: Option<Sample> = read_json(&path).unwrap();
+
+        assert_eq!(loaded, Some(sample));
+        assert!(!path.with_extension("tmp").exists());
+    }
+
+    #[test]
+    fn test_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sample.json");
+        fs::write(&path, "{}").unwrap();
+
+        remove(&path).unwrap();
+        assert!(!path.exists());
+
+        // Removing again is fine
+        remove(&path).unwrap();
+    }
+
+    #[test]
+    fn test_state_dir_inside_git_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+
+        let state = state_dir(&repo);
+        assert!(state.ends_with(".git/gh-stack/") || state.ends_with(".git/gh-stack"));
+    }
+}
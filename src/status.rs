@@ -19,8 +19,8 @@ use crate::api::checks::{fetch_check_status, fetch_mergeable_status, CheckState,
 use crate::api::{PullRequest, PullRequestReviewState};
 use crate::graph::FlatDep;
 use crate::tree::{
-    branch_exists_locally, commits_for_branch, current_branch, format_relative_time,
-    parse_timestamp, CommitInfo,
+    branch_exists_locally, commits_for_branch, current_branch, format_relative_time, graph_layout,
+    parent_indices, parse_timestamp, place_in_tree, CommitInfo, GraphSymbols,
 };
 use crate::Credentials;
 
@@ -100,6 +100,15 @@ pub struct StatusEntry {
     pub commits: Vec<CommitInfo>,
     #[serde(skip_serializing_if = "is_zero")]
     pub extra_commits: usize,
+    /// Number of PRs between this one and trunk
+    #[serde(skip_serializing_if = "is_zero")]
+    pub depth: usize,
+    /// Branch this entry is drawn on top of (`None` means the entry below it)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    /// Number of other entries sharing this entry's parent
+    #[serde(skip_serializing_if = "is_zero")]
+    pub siblings: usize,
 }
 
 fn is_zero(n: &usize) -> bool {
@@ -131,10 +140,18 @@ impl Default for StatusConfig {
 /// JSON output structure
 #[derive(Debug, Serialize)]
 pub struct StatusOutput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
     pub stack: Vec<StatusEntry>,
     pub trunk: String,
 }
 
+/// JSON output for a stack spanning several repositories
+#[derive(Debug, Serialize)]
+pub struct GroupedStatusOutput {
+    pub repositories: Vec<StatusOutput>,
+}
+
 /// Get the path to the legend seen file
 fn legend_file_path() -> Option<PathBuf> {
     dirs::home_dir().map(|h| h.join(LEGEND_FILE_NAME))
@@ -200,8 +217,19 @@ fn mergeable_to_bit(mergeable: Option<bool>) -> StatusBit {
 /// Compute stack clear status for a PR at given index
 /// A PR is "stack clear" if all PRs below it are approved and not draft
 fn compute_stack_clear(entries: &[StatusEntry], index: usize) -> StatusBit {
-    // Check all entries below this one (higher indices = lower in stack)
-    for entry in entries.iter().skip(index + 1) {
+    let rows: Vec<(&str, Option<&str>)> = entries
+        .iter()
+        .map(|e| (e.branch.as_str(), e.parent.as_deref()))
+        .collect();
+    let parents = parent_indices(&rows);
+
+    // Check every ancestor of this entry; PRs on other branches of a fork
+    // don't block it
+    let mut below = parents[index];
+    while let Some(i) = below {
+        below = parents[i];
+        let entry = &entries[i];
+
         if entry.is_trunk {
             continue;
         }
@@ -282,6 +310,13 @@ pub async fn build_status_entries(
     // Get trunk branch from first PR's base
     let trunk_branch = stack.first().map(|(pr, _)| pr.base().to_string());
 
+    let open: Vec<usize> = stack
+        .iter()
+        .filter(|(pr, _)| !pr.is_merged() && pr.state() != &crate::api::PullRequestStatus::Closed)
+        .map(|(pr, _)| pr.number())
+        .collect();
+    let placements = place_in_tree(stack, &open, trunk_branch.as_deref().unwrap_or("main"));
+
     // Collect PR data (non-async operations)
     let pr_data: Vec<PrCheckData> = stack
         .iter()
@@ -336,6 +371,8 @@ pub async fn build_status_entries(
                 stack_clear: StatusBit::Pending, // Will be computed after all entries are built
             });
 
+            let placement = &placements[&data.pr.number()];
+
             StatusEntry {
                 branch: data.pr.head().to_string(),
                 pr_number: Some(data.pr.number()),
@@ -347,6 +384,9 @@ pub async fn build_status_entries(
                 updated_at: timestamp.map(|t| t.to_rfc3339()),
                 commits: data.commits,
                 extra_commits: data.extra_commits,
+                depth: placement.depth,
+                parent: Some(placement.parent.clone()),
+                siblings: placement.siblings,
             }
         })
         .collect();
@@ -376,6 +416,9 @@ pub async fn build_status_entries(
             updated_at: None,
             commits: vec![],
             extra_commits: 0,
+            depth: 0,
+            parent: None,
+            siblings: 0,
         });
     }
 
@@ -429,14 +472,27 @@ pub fn render_status(entries: &[StatusEntry], config: &StatusConfig, has_repo: b
     let mut out = String::new();
 
     // Symbols
-    let (current_node, other_node, pipe) = if config.use_unicode {
-        ("\u{25C9}", "\u{25EF}", "\u{2502}")
+    let (current_node, other_node) = if config.use_unicode {
+        ("\u{25C9}", "\u{25EF}")
     } else {
-        ("*", "o", "|")
+        ("*", "o")
     };
+    let graph = GraphSymbols::new(config.use_unicode);
+
+    let rows: Vec<(&str, Option<&str>)> = entries
+        .iter()
+        .map(|e| (e.branch.as_str(), e.parent.as_deref()))
+        .collect();
+    let layout = graph_layout(&parent_indices(&rows));
 
     for (i, entry) in entries.iter().enumerate() {
         let is_last = i == entries.len() - 1;
+        let row = &layout[i];
+        let lanes = graph.lanes(row.column);
+
+        if row.joins > 0 {
+            out.push_str(&format!("{}\n", graph.join_line(row)));
+        }
 
         // Node symbol
         let node = if entry.is_current {
@@ -471,10 +527,10 @@ pub fn render_status(entries: &[StatusEntry], config: &StatusConfig, has_repo: b
             branch_display = format!("{} (draft)", branch_display);
         }
 
-        out.push_str(&format!("{} {}\n", node, branch_display));
+        out.push_str(&format!("{}{} {}\n", lanes, node, branch_display));
 
         // Connector for content below
-        let connector = if is_last { " " } else { pipe };
+        let connector = format!("{}{}", lanes, if row.continues { graph.pipe } else { " " });
 
         // Status bits (if available)
         if let Some(status) = &entry.status {
@@ -541,7 +597,7 @@ pub fn render_status(entries: &[StatusEntry], config: &StatusConfig, has_repo: b
 
         // Empty line before next entry (except last)
         if !is_last {
-            out.push_str(&format!("{}\n", pipe));
+            out.push_str(&format!("{}\n", graph.separator(row)));
         }
     }
 
@@ -590,6 +646,23 @@ fn colorize_status_bits(status: &PrStatus, use_unicode: bool) -> String {
 
 /// Render status entries as JSON
 pub fn render_status_json(entries: &[StatusEntry]) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(&status_output(entries, None))
+}
+
+/// Render status entries for each repository of a multi-repo stack as JSON
+pub fn render_grouped_status_json(
+    groups: &[(String, Vec<StatusEntry>)],
+) -> Result<String, serde_json::Error> {
+    let output = GroupedStatusOutput {
+        repositories: groups
+            .iter()
+            .map(|(repository, entries)| status_output(entries, Some(repository.clone())))
+            .collect(),
+    };
+    serde_json::to_string_pretty(&output)
+}
+
+fn status_output(entries: &[StatusEntry], repository: Option<String>) -> StatusOutput {
     let trunk = entries
         .iter()
         .find(|e| e.is_trunk)
@@ -598,8 +671,11 @@ pub fn render_status_json(entries: &[StatusEntry]) -> Result<String, serde_json:
 
     let stack: Vec<StatusEntry> = entries.iter().filter(|e| !e.is_trunk).cloned().collect();
 
-    let output = StatusOutput { stack, trunk };
-    serde_json::to_string_pretty(&output)
+    StatusOutput {
+        repository,
+        stack,
+        trunk,
+    }
 }
 
 #[cfg(test)]
@@ -839,6 +915,9 @@ mod tests {
             updated_at: None,
             commits: vec![],
             extra_commits: 0,
+            depth: 0,
+            parent: None,
+            siblings: 0,
         }
     }
 
@@ -859,6 +938,9 @@ mod tests {
                 updated_at: None,
                 commits: vec![],
                 extra_commits: 0,
+                depth: 0,
+                parent: None,
+                siblings: 0,
             },
         ];
 
@@ -883,6 +965,9 @@ mod tests {
                 updated_at: None,
                 commits: vec![],
                 extra_commits: 0,
+                depth: 0,
+                parent: None,
+                siblings: 0,
             },
         ];
 
@@ -906,6 +991,9 @@ mod tests {
                 updated_at: None,
                 commits: vec![],
                 extra_commits: 0,
+                depth: 0,
+                parent: None,
+                siblings: 0,
             },
         ];
 
@@ -928,6 +1016,9 @@ mod tests {
                 updated_at: None,
                 commits: vec![],
                 extra_commits: 0,
+                depth: 0,
+                parent: None,
+                siblings: 0,
             },
         ];
 
@@ -1000,6 +1091,9 @@ mod tests {
                 updated_at: None,
                 commits: vec![],
                 extra_commits: 0,
+                depth: 0,
+                parent: None,
+                siblings: 0,
             },
             StatusEntry {
                 branch: "main".to_string(),
@@ -1012,6 +1106,9 @@ mod tests {
                 updated_at: None,
                 commits: vec![],
                 extra_commits: 0,
+                depth: 0,
+                parent: None,
+                siblings: 0,
             },
         ];
 
@@ -1019,6 +1116,17 @@ mod tests {
         assert!(json.contains("\"trunk\": \"main\""));
         assert!(json.contains("\"branch\": \"feature\""));
         assert!(json.contains("\"pr_number\": 123"));
+        assert!(!json.contains("repository"));
+
+        let grouped = render_grouped_status_json(&[
+            ("org/service".to_string(), entries.clone()),
+            ("org/client".to_string(), entries),
+        ])
+        .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&grouped).unwrap();
+        assert_eq!(value["repositories"][1]["repository"], "org/client");
+        assert_eq!(value["repositories"][0]["stack"][0]["pr_number"], 123);
+        assert_eq!(value["repositories"][0]["trunk"], "main");
     }
 
     #[test]
@@ -1039,6 +1147,9 @@ mod tests {
             updated_at: None,
             commits: vec![],
             extra_commits: 0,
+            depth: 0,
+            parent: None,
+            siblings: 0,
         }];
 
         let json = render_status_json(&entries).unwrap();
@@ -1061,6 +1172,9 @@ mod tests {
             updated_at: None,
             commits: vec![],
             extra_commits: 0,
+            depth: 0,
+            parent: None,
+            siblings: 0,
         }];
 
         let json = render_status_json(&entries).unwrap();
@@ -1090,6 +1204,9 @@ mod tests {
             updated_at: None,
             commits: vec![],
             extra_commits: 0,
+            depth: 0,
+            parent: None,
+            siblings: 0,
         }
     }
 
@@ -1303,6 +1420,9 @@ mod tests {
                     },
                 ],
                 extra_commits: 2,
+                depth: 0,
+                parent: None,
+                siblings: 0,
             },
             make_test_entry("main", None, None, false, false, true, None),
         ];
@@ -1397,6 +1517,9 @@ mod tests {
                     message: "Add widget".to_string(),
                 }],
                 extra_commits: 0,
+                depth: 0,
+                parent: None,
+                siblings: 0,
             },
             StatusEntry {
                 branch: "main".to_string(),
@@ -1409,6 +1532,9 @@ mod tests {
                 updated_at: None,
                 commits: vec![],
                 extra_commits: 0,
+                depth: 0,
+                parent: None,
+                siblings: 0,
             },
         ];
 
@@ -1447,4 +1573,73 @@ mod tests {
         let output = render_status(&entries, &config, true);
         insta::assert_snapshot!(output);
     }
+
+    fn make_forked_status_entries() -> Vec<StatusEntry> {
+        let status = |approved: StatusBit| {
+            Some(PrStatus {
+                ci: StatusBit::Passed,
+                approved,
+                mergeable: StatusBit::Passed,
+                stack_clear: StatusBit::Passed,
+            })
+        };
+        let entry = |branch: &str, number: usize, parent: &str, approved: StatusBit| {
+            let mut entry = make_test_entry(
+                branch,
+                Some(number),
+                Some("Part"),
+                false,
+                false,
+                false,
+                status(approved),
+            );
+            entry.parent = Some(parent.to_string());
+            entry
+        };
+
+        vec![
+            entry("feature-2b", 4, "feature-1", StatusBit::Failed),
+            entry("feature-3a", 3, "feature-2a", StatusBit::Passed),
+            entry("feature-2a", 2, "feature-1", StatusBit::Passed),
+            entry("feature-1", 1, "main", StatusBit::Passed),
+            make_test_entry("main", None, None, false, false, true, None),
+        ]
+    }
+
+    #[test]
+    fn test_compute_stack_clear_ignores_other_branches() {
+        let entries = make_forked_status_entries();
+
+        // #4 isn't approved, but it's on another branch of the fork
+        assert_eq!(compute_stack_clear(&entries, 1), StatusBit::Passed);
+        assert_eq!(compute_stack_clear(&entries, 0), StatusBit::Failed);
+    }
+
+    #[test]
+    fn test_snapshot_status_forked_unicode() {
+        let config = StatusConfig {
+            use_color: false,
+            use_unicode: true,
+            show_legend: false,
+            include_checks: true,
+            json_output: false,
+        };
+
+        let output = render_status(&make_forked_status_entries(), &config, true);
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_snapshot_status_forked_ascii() {
+        let config = StatusConfig {
+            use_color: false,
+            use_unicode: false,
+            show_legend: false,
+            include_checks: true,
+            json_output: false,
+        };
+
+        let output = render_status(&make_forked_status_entries(), &config, true);
+        insta::assert_snapshot!(output);
+    }
 }
@@ -0,0 +1,496 @@
+//! Local stack metadata stored in git config
+//!
+//! Each tracked branch records its parent branch and, optionally, the stack
+//! it belongs to:
+//!
+//! ```text
+//! [branch "feat/part-2"]
+//!     gh-stack-parent = feat/part-1
+//!     gh-stack-id = STACK-123
+//! ```
+//!
+//! This lets stacks be discovered and restacked before any PR exists, and
+//! without asking GitHub. Branches that were never tracked get a parent
+//! inferred from commit ancestry instead.
+
+use git2::{BranchType, Config, Oid, Repository};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+use crate::api::PullRequest;
+
+const PARENT_KEY: &str = "gh-stack-parent";
+const STACK_KEY: &str = "gh-stack-id";
+
+/// A branch with a parent link in the local store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackedBranch {
+    pub name: String,
+    pub parent: String,
+    pub stack: Option<String>,
+}
+
+fn key(branch: &str, name: &str) -> String {
+    format!("branch.{}.{}", branch, name)
+}
+
+/// Record `branch` as stacked on `parent`, optionally as part of `stack`
+///
+/// Without a stack, the branch joins its parent's stack if it has one.
+pub fn track(
+    repo: &Repository,
+    branch: &str,
+    parent: &str,
+    stack: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    if branch == parent {
+        return Err(format!("Branch '{}' can't be its own parent", branch).into());
+    }
+
+    let mut config = repo.config()?;
+    let stack = match stack {
+        Some(stack) => Some(stack.to_string()),
+        None => config.get_string(&key(parent, STACK_KEY)).ok(),
+    };
+
+    config.set_str(&key(branch, PARENT_KEY), parent)?;
+    if let Some(stack) = stack {
+        config.set_str(&key(branch, STACK_KEY), &stack)?;
+    }
+
+    Ok(())
+}
+
+/// Forget everything recorded about `branch` (no-op if untracked)
+pub fn untrack(repo: &Repository, branch: &str) -> Result<(), Box<dyn Error>> {
+    let mut config = repo.config()?;
+    for name in [PARENT_KEY, STACK_KEY] {
+        match config.remove(&key(branch, name)) {
+            Ok(()) => {}
+            Err(e) if e.code() == git2::ErrorCode::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// The recorded parent of `branch`, if it is tracked
+pub fn parent_of(repo: &Repository, branch: &str) -> Option<String> {
+    repo.config()
+        .ok()?
+        .get_string(&key(branch, PARENT_KEY))
+        .ok()
+}
+
+/// Every tracked branch, sorted by name
+pub fn tracked_branches(repo: &Repository) -> Vec<TrackedBranch> {
+    let config = match repo.config().and_then(|mut c| c.snapshot()) {
+        Ok(config) => config,
+        Err(_) => return vec![],
+    };
+    read_tracked(&config)
+}
+
+fn read_tracked(config: &Config) -> Vec<TrackedBranch> {
+    let suffix = format!(".{}", PARENT_KEY);
+    let mut parents: HashMap<String, String> = HashMap::new();
+    let mut stacks: HashMap<String, String> = HashMap::new();
+
+    if let Ok(mut entries) = config.entries(Some(r"^branch\..*\.gh-stack-")) {
+        while let Some(Ok(entry)) = entries.next() {
+            let (Some(name), Some(value)) = (entry.name(), entry.value()) else {
+                continue;
+            };
+            let Some(rest) = name.strip_prefix("branch.") else {
+                continue;
+            };
+            if let Some(branch) = rest.strip_suffix(&suffix) {
+                parents.insert(branch.to_string(), value.to_string());
+            } else if let Some(branch) = rest.strip_suffix(&format!(".{}", STACK_KEY)) {
+                stacks.insert(branch.to_string(), value.to_string());
+            }
+        }
+    }
+
+    let mut tracked: Vec<TrackedBranch> = parents
+        .into_iter()
+        .map(|(name, parent)| TrackedBranch {
+            stack: stacks.get(&name).cloned(),
+            name,
+            parent,
+        })
+        .collect();
+    tracked.sort_by(|a, b| a.name.cmp(&b.name));
+    tracked
+}
+
+/// Branches of the stack named by `identifier`
+///
+/// `identifier` is either a stack ID recorded with the branches, or the name
+/// of any tracked branch, in which case every branch linked to it through
+/// parent links is returned.
+pub fn stack_branches(repo: &Repository, identifier: &str) -> Vec<TrackedBranch> {
+    select_stack(tracked_branches(repo), identifier)
+}
+
+fn select_stack(tracked: Vec<TrackedBranch>, identifier: &str) -> Vec<TrackedBranch> {
+    let by_id: Vec<TrackedBranch> = tracked
+        .iter()
+        .filter(|b| b.stack.as_deref() == Some(identifier))
+        .cloned()
+        .collect();
+    if !by_id.is_empty() {
+        return by_id;
+    }
+
+    // Walk parent links in both directions from the named branch, stopping at
+    // untracked branches such as trunk so other stacks aren't pulled in
+    let names: HashSet<&str> = tracked.iter().map(|b| b.name.as_str()).collect();
+    let mut members: HashSet<&str> = HashSet::new();
+    let mut queue = vec![identifier];
+    while let Some(branch) = queue.pop() {
+        if !names.contains(branch) || !members.insert(branch) {
+            continue;
+        }
+        for b in &tracked {
+            if b.name == branch {
+                queue.push(&b.parent);
+            } else if b.parent == branch {
+                queue.push(&b.name);
+            }
+        }
+    }
+
+    tracked
+        .iter()
+        .filter(|b| members.contains(b.name.as_str()))
+        .cloned()
+        .collect()
+}
+
+/// How far down a branch's history to look for its parent
+const MAX_ANCESTRY_WALK: usize = 1000;
+
+/// Tips of the local trunk branch and of each remote's trunk branch
+fn trunk_tips(repo: &Repository, trunk: &str) -> Vec<Oid> {
+    let mut names = vec![format!("refs/heads/{}", trunk)];
+    if let Ok(remotes) = repo.remotes() {
+        names.extend(
+            remotes
+                .iter()
+                .flatten()
+                .map(|remote| format!("refs/remotes/{}/{}", remote, trunk)),
+        );
+    }
+    names
+        .iter()
+        .filter_map(|name| repo.find_reference(name).ok())
+        .filter_map(|reference| reference.peel_to_commit().ok())
+        .map(|commit| commit.id())
+        .collect()
+}
+
+/// Every local branch with commits above trunk, with its parent
+///
+/// A branch's recorded parent wins. Otherwise its parent is the nearest
+/// other such branch on its first-parent history, or trunk if it gets there
+/// first. Branches at the same commit are never each other's parent, and
+/// the alphabetically first wins when several share the parent's commit.
+pub fn inferred_branches(
+    repo: &Repository,
+    trunk: &str,
+) -> Result<Vec<TrackedBranch>, Box<dyn Error>> {
+    let trunks = trunk_tips(repo, trunk);
+    let in_trunk = |commit: Oid| -> Result<bool, git2::Error> {
+        for tip in &trunks {
+            if *tip == commit || repo.graph_descendant_of(*tip, commit)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    };
+
+    let mut tips: Vec<(String, Oid)> = vec![];
+    for branch in repo.branches(Some(BranchType::Local))? {
+        let (branch, _) = branch?;
+        let (Some(name), Some(tip)) = (branch.name()?, branch.get().target()) else {
+            continue;
+        };
+        if name != trunk && !in_trunk(tip)? {
+            tips.push((name.to_string(), tip));
+        }
+    }
+    tips.sort();
+
+    let recorded = tracked_branches(repo);
+    let mut inferred = vec![];
+    for (name, tip) in &tips {
+        let record = recorded.iter().find(|b| &b.name == name);
+        let parent = match record {
+            Some(b) => b.parent.clone(),
+            None => {
+                let mut parent = trunk.to_string();
+                let mut commit = repo.find_commit(*tip)?;
+                for _ in 0..MAX_ANCESTRY_WALK {
+                    let Ok(next) = commit.parent(0) else {
+                        break;
+                    };
+                    if in_trunk(next.id())? {
+                        break;
+                    }
+                    if let Some((below, _)) = tips.iter().find(|(_, t)| *t == next.id()) {
+                        parent = below.clone();
+                        break;
+                    }
+                    commit = next;
+                }
+                parent
+            }
+        };
+
+        inferred.push(TrackedBranch {
+            name: name.clone(),
+            parent,
+            stack: record.and_then(|b| b.stack.clone()),
+        });
+    }
+
+    Ok(inferred)
+}
+
+/// Branches of the stack named by `identifier`, inferred from ancestry
+///
+/// `identifier` is a recorded stack ID or the name of any branch in the stack.
+pub fn inferred_stack(
+    repo: &Repository,
+    trunk: &str,
+    identifier: &str,
+) -> Result<Vec<TrackedBranch>, Box<dyn Error>> {
+    Ok(select_stack(inferred_branches(repo, trunk)?, identifier))
+}
+
+/// Stand-in PRs for local branches, for a stack found without GitHub
+///
+/// Each is titled after its branch's latest commit, and records where
+/// `remote` has the branch as its head SHA.
+pub fn unopened_prs(
+    repo: &Repository,
+    branches: &[TrackedBranch],
+    remote: &str,
+) -> Vec<PullRequest> {
+    branches
+        .iter()
+        .enumerate()
+        .map(|(i, b)| {
+            let title = repo
+                .revparse_single(&format!("refs/heads/{}", b.name))
+                .and_then(|o| o.peel_to_commit())
+                .map(|c| c.summary().unwrap_or_default().to_string())
+                .unwrap_or_default();
+            let remote_sha = repo
+                .refname_to_id(&format!("refs/remotes/{}/{}", remote, b.name))
+                .map(|oid| oid.to_string())
+                .unwrap_or_default();
+            PullRequest::unopened(i + 1, &b.name, &b.parent, &remote_sha, &title)
+        })
+        .collect()
+}
+
+/// Point each PR at its recorded parent instead of its GitHub base
+pub fn apply_parents(prs: Vec<PullRequest>, tracked: &[TrackedBranch]) -> Vec<PullRequest> {
+    prs.into_iter()
+        .map(|pr| match tracked.iter().find(|b| b.name == pr.head()) {
+            Some(b) if b.parent != pr.base() => pr.with_base(&b.parent),
+            _ => pr,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::PullRequestStatus;
+    use crate::test_support::{branch, commit, init_repo};
+
+    fn tracked(name: &str, parent: &str, stack: Option<&str>) -> TrackedBranch {
+        TrackedBranch {
+            name: name.to_string(),
+            parent: parent.to_string(),
+            stack: stack.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_track_and_read_back() {
+        let (_dir, repo) = init_repo();
+
+        track(&repo, "feat/part-1", "main", Some("STACK-1")).unwrap();
+        track(&repo, "feat/part-2", "feat/part-1", None).unwrap();
+
+        assert_eq!(
+            parent_of(&repo, "feat/part-2").as_deref(),
+            Some("feat/part-1")
+        );
+        assert_eq!(
+            tracked_branches(&repo),
+            vec![
+                tracked("feat/part-1", "main", Some("STACK-1")),
+                tracked("feat/part-2", "feat/part-1", Some("STACK-1")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_track_rejects_self_parent() {
+        let (_dir, repo) = init_repo();
+        assert!(track(&repo, "feature", "feature", None).is_err());
+    }
+
+    #[test]
+    fn test_untrack() {
+        let (_dir, repo) = init_repo();
+
+        track(&repo, "feature", "main", Some("STACK-1")).unwrap();
+        untrack(&repo, "feature").unwrap();
+        assert!(tracked_branches(&repo).is_empty());
+
+        // Untracking again is fine
+        untrack(&repo, "feature").unwrap();
+    }
+
+    #[test]
+    fn test_select_stack_by_id() {
+        let all = vec![
+            tracked("a", "main", Some("S1")),
+            tracked("b", "a", Some("S1")),
+            tracked("c", "main", Some("S2")),
+        ];
+
+        let names: Vec<String> = select_stack(all, "S1")
+            .into_iter()
+            .map(|b| b.name)
+            .collect();
+        assert_eq!(names, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_select_stack_by_branch_follows_links() {
+        let all = vec![
+            tracked("a", "main", None),
+            tracked("b", "a", None),
+            tracked("c", "b", None),
+            tracked("d", "a", None),
+            tracked("other", "main", None),
+        ];
+
+        let names: Vec<String> = select_stack(all, "b").into_iter().map(|b| b.name).collect();
+        assert_eq!(names, vec!["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn test_apply_parents_overrides_base() {
+        let pr = PullRequest::new_for_test(
+            1,
+            "b",
+            "main",
+            "PR 1",
+            PullRequestStatus::Open,
+            false,
+            None,
+            vec![],
+        );
+
+        let prs = apply_parents(vec![pr], &[tracked("b", "a", None)]);
+        assert_eq!(prs[0].base(), "a");
+    }
+
+    #[test]
+    fn test_inferred_branches_follow_ancestry() {
+        let (_dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[], "root");
+        let landed = commit(&repo, &[root], &[], "landed");
+        let trunk = commit(&repo, &[landed], &[], "trunk moves");
+        let one = commit(&repo, &[root], &[], "part 1");
+        let two_a = commit(&repo, &[one], &[], "part 2a");
+        let two = commit(&repo, &[two_a], &[], "part 2b");
+        let other = commit(&repo, &[root], &[], "unrelated");
+        branch(&repo, "main", trunk);
+        branch(&repo, "part-1", one);
+        branch(&repo, "part-2", two);
+        branch(&repo, "other", other);
+        branch(&repo, "merged", landed);
+
+        let names = |branches: Vec<TrackedBranch>| -> Vec<(String, String)> {
+            branches.into_iter().map(|b| (b.name, b.parent)).collect()
+        };
+        let pairs = |list: &[(&str, &str)]| -> Vec<(String, String)> {
+            list.iter()
+                .map(|(n, p)| (n.to_string(), p.to_string()))
+                .collect()
+        };
+
+        assert_eq!(
+            names(inferred_branches(&repo, "main").unwrap()),
+            pairs(&[("other", "main"), ("part-1", "main"), ("part-2", "part-1")])
+        );
+        assert_eq!(
+            names(inferred_stack(&repo, "main", "part-2").unwrap()),
+            pairs(&[("part-1", "main"), ("part-2", "part-1")])
+        );
+
+        // A recorded parent wins over ancestry
+        track(&repo, "part-2", "other", None).unwrap();
+        assert_eq!(
+            names(inferred_branches(&repo, "main").unwrap()),
+            pairs(&[("other", "main"), ("part-1", "main"), ("part-2", "other")])
+        );
+    }
+
+    #[test]
+    fn test_trunk_tips_match_the_trunk_exactly() {
+        let (_dir, repo) = init_repo();
+        repo.remote("origin", "https://github.com/owner/repo.git")
+            .unwrap();
+        let root = commit(&repo, &[], &[], "root");
+        let trunk = commit(&repo, &[root], &[], "trunk moves");
+        let feature = commit(&repo, &[root], &[], "feature");
+        branch(&repo, "main", root);
+        repo.reference("refs/remotes/origin/main", trunk, true, "test")
+            .unwrap();
+        // Someone else's `feature/main`, not origin's trunk
+        repo.reference("refs/remotes/origin/feature/main", feature, true, "test")
+            .unwrap();
+        repo.reference("refs/remotes/gone/main", feature, true, "test")
+            .unwrap();
+
+        assert_eq!(trunk_tips(&repo, "main"), vec![root, trunk]);
+    }
+
+    #[test]
+    fn test_unopened_prs() {
+        let (_dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[], "root");
+        let one = commit(&repo, &[root], &[], "Add the first part");
+        let two = commit(&repo, &[one], &[], "Add the second part");
+        branch(&repo, "main", root);
+        branch(&repo, "part-1", one);
+        branch(&repo, "part-2", two);
+        repo.reference("refs/remotes/origin/part-1", root, true, "test")
+            .unwrap();
+
+        let prs = unopened_prs(
+            &repo,
+            &[
+                tracked("part-1", "main", None),
+                tracked("part-2", "part-1", None),
+            ],
+            "origin",
+        );
+        assert_eq!(prs.len(), 2);
+        assert!(prs.iter().all(|pr| pr.is_unopened()));
+        assert_eq!(prs[0].title(), "Add the first part");
+        assert_eq!(prs[0].head_sha(), root.to_string());
+        assert_eq!(prs[1].base(), "part-1");
+        assert_eq!(prs[1].head_sha(), "");
+    }
+}
@@ -0,0 +1,521 @@
+//! `gh-stack sync`: bring local stacks up to date after PRs land
+//!
+//! For each stack, the open PRs are restacked with autorebase onto wherever
+//! their merged parents went, then the merged PRs' branches are pruned and
+//! the PRs above them retargeted, as `land --cleanup` does.
+
+use git2::{BranchType, Repository};
+use std::collections::HashMap;
+use std::error::Error;
+
+use crate::api::{PullRequest, PullRequestStatus};
+use crate::backup;
+use crate::git::{self, BranchUpdate, RebaseOutcome};
+use crate::graph::FlatDep;
+use crate::land::{self, CleanupPlan, OutputFormat};
+use crate::Credentials;
+
+/// How syncing one stack ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncOutcome {
+    /// Restacked (or already up to date) and pushed
+    Synced,
+    /// The restack stopped at a conflict, left for `autorebase --continue`
+    Conflict {
+        branch: String,
+        commit: String,
+        summary: String,
+    },
+    /// The remote refused these branches' pushes
+    Rejected(Vec<String>),
+    /// Not synced, for this reason
+    Skipped(String),
+}
+
+/// What syncing did to one stack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackSync {
+    /// The stack's bottom branch
+    pub name: String,
+    pub outcome: SyncOutcome,
+    /// Local branches the restack moved
+    pub moved: Vec<String>,
+    /// Merged branches deleted locally or on the remote
+    pub pruned: Vec<String>,
+}
+
+impl StackSync {
+    pub fn skipped(name: &str, reason: &str) -> Self {
+        StackSync {
+            name: name.to_string(),
+            outcome: SyncOutcome::Skipped(reason.to_string()),
+            moved: vec![],
+            pruned: vec![],
+        }
+    }
+
+    /// Whether the user has something left to do
+    pub fn needs_attention(&self) -> bool {
+        matches!(
+            self.outcome,
+            SyncOutcome::Conflict { .. } | SyncOutcome::Rejected(_)
+        )
+    }
+}
+
+fn open_prs(stack: &FlatDep) -> Vec<&PullRequest> {
+    stack
+        .iter()
+        .map(|(pr, _)| pr.as_ref())
+        .filter(|pr| *pr.state() == PullRequestStatus::Open)
+        .collect()
+}
+
+fn has_local_branch(repo: &Repository, branch: &str) -> bool {
+    repo.find_branch(branch, BranchType::Local).is_ok()
+}
+
+/// The first open PR that isn't built on the open PR before it
+///
+/// autorebase replays a stack as a single line, so in a stack that forks,
+/// above an open PR or a merged one, a branch would be rebuilt on its sibling.
+fn fork(stack: &FlatDep) -> Option<&PullRequest> {
+    open_prs(stack)
+        .windows(2)
+        .find(|pair| git::landed_base(stack, pair[1]) != pair[0].head())
+        .map(|pair| pair[1])
+}
+
+/// The branch a stack is known by: its bottom open PR, or its bottom PR
+pub fn stack_name(stack: &FlatDep) -> String {
+    open_prs(stack)
+        .first()
+        .map(|pr| pr.head())
+        .or_else(|| stack.first().map(|(pr, _)| pr.head()))
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Stacks with at least one open PR whose branch exists locally
+pub fn local_stacks(repo: &Repository, stacks: Vec<FlatDep>) -> Vec<FlatDep> {
+    stacks
+        .into_iter()
+        .filter(|stack| {
+            open_prs(stack)
+                .iter()
+                .any(|pr| has_local_branch(repo, pr.head()))
+        })
+        .collect()
+}
+
+/// Branches of merged PRs to prune, and the open PRs based on them to
+/// retarget onto where they were merged
+pub fn merged_cleanup(stack: &FlatDep, repository: &str) -> Option<CleanupPlan> {
+    let merged: Vec<String> = stack
+        .iter()
+        .filter(|(pr, _)| pr.is_merged())
+        .map(|(pr, _)| pr.head().to_string())
+        .collect();
+    if merged.is_empty() {
+        return None;
+    }
+
+    let orphans: Vec<_> = stack
+        .iter()
+        .filter(|(pr, _)| *pr.state() == PullRequestStatus::Open)
+        .filter(|(pr, _)| merged.iter().any(|head| head == pr.base()))
+        .collect();
+    let target_branch = match orphans.first() {
+        Some((pr, _)) => git::landed_base(stack, pr).to_string(),
+        None => stack
+            .iter()
+            .find(|(pr, _)| pr.is_merged())
+            .map(|(pr, _)| git::landed_base(stack, pr).to_string())?,
+    };
+
+    Some(CleanupPlan {
+        retarget: orphans
+            .iter()
+            .filter(|(pr, _)| git::landed_base(stack, pr) == target_branch)
+            .map(|(pr, _)| pr.clone())
+            .collect(),
+        branches_to_delete: merged,
+        target_branch,
+        repository: repository.to_string(),
+    })
+}
+
+fn local_heads(repo: &Repository, stack: &FlatDep) -> HashMap<String, Option<git2::Oid>> {
+    stack
+        .iter()
+        .map(|(pr, _)| {
+            let head = repo
+                .find_branch(pr.head(), BranchType::Local)
+                .ok()
+                .and_then(|b| b.get().target());
+            (pr.head().to_string(), head)
+        })
+        .collect()
+}
+
+/// Restack one stack onto trunk, then prune its merged branches
+///
+/// Needs every open PR's branch locally, and skips stacks that fork. When
+/// the restack stops at a conflict, nothing is pruned, so `sync` can simply
+/// be run again once the autorebase is finished. Nor is anything pruned or retargeted when a push
+/// is rejected: the PRs above a merged branch would show its commits until
+/// their rebuilt branches reach the remote.
+pub async fn sync_stack(
+    stack: FlatDep,
+    repo: &Repository,
+    remote: &str,
+    repository: &str,
+    ci: bool,
+    credentials: &Credentials,
+) -> Result<StackSync, Box<dyn Error>> {
+    let name = stack_name(&stack);
+    if let Some(pr) = open_prs(&stack)
+        .into_iter()
+        .find(|pr| !has_local_branch(repo, pr.head()))
+    {
+        return Ok(StackSync::skipped(
+            &name,
+            &format!("{} isn't a local branch", pr.head()),
+        ));
+    }
+    if let Some(pr) = fork(&stack) {
+        return Ok(StackSync::skipped(
+            &name,
+            &format!(
+                "it forks at #{}, and sync only restacks a single line of PRs",
+                pr.number()
+            ),
+        ));
+    }
+
+    let before = local_heads(repo, &stack);
+    let mut outcome = SyncOutcome::Synced;
+    if !open_prs(&stack).is_empty() {
+        match git::perform_rebase(stack.clone(), repo, remote, None, ci, false).await? {
+            RebaseOutcome::Done => {}
+            RebaseOutcome::Rejected(rejected) if rejected.is_empty() => {}
+            RebaseOutcome::Rejected(rejected) => {
+                outcome = SyncOutcome::Rejected(rejected.into_iter().map(|r| r.branch).collect());
+            }
+            RebaseOutcome::Conflict {
+                branch,
+                commit,
+                summary,
+                ..
+            } => {
+                return Ok(StackSync {
+                    name,
+                    outcome: SyncOutcome::Conflict {
+                        branch,
+                        commit,
+                        summary,
+                    },
+                    moved: vec![],
+                    pruned: vec![],
+                });
+            }
+        }
+    }
+
+    let after = local_heads(repo, &stack);
+    let moved = stack
+        .iter()
+        .map(|(pr, _)| pr.head().to_string())
+        .filter(|head| before.get(head) != after.get(head))
+        .collect();
+
+    let mut pruned: Vec<String> = vec![];
+    let pushed = !matches!(outcome, SyncOutcome::Rejected(_));
+    if let Some(cleanup) = merged_cleanup(&stack, repository).filter(|_| pushed) {
+        backup::record(repo, "sync", remote, &cleanup.branches_to_delete)?;
+        let result = land::execute_cleanup(
+            &cleanup,
+            Some(repo),
+            remote,
+            OutputFormat::Text,
+            credentials,
+        )
+        .await?;
+        for branch in result
+            .deleted_remote
+            .into_iter()
+            .chain(result.deleted_local)
+        {
+            if !pruned.contains(&branch) {
+                pruned.push(branch);
+            }
+        }
+    }
+
+    Ok(StackSync {
+        name,
+        outcome,
+        moved,
+        pruned,
+    })
+}
+
+/// Plain-text summary of a sync
+pub fn render_summary(trunk: &str, update: &BranchUpdate, stacks: &[StackSync]) -> String {
+    let short = |sha: &str| sha[..7.min(sha.len())].to_string();
+    let mut out = match update {
+        BranchUpdate::UpToDate => format!("{}: up to date\n", trunk),
+        BranchUpdate::FastForwarded(from, to) => {
+            format!("{}: fast-forwarded {}..{}\n", trunk, short(from), short(to))
+        }
+        BranchUpdate::Diverged => format!(
+            "{}: has local commits that aren't on the remote; left alone\n",
+            trunk
+        ),
+        BranchUpdate::Missing => format!("{}: no local branch to update\n", trunk),
+    };
+
+    if stacks.is_empty() {
+        out.push_str("\nNo local stacks to sync.\n");
+    }
+
+    for stack in stacks {
+        out.push_str(&format!("\n{}\n", stack.name));
+        if !stack.moved.is_empty() {
+            out.push_str(&format!("  ✓ restacked: {}\n", stack.moved.join(", ")));
+        }
+        if !stack.pruned.is_empty() {
+            out.push_str(&format!("  ✓ pruned: {}\n", stack.pruned.join(", ")));
+        }
+        match &stack.outcome {
+            SyncOutcome::Synced if stack.moved.is_empty() && stack.pruned.is_empty() => {
+                out.push_str("  = already up to date\n");
+            }
+            SyncOutcome::Synced => {}
+            SyncOutcome::Conflict {
+                branch,
+                commit,
+                summary,
+            } => {
+                out.push_str(&format!(
+                    "  ✗ conflict on {} at {} {}\n    resolve it, run `gh-stack autorebase --continue`, then sync again\n",
+                    branch,
+                    short(commit),
+                    summary
+                ));
+            }
+            SyncOutcome::Rejected(branches) => {
+                out.push_str(&format!(
+                    "  ✗ push rejected: {} (changed on the remote since the fetch)\n    merged branches are kept until it's pushed; sort it out, then sync again\n",
+                    branches.join(", ")
+                ));
+            }
+            SyncOutcome::Skipped(reason) => {
+                out.push_str(&format!("  - skipped: {}\n", reason));
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::BranchPush;
+    use crate::test_support::{branch, commit, deps, init_repo, pr};
+    use insta::assert_snapshot;
+
+    #[test]
+    fn test_merged_cleanup_retargets_onto_landed_base() {
+        let stack = deps(vec![
+            pr(1, "part-1", "main", true),
+            pr(2, "part-2", "part-1", false),
+            pr(3, "part-3", "part-2", false),
+        ]);
+
+        let cleanup = merged_cleanup(&stack, "owner/repo").unwrap();
+        assert_eq!(cleanup.target_branch, "main");
+        assert_eq!(cleanup.branches_to_delete, vec!["part-1"]);
+        let retarget: Vec<usize> = cleanup.retarget.iter().map(|pr| pr.number()).collect();
+        assert_eq!(retarget, vec![2]);
+    }
+
+    #[test]
+    fn test_merged_cleanup_without_merged_prs() {
+        let stack = deps(vec![
+            pr(1, "part-1", "main", false),
+            pr(2, "part-2", "part-1", false),
+        ]);
+        assert!(merged_cleanup(&stack, "owner/repo").is_none());
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn test_rejected_push_skips_cleanup() {
+        let (_dir, repo) = init_repo();
+        let remote_dir = tempfile::tempdir().unwrap();
+        let remote = Repository::init_bare(remote_dir.path()).unwrap();
+        repo.remote("origin", remote_dir.path().to_str().unwrap())
+            .unwrap();
+
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        let one = commit(&repo, &[root], &[("b", "1")], "part 1");
+        let two = commit(&repo, &[one], &[("c", "1")], "part 2");
+        // part-1 was squash-merged
+        let trunk = commit(&repo, &[root], &[("b", "1")], "Part 1 (#1)");
+        for (name, at) in [("main", trunk), ("part-1", one), ("part-2", two)] {
+            branch(&repo, name, at);
+        }
+        let initial: Vec<BranchPush> = [("main", trunk), ("part-1", one), ("part-2", two)]
+            .iter()
+            .map(|(name, at)| BranchPush {
+                branch: name.to_string(),
+                sha: at.to_string(),
+                expected: String::new(),
+            })
+            .collect();
+        assert!(git::push_branches(&repo, "origin", &initial, true)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // A teammate pushes to part-2 after the fetch
+        let theirs = commit(&remote, &[two], &[("c", "theirs")], "theirs");
+        remote
+            .reference("refs/heads/part-2", theirs, true, "test")
+            .unwrap();
+
+        // Nothing may be retargeted or deleted on GitHub
+        let mut server = mockito::Server::new_async().await;
+        let mut untouched = vec![];
+        for method in ["GET", "PATCH", "DELETE"] {
+            untouched.push(
+                server
+                    .mock(method, mockito::Matcher::Any)
+                    .expect(0)
+                    .create_async()
+                    .await,
+            );
+        }
+        std::env::set_var("GITHUB_API_BASE", server.url());
+
+        let stack = deps(vec![
+            pr(1, "part-1", "main", true),
+            pr(2, "part-2", "part-1", false),
+        ]);
+        let creds = crate::Credentials::new("test-token");
+        let sync = sync_stack(stack, &repo, "origin", "owner/repo", true, &creds)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            sync.outcome,
+            SyncOutcome::Rejected(vec!["part-2".to_string()])
+        );
+        assert!(sync.pruned.is_empty());
+        assert!(has_local_branch(&repo, "part-1"));
+        assert_eq!(remote.refname_to_id("refs/heads/part-2").unwrap(), theirs);
+        for mock in untouched {
+            mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forked_stack_is_skipped() {
+        let (_dir, repo) = init_repo();
+        let root = commit(&repo, &[], &[("a", "1")], "root");
+        for name in ["part-2", "part-3", "part-4"] {
+            branch(&repo, name, root);
+        }
+        let creds = crate::Credentials::new("test-token");
+
+        // Siblings above a merged PR
+        let stack = deps(vec![
+            pr(1, "part-1", "main", true),
+            pr(2, "part-2", "part-1", false),
+            pr(4, "part-4", "part-1", false),
+        ]);
+        assert_eq!(fork(&stack).map(|pr| pr.number()), Some(4));
+        let sync = sync_stack(stack, &repo, "origin", "owner/repo", true, &creds)
+            .await
+            .unwrap();
+        assert_eq!(
+            sync.outcome,
+            SyncOutcome::Skipped(
+                "it forks at #4, and sync only restacks a single line of PRs".to_string()
+            )
+        );
+
+        // Siblings above an open PR
+        let stack = deps(vec![
+            pr(2, "part-2", "main", false),
+            pr(3, "part-3", "part-2", false),
+            pr(4, "part-4", "part-2", false),
+        ]);
+        assert_eq!(fork(&stack).map(|pr| pr.number()), Some(4));
+
+        // A merged PR in the middle of a line isn't a fork
+        let stack = deps(vec![
+            pr(2, "part-2", "main", false),
+            pr(3, "part-3", "part-2", true),
+            pr(4, "part-4", "part-3", false),
+        ]);
+        assert!(fork(&stack).is_none());
+    }
+
+    #[test]
+    fn test_stack_name_skips_merged_prs() {
+        let stack = deps(vec![
+            pr(1, "part-1", "main", true),
+            pr(2, "part-2", "part-1", false),
+        ]);
+        assert_eq!(stack_name(&stack), "part-2");
+
+        let landed = deps(vec![pr(1, "part-1", "main", true)]);
+        assert_eq!(stack_name(&landed), "part-1");
+    }
+
+    #[test]
+    fn test_render_summary() {
+        let stacks = vec![
+            StackSync {
+                name: "feat/part-2".to_string(),
+                outcome: SyncOutcome::Synced,
+                moved: vec!["feat/part-2".to_string(), "feat/part-3".to_string()],
+                pruned: vec!["feat/part-1".to_string()],
+            },
+            StackSync {
+                name: "fix/typo".to_string(),
+                outcome: SyncOutcome::Synced,
+                moved: vec![],
+                pruned: vec![],
+            },
+            StackSync {
+                name: "feat/api".to_string(),
+                outcome: SyncOutcome::Conflict {
+                    branch: "feat/api-2".to_string(),
+                    commit: "0123456789abcdef".to_string(),
+                    summary: "Rework API".to_string(),
+                },
+                moved: vec![],
+                pruned: vec![],
+            },
+            StackSync {
+                name: "feat/ui".to_string(),
+                outcome: SyncOutcome::Rejected(vec!["feat/ui".to_string()]),
+                moved: vec!["feat/ui".to_string()],
+                pruned: vec![],
+            },
+            StackSync::skipped("feat/docs", "resolve the conflict above first"),
+        ];
+        let update = BranchUpdate::FastForwarded(
+            "aaaaaaaaaaaaaaaa".to_string(),
+            "bbbbbbbbbbbbbbbb".to_string(),
+        );
+
+        assert_snapshot!(render_summary("main", &update, &stacks));
+        assert!(stacks[2].needs_attention());
+        assert!(!stacks[0].needs_attention());
+    }
+}
@@ -0,0 +1,64 @@
+//! Fixtures the unit tests share: scratch repositories, commits and stacks
+
+use crate::api::{PullRequest, PullRequestStatus};
+use crate::graph::FlatDep;
+use git2::{Commit, Oid, Repository, Signature, Time};
+use std::rc::Rc;
+
+/// A fresh repository in a temporary directory, with a committer configured
+pub fn init_repo() -> (tempfile::TempDir, Repository) {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+    let mut config = repo.config().unwrap();
+    config.set_str("user.name", "Committer").unwrap();
+    config
+        .set_str("user.email", "committer@example.com")
+        .unwrap();
+    (dir, repo)
+}
+
+pub fn author() -> Signature<'static> {
+    Signature::new(
+        "Author",
+        "author@example.com",
+        &Time::new(1_600_000_000, 60),
+    )
+    .unwrap()
+}
+
+/// Commit `files` (name, contents) on top of `parents` without touching the working tree
+pub fn commit(repo: &Repository, parents: &[Oid], files: &[(&str, &str)], message: &str) -> Oid {
+    let parents: Vec<Commit> = parents
+        .iter()
+        .map(|p| repo.find_commit(*p).unwrap())
+        .collect();
+    let base = parents.first().map(|p| p.tree().unwrap());
+    let mut builder = repo.treebuilder(base.as_ref()).unwrap();
+    for (name, contents) in files {
+        let blob = repo.blob(contents.as_bytes()).unwrap();
+        builder.insert(name, blob, 0o100644).unwrap();
+    }
+    let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+    let parents: Vec<&Commit> = parents.iter().collect();
+    repo.commit(None, &author(), &author(), message, &tree, &parents)
+        .unwrap()
+}
+
+pub fn branch(repo: &Repository, name: &str, at: Oid) {
+    repo.branch(name, &repo.find_commit(at).unwrap(), true)
+        .unwrap();
+}
+
+pub fn pr(number: usize, head: &str, base: &str, merged: bool) -> PullRequest {
+    let state = if merged {
+        PullRequestStatus::Closed
+    } else {
+        PullRequestStatus::Open
+    };
+    let merged_at = merged.then(|| "2024-01-01T00:00:00Z".to_string());
+    PullRequest::new_for_test(number, head, base, "PR", state, false, merged_at, vec![])
+}
+
+pub fn deps(prs: Vec<PullRequest>) -> FlatDep {
+    prs.into_iter().map(|pr| (Rc::new(pr), None)).collect()
+}
@@ -4,9 +4,11 @@
 use crate::api::pull_request::PullRequestStatus;
 use crate::api::PullRequest;
 use crate::graph::FlatDep;
+use crate::store::TrackedBranch;
 use chrono::{DateTime, Utc};
 use console::style;
 use git2::{Repository, Sort};
+use std::collections::HashMap;
 use std::io::IsTerminal;
 use std::rc::Rc;
 
@@ -42,6 +44,12 @@ pub struct StackEntry {
     pub timestamp: Option<DateTime<Utc>>,
     pub commits: Vec<CommitInfo>,
     pub extra_commits: usize,
+    /// Number of PRs between this one and trunk
+    pub depth: usize,
+    /// Branch this entry is drawn on top of (`None` means the entry below it)
+    pub parent: Option<String>,
+    /// Number of other entries sharing this entry's parent
+    pub siblings: usize,
 }
 
 /// State of a PR in the stack
@@ -267,6 +275,199 @@ fn truncate(s: &str, max: usize) -> String {
     }
 }
 
+/// Where a displayed PR sits in the tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    /// Head branch of the nearest displayed ancestor, or trunk
+    pub parent: String,
+    /// Number of displayed ancestors
+    pub depth: usize,
+    /// Number of other displayed PRs with the same parent
+    pub siblings: usize,
+}
+
+/// Place the displayed PRs (`shown`, by number) of a stack in its tree
+///
+/// PRs that are hidden (e.g. closed ones) are skipped over, so a PR whose
+/// parent is hidden hangs off its nearest displayed ancestor instead.
+pub fn place_in_tree(stack: &FlatDep, shown: &[usize], trunk: &str) -> HashMap<usize, Placement> {
+    let parent_of = |number: usize| -> Option<&Rc<PullRequest>> {
+        stack
+            .iter()
+            .find(|(pr, _)| pr.number() == number)
+            .and_then(|(_, parent)| parent.as_ref())
+    };
+
+    let mut placements: HashMap<usize, Placement> = HashMap::new();
+    for &number in shown {
+        let mut parent = trunk.to_string();
+        let mut depth = 0;
+        let mut ancestor = parent_of(number);
+
+        // Bounded by the stack size so a cycle can't loop forever
+        for _ in 0..stack.len() {
+            match ancestor {
+                Some(pr) if pr.number() == number => break,
+                Some(pr) => {
+                    if shown.contains(&pr.number()) {
+                        if depth == 0 {
+                            parent = pr.head().to_string();
+                        }
+                        depth += 1;
+                    }
+                    ancestor = parent_of(pr.number());
+                }
+                None => break,
+            }
+        }
+
+        placements.insert(
+            number,
+            Placement {
+                parent,
+                depth,
+                siblings: 0,
+            },
+        );
+    }
+
+    let parents: Vec<String> = placements.values().map(|p| p.parent.clone()).collect();
+    for placement in placements.values_mut() {
+        placement.siblings = parents.iter().filter(|p| **p == placement.parent).count() - 1;
+    }
+
+    placements
+}
+
+/// Position of one entry in a `git log --graph`-style drawing
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphRow {
+    /// Column the entry's node is drawn in
+    pub column: usize,
+    /// Number of extra lanes that join the node's lane just above it
+    pub joins: usize,
+    /// Whether the node's lane continues down to its parent
+    pub continues: bool,
+    /// Number of lanes open below the entry
+    pub lanes_after: usize,
+}
+
+/// Index of each entry's parent in a top-first list of `(branch, parent)` pairs
+///
+/// An entry without a recorded parent sits on the entry below it, so plain
+/// lists stay linear. A parent that isn't listed below falls back to the
+/// last entry (trunk).
+pub fn parent_indices(rows: &[(&str, Option<&str>)]) -> Vec<Option<usize>> {
+    rows.iter()
+        .enumerate()
+        .map(|(i, (_, parent))| {
+            let below = i + 1 < rows.len();
+            match parent {
+                Some(parent) => rows
+                    .iter()
+                    .enumerate()
+                    .skip(i + 1)
+                    .find(|(_, (branch, _))| branch == parent)
+                    .map(|(j, _)| j)
+                    .or(below.then(|| rows.len() - 1)),
+                None => below.then_some(i + 1),
+            }
+        })
+        .collect()
+}
+
+/// Lay out a top-first list of entries as lanes
+///
+/// Each open lane waits for the entry its last node is based on. An entry
+/// nothing is waiting for opens a new lane on the right (a branch tip); an
+/// entry several lanes wait for joins them into the leftmost one (a fork).
+pub fn graph_layout(parents: &[Option<usize>]) -> Vec<GraphRow> {
+    let mut lanes: Vec<usize> = Vec::new();
+    let mut rows = Vec::with_capacity(parents.len());
+
+    for (i, parent) in parents.iter().enumerate() {
+        let waiting: Vec<usize> = (0..lanes.len()).filter(|&l| lanes[l] == i).collect();
+
+        let (column, joins) = match waiting.first() {
+            Some(&first) => {
+                for &lane in waiting[1..].iter().rev() {
+                    lanes.remove(lane);
+                }
+                (first, waiting.len() - 1)
+            }
+            None => {
+                lanes.push(i);
+                (lanes.len() - 1, 0)
+            }
+        };
+
+        match parent {
+            Some(p) => lanes[column] = *p,
+            None => {
+                lanes.remove(column);
+            }
+        }
+
+        rows.push(GraphRow {
+            column,
+            joins,
+            continues: parent.is_some(),
+            lanes_after: lanes.len(),
+        });
+    }
+
+    rows
+}
+
+/// Lane symbols for drawing the graph
+pub struct GraphSymbols {
+    pub pipe: &'static str,
+    fork: &'static str,
+    join: &'static str,
+    join_end: &'static str,
+}
+
+impl GraphSymbols {
+    pub fn new(use_unicode: bool) -> Self {
+        if use_unicode {
+            GraphSymbols {
+                pipe: "\u{2502}",
+                fork: "\u{251C}",
+                join: "\u{2500}\u{2534}",
+                join_end: "\u{2500}\u{256F}",
+            }
+        } else {
+            GraphSymbols {
+                pipe: "|",
+                fork: "|",
+                join: "-+",
+                join_end: "-'",
+            }
+        }
+    }
+
+    /// The open lanes to the left of a node in `column`
+    pub fn lanes(&self, column: usize) -> String {
+        format!("{} ", self.pipe).repeat(column)
+    }
+
+    /// The connector line joining lanes into the node of `row`
+    pub fn join_line(&self, row: &GraphRow) -> String {
+        format!(
+            "{}{}{}{}",
+            self.lanes(row.column),
+            self.fork,
+            self.join.repeat(row.joins - 1),
+            self.join_end
+        )
+    }
+
+    /// The line separating an entry from the next one
+    pub fn separator(&self, row: &GraphRow) -> String {
+        vec![self.pipe; row.lanes_after].join(" ")
+    }
+}
+
 /// Build stack entries from FlatDep, enriching with local git info if available
 /// Filters out closed/merged PRs unless include_closed is true AND branch exists locally
 pub fn build_entries(
@@ -281,22 +482,28 @@ pub fn build_entries(
     let trunk_branch = stack.first().map(|(pr, _)| pr.base().to_string());
 
     // Process PRs in reverse order (top of stack first)
-    for (pr, _parent) in stack.iter().rev() {
+    let shown: Vec<&Rc<PullRequest>> = stack
+        .iter()
+        .rev()
+        .map(|(pr, _)| pr)
+        .filter(|pr| {
+            let pr_state = determine_pr_state(pr);
+            let closed = pr_state == PrState::Closed || pr_state == PrState::Merged;
+
+            // Filter closed/merged PRs unless include_closed is set;
+            // if it is, still filter if branch doesn't exist locally
+            !closed
+                || (config.include_closed
+                    && repo.is_none_or(|r| branch_exists_locally(r, pr.head())))
+        })
+        .collect();
+
+    let numbers: Vec<usize> = shown.iter().map(|pr| pr.number()).collect();
+    let placements = place_in_tree(stack, &numbers, trunk_branch.as_deref().unwrap_or("main"));
+
+    for pr in shown {
         let pr_state = determine_pr_state(pr);
-
-        // Filter closed/merged PRs unless include_closed is set
-        if !config.include_closed && (pr_state == PrState::Closed || pr_state == PrState::Merged) {
-            continue;
-        }
-
-        // If include_closed is set, still filter if branch doesn't exist locally
-        if config.include_closed && (pr_state == PrState::Closed || pr_state == PrState::Merged) {
-            if let Some(r) = repo {
-                if !branch_exists_locally(r, pr.head()) {
-                    continue;
-                }
-            }
-        }
+        let placement = &placements[&pr.number()];
 
         let is_current = current.as_ref().is_some_and(|c| c == pr.head());
         let timestamp = pr.updated_at().and_then(parse_timestamp);
@@ -321,6 +528,9 @@ pub fn build_entries(
             timestamp,
             commits,
             extra_commits,
+            depth: placement.depth,
+            parent: Some(placement.parent.clone()),
+            siblings: placement.siblings,
         });
     }
 
@@ -350,12 +560,131 @@ pub fn build_entries(
             timestamp,
             commits: vec![],
             extra_commits: 0,
+            depth: 0,
+            parent: None,
+            siblings: 0,
         });
     }
 
     entries
 }
 
+/// Add tracked local branches that have no PR yet to the entries
+///
+/// Entries are reordered so each one is still drawn above its parent, with
+/// the trunk entry last. Without any PRs, trunk is the parent of the lowest
+/// tracked branch.
+pub fn add_local_branches(
+    mut entries: Vec<StackEntry>,
+    branches: &[TrackedBranch],
+    repo: Option<&Repository>,
+) -> Vec<StackEntry> {
+    let missing: Vec<&TrackedBranch> = branches
+        .iter()
+        .filter(|b| !entries.iter().any(|e| e.branch == b.name))
+        .collect();
+    if missing.is_empty() {
+        return entries;
+    }
+
+    let current = repo.and_then(current_branch);
+    let trunk = match entries.last() {
+        Some(e) if e.is_trunk => entries.pop(),
+        _ => None,
+    };
+
+    // Bottom of the stack first, so PR entries keep their relative order
+    entries.reverse();
+    for b in missing {
+        let (commits, extra_commits) = match repo {
+            Some(r) if branch_exists_locally(r, &b.name) => {
+                commits_for_branch(r, &b.name, &b.parent)
+            }
+            _ => (vec![], 0),
+        };
+
+        entries.push(StackEntry {
+            branch: b.name.clone(),
+            is_current: current.as_deref() == Some(b.name.as_str()),
+            is_trunk: false,
+            pr: None,
+            pr_state: PrState::NoPr,
+            timestamp: None,
+            commits,
+            extra_commits,
+            depth: 0,
+            parent: Some(b.parent.clone()),
+            siblings: 0,
+        });
+    }
+
+    // Depth-first from the roots, children in their current order
+    let index_of = |branch: &str| entries.iter().position(|e| e.branch == branch);
+    let parents: Vec<Option<usize>> = entries
+        .iter()
+        .map(|e| e.parent.as_deref().and_then(index_of))
+        .collect();
+    let mut order = Vec::with_capacity(entries.len());
+    let mut stack: Vec<usize> = (0..entries.len())
+        .filter(|&i| parents[i].is_none())
+        .rev()
+        .collect();
+    while let Some(i) = stack.pop() {
+        if order.contains(&i) {
+            continue;
+        }
+        order.push(i);
+        stack.extend((0..entries.len()).filter(|&c| parents[c] == Some(i)).rev());
+    }
+    // Anything left is part of a cycle; keep it rather than drop it
+    order.extend(
+        (0..entries.len())
+            .filter(|i| !order.contains(i))
+            .collect::<Vec<_>>(),
+    );
+
+    let mut depths = vec![0; entries.len()];
+    for &i in &order {
+        depths[i] = parents[i].map_or(0, |p| depths[p] + 1);
+    }
+
+    let trunk = trunk.or_else(|| {
+        let root = order.first().and_then(|&i| entries[i].parent.clone())?;
+        Some(StackEntry {
+            is_current: current.as_ref() == Some(&root),
+            branch: root,
+            is_trunk: true,
+            pr: None,
+            pr_state: PrState::NoPr,
+            timestamp: None,
+            commits: vec![],
+            extra_commits: 0,
+            depth: 0,
+            parent: None,
+            siblings: 0,
+        })
+    });
+
+    let mut slots: Vec<Option<StackEntry>> = entries.into_iter().map(Some).collect();
+    let mut sorted: Vec<StackEntry> = order
+        .iter()
+        .rev()
+        .map(|&i| {
+            let mut entry = slots[i].take().unwrap();
+            entry.depth = depths[i];
+            entry
+        })
+        .collect();
+
+    let parent_names: Vec<Option<String>> = sorted.iter().map(|e| e.parent.clone()).collect();
+    for entry in &mut sorted {
+        entry.siblings = parent_names.iter().filter(|p| **p == entry.parent).count() - 1;
+    }
+
+    sorted.extend(trunk);
+    sorted
+}
+
 /// Determine PR state from PullRequest
 fn determine_pr_state(pr: &PullRequest) -> PrState {
     if pr.is_merged() {
@@ -374,14 +703,27 @@ pub fn render(entries: &[StackEntry], config: &TreeConfig, has_repo: bool) -> St
     let mut out = String::new();
 
     // Symbols based on config
-    let (current_node, other_node, pipe) = if config.use_unicode {
-        ("\u{25C9}", "\u{25EF}", "\u{2502}")
+    let (current_node, other_node) = if config.use_unicode {
+        ("\u{25C9}", "\u{25EF}")
     } else {
-        ("*", "o", "|")
+        ("*", "o")
     };
+    let graph = GraphSymbols::new(config.use_unicode);
+
+    let rows: Vec<(&str, Option<&str>)> = entries
+        .iter()
+        .map(|e| (e.branch.as_str(), e.parent.as_deref()))
+        .collect();
+    let layout = graph_layout(&parent_indices(&rows));
 
     for (i, entry) in entries.iter().enumerate() {
         let is_last = i == entries.len() - 1;
+        let row = &layout[i];
+        let lanes = graph.lanes(row.column);
+
+        if row.joins > 0 {
+            out.push_str(&format!("{}\n", graph.join_line(row)));
+        }
 
         // Node symbol
         let node = if entry.is_current {
@@ -399,10 +741,10 @@ pub fn render(entries: &[StackEntry], config: &TreeConfig, has_repo: bool) -> St
         // Branch name + styling for closed/merged
         let branch_display = format_branch(entry, config);
 
-        out.push_str(&format!("{} {}\n", node, branch_display));
+        out.push_str(&format!("{}{} {}\n", lanes, node, branch_display));
 
         // Connector for content below
-        let connector = if is_last { " " } else { pipe };
+        let connector = format!("{}{}", lanes, if row.continues { graph.pipe } else { " " });
 
         // Timestamp line
         if let Some(ts) = &entry.timestamp {
@@ -442,7 +784,7 @@ pub fn render(entries: &[StackEntry], config: &TreeConfig, has_repo: bool) -> St
 
         // Empty line before next entry (except last)
         if !is_last {
-            out.push_str(&format!("{}\n", pipe));
+            out.push_str(&format!("{}\n", graph.separator(row)));
         }
     }
 
@@ -695,6 +1037,9 @@ mod tests {
             timestamp,
             commits,
             extra_commits,
+            depth: 0,
+            parent: None,
+            siblings: 0,
         }
     }
 
@@ -1326,4 +1671,280 @@ mod tests {
             Some("First commit".to_string())
         );
     }
+
+    fn make_open_pr(number: usize, head: &str, base: &str) -> Rc<PullRequest> {
+        Rc::new(PullRequest::new_for_test(
+            number,
+            head,
+            base,
+            &format!("PR #{}", number),
+            PullRequestStatus::Open,
+            false,
+            None,
+            vec![],
+        ))
+    }
+
+    /// main <- feature-1 <- feature-2a <- feature-3a, and feature-1 <- feature-2b
+    fn make_forked_stack() -> FlatDep {
+        let pr1 = make_open_pr(1, "feature-1", "main");
+        let pr2 = make_open_pr(2, "feature-2a", "feature-1");
+        let pr3 = make_open_pr(3, "feature-3a", "feature-2a");
+        let pr4 = make_open_pr(4, "feature-2b", "feature-1");
+        crate::graph::log(&crate::graph::build(&[pr1, pr2, pr3, pr4]))
+    }
+
+    fn on(mut entry: StackEntry, parent: &str) -> StackEntry {
+        entry.parent = Some(parent.to_string());
+        entry
+    }
+
+    fn make_forked_entries() -> Vec<StackEntry> {
+        let entry = |branch: &str, parent: &str| {
+            on(
+                make_test_entry(branch, false, false, PrState::Open, None, vec![], 0),
+                parent,
+            )
+        };
+        vec![
+            entry("feature-2b", "feature-1"),
+            entry("feature-3a", "feature-2a"),
+            entry("feature-2a", "feature-1"),
+            entry("feature-1", "main"),
+            make_test_entry("main", false, true, PrState::NoPr, None, vec![], 0),
+        ]
+    }
+
+    #[test]
+    fn test_parent_indices() {
+        let rows = [
+            ("feature-2b", Some("feature-1")),
+            ("feature-2a", None),
+            ("feature-1", Some("main")),
+            ("orphan", Some("gone")),
+            ("main", None),
+        ];
+
+        assert_eq!(
+            parent_indices(&rows),
+            vec![Some(2), Some(2), Some(4), Some(4), None]
+        );
+    }
+
+    #[test]
+    fn test_graph_layout_linear() {
+        let layout = graph_layout(&[Some(1), Some(2), None]);
+
+        assert!(layout.iter().all(|row| row.column == 0 && row.joins == 0));
+        assert_eq!(
+            layout.iter().map(|row| row.lanes_after).collect::<Vec<_>>(),
+            vec![1, 1, 0]
+        );
+        assert!(!layout[2].continues);
+    }
+
+    #[test]
+    fn test_graph_layout_fork() {
+        // feature-2b, feature-3a, feature-2a, feature-1, main
+        let layout = graph_layout(&[Some(3), Some(2), Some(3), Some(4), None]);
+
+        let columns: Vec<usize> = layout.iter().map(|row| row.column).collect();
+        assert_eq!(columns, vec![0, 1, 1, 0, 0]);
+        assert_eq!(layout[3].joins, 1);
+        assert_eq!(layout[1].lanes_after, 2);
+        assert_eq!(layout[3].lanes_after, 1);
+    }
+
+    #[test]
+    fn test_place_in_tree_skips_hidden_parent() {
+        let pr1 = make_open_pr(1, "feature-1", "main");
+        let pr2 = make_open_pr(2, "feature-2", "feature-1");
+        let pr3 = make_open_pr(3, "feature-3", "feature-2");
+        let stack = crate::graph::log(&crate::graph::build(&[pr1, pr2, pr3]));
+
+        let placements = place_in_tree(&stack, &[1, 3], "main");
+
+        assert_eq!(placements[&3].parent, "feature-1");
+        assert_eq!(placements[&3].depth, 1);
+        assert_eq!(placements[&1].parent, "main");
+        assert_eq!(placements[&1].depth, 0);
+    }
+
+    #[test]
+    fn test_build_entries_forked_stack() {
+        let config = TreeConfig {
+            use_color: false,
+            use_unicode: false,
+            include_closed: false,
+        };
+
+        let entries = build_entries(&make_forked_stack(), None, &config);
+
+        let summary: Vec<(&str, Option<&str>, usize, usize)> = entries
+            .iter()
+            .map(|e| (e.branch.as_str(), e.parent.as_deref(), e.depth, e.siblings))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("feature-2b", Some("feature-1"), 1, 1),
+                ("feature-3a", Some("feature-2a"), 2, 0),
+                ("feature-2a", Some("feature-1"), 1, 1),
+                ("feature-1", Some("main"), 0, 0),
+                ("main", None, 0, 0),
+            ]
+        );
+    }
+
+    fn tracked(name: &str, parent: &str) -> TrackedBranch {
+        TrackedBranch {
+            name: name.to_string(),
+            parent: parent.to_string(),
+            stack: None,
+        }
+    }
+
+    #[test]
+    fn test_add_local_branches_places_branch_above_parent() {
+        let config = TreeConfig {
+            use_color: false,
+            use_unicode: false,
+            include_closed: false,
+        };
+        let entries = build_entries(&make_forked_stack(), None, &config);
+
+        let entries = add_local_branches(
+            entries,
+            &[
+                tracked("feature-1", "main"),
+                tracked("feature-3b", "feature-2b"),
+            ],
+            None,
+        );
+
+        let summary: Vec<(&str, Option<&str>, usize, usize, PrState)> = entries
+            .iter()
+            .map(|e| {
+                (
+                    e.branch.as_str(),
+                    e.parent.as_deref(),
+                    e.depth,
+                    e.siblings,
+                    e.pr_state,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("feature-3b", Some("feature-2b"), 2, 0, PrState::NoPr),
+                ("feature-2b", Some("feature-1"), 1, 1, PrState::Open),
+                ("feature-3a", Some("feature-2a"), 2, 0, PrState::Open),
+                ("feature-2a", Some("feature-1"), 1, 1, PrState::Open),
+                ("feature-1", Some("main"), 0, 0, PrState::Open),
+                ("main", None, 0, 0, PrState::NoPr),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_add_local_branches_without_prs() {
+        let entries = add_local_branches(
+            vec![],
+            &[tracked("part-2", "part-1"), tracked("part-1", "main")],
+            None,
+        );
+
+        let summary: Vec<(&str, bool, usize)> = entries
+            .iter()
+            .map(|e| (e.branch.as_str(), e.is_trunk, e.depth))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("part-2", false, 1),
+                ("part-1", false, 0),
+                ("main", true, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_add_local_branches_nothing_missing_keeps_entries() {
+        let config = TreeConfig {
+            use_color: false,
+            use_unicode: false,
+            include_closed: false,
+        };
+        let before: Vec<String> = build_entries(&make_forked_stack(), None, &config)
+            .into_iter()
+            .map(|e| e.branch)
+            .collect();
+
+        let after: Vec<String> = add_local_branches(
+            build_entries(&make_forked_stack(), None, &config),
+            &[tracked("feature-1", "main")],
+            None,
+        )
+        .into_iter()
+        .map(|e| e.branch)
+        .collect();
+
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn test_snapshot_forked_stack_unicode() {
+        let config = TreeConfig {
+            use_color: false,
+            use_unicode: true,
+            include_closed: false,
+        };
+
+        let output = render(&make_forked_entries(), &config, true);
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_snapshot_forked_stack_ascii() {
+        let config = TreeConfig {
+            use_color: false,
+            use_unicode: false,
+            include_closed: false,
+        };
+
+        let mut entries = make_forked_entries();
+        entries[1].commits = vec![CommitInfo {
+            sha: "a1b2c3d".to_string(),
+            message: "Add part 3a".to_string(),
+        }];
+
+        let output = render(&entries, &config, true);
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_snapshot_three_way_fork() {
+        let config = TreeConfig {
+            use_color: false,
+            use_unicode: true,
+            include_closed: false,
+        };
+
+        let entry = |branch: &str, parent: &str| {
+            on(
+                make_test_entry(branch, false, false, PrState::Open, None, vec![], 0),
+                parent,
+            )
+        };
+        let entries = vec![
+            entry("feature-c", "main"),
+            entry("feature-b", "main"),
+            entry("feature-a", "main"),
+            make_test_entry("main", false, true, PrState::NoPr, None, vec![], 0),
+        ];
+
+        let output = render(&entries, &config, true);
+        insta::assert_snapshot!(output);
+    }
 }